@@ -0,0 +1,186 @@
+//! A [Debug Adapter Protocol](https://microsoft.github.io/debug-adapter-protocol/) server for CHIP-8 ROMs, speaking stdio.
+//!
+//! ### Launch arguments
+//! *   `program`       — path to the `.ch8` ROM to debug.
+//! *   `quirks`        — `"vip"` (default), `"chip48"`, `"schip"`, or `"xochip"`.
+//! *   `sourceMap`     — path to a [`SourceMap`](maulingmonkey_chip8_interpreter::SourceMap) for source line breakpoints
+//!     (defaults to `program` + `.map`, if that exists.)  Relative source paths are relative to the map.
+//! *   `stopOnEntry`   — stop before the first instruction.
+//!
+//! ### Custom requests
+//! *   `keys`          — `{ "keys": [7, 10] }` holds exactly keys `0x7` and `0xA` until the next `keys` request.
+//!     Without one, no key is ever pressed, and `FX0A` waits forever (reported once per wait as `output`.)
+
+mod session;
+mod target;
+
+use serde_json::Value;
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+
+
+const FRAME : Duration = Duration::from_micros(1_000_000 / 60);
+
+fn main() {
+    let (requests, rx) = mpsc::channel();
+    std::thread::spawn(move || forward_requests(&mut io::stdin().lock(), &mut io::stderr(), &requests));
+
+    let mut session = session::Session::new(io::stdout());
+    let mut next_frame = Instant::now();
+    while !session.is_terminated() {
+        let request = if session.is_running() {
+            match rx.try_recv() {
+                Ok(request)                             => Some(request),
+                Err(mpsc::TryRecvError::Empty)          => None,
+                Err(mpsc::TryRecvError::Disconnected)   => break,
+            }
+        } else {
+            match rx.recv() {
+                Ok(request) => Some(request),
+                Err(_)      => break,
+            }
+        };
+
+        if let Some(request) = request {
+            session.handle(&request).expect("unable to write to stdout");
+            next_frame = Instant::now();
+        } else {
+            session.run_frame().expect("unable to write to stdout");
+            next_frame += FRAME;
+            if let Some(wait) = next_frame.checked_duration_since(Instant::now()) { std::thread::sleep(wait) }
+        }
+    }
+}
+
+/// Send messages from `r` to `requests` until the end of the stream, an I/O error, or `requests` disconnects.
+/// Malformed messages are logged to `log` and skipped.
+fn forward_requests(r: &mut impl BufRead, log: &mut impl Write, requests: &mpsc::Sender<Value>) {
+    loop {
+        match read_message(r) {
+            Ok(Some(request))                                   => if requests.send(request).is_err() { break },
+            Ok(None)                                            => break,
+            Err(err) if err.kind() == io::ErrorKind::InvalidData => { let _ = writeln!(log, "chip8-dap: skipping malformed message: {err}"); },
+            Err(err)                                            => { let _ = writeln!(log, "chip8-dap: unable to read stdin: {err}"); break },
+        }
+    }
+}
+
+/// Read one `Content-Length: N\r\n\r\n{json}` message.  Returns `None` at the end of the stream.
+fn read_message(r: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut len = None;
+    loop {
+        let mut line = String::new();
+        if r.read_line(&mut line)? == 0 { return Ok(None) }
+        let line = line.trim_end();
+        if line.is_empty() { if len.is_some() { break } else { continue } }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("Content-Length") { len = value.trim().parse::<usize>().ok() }
+        }
+    }
+    let mut body = vec![0u8; len.unwrap_or(0)];
+    r.read_exact(&mut body)?;
+    serde_json::from_slice(&body).map(Some).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+#[test] fn test_session() {
+    use serde_json::json;
+
+    let dir = std::env::temp_dir().join(format!("chip8-dap-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let rom = dir.join("game.ch8");
+    std::fs::write(&rom, [0x22, 0x06, 0x70, 0x01, 0x12, 0x00, 0x61, 0x05, 0x00, 0xEE]).unwrap(); // call 206; V0 += 1; goto 200; V1 = 5; return
+    std::fs::write(dir.join("game.ch8.map"), "0200 1 game.8o\n0202 2 game.8o\n0204 3 game.8o\n0206 5 game.8o\n0208 6 game.8o\n").unwrap();
+
+    let mut out = Vec::new();
+    let mut session = session::Session::new(&mut out);
+    let mut seq = 0;
+    let mut request = |session: &mut session::Session<_>, command: &str, arguments: Value| {
+        seq += 1;
+        session.handle(&json!({ "seq": seq, "type": "request", "command": command, "arguments": arguments })).unwrap();
+    };
+
+    request(&mut session, "initialize", json!({ "adapterID": "chip8" }));
+    request(&mut session, "launch", json!({ "program": rom, "stopOnEntry": true }));
+    request(&mut session, "setBreakpoints", json!({ "source": { "path": dir.join("game.8o") }, "breakpoints": [{ "line": 6 }, { "line": 4 }] }));
+    request(&mut session, "configurationDone", json!({}));
+    request(&mut session, "continue", json!({}));
+    while session.is_running() { session.run_frame().unwrap() }
+    request(&mut session, "stackTrace", json!({ "threadId": 1 }));
+    request(&mut session, "variables", json!({ "variablesReference": 1 }));
+    request(&mut session, "stepOut", json!({}));
+    while session.is_running() { session.run_frame().unwrap() }
+    request(&mut session, "next", json!({}));
+    request(&mut session, "disconnect", json!({}));
+    drop(session);
+
+    let mut messages = Vec::new();
+    let mut r = &out[..];
+    while let Some(message) = read_message(&mut r).unwrap() { messages.push(message) }
+    let find = |pred: &dyn Fn(&Value) -> bool| messages.iter().filter(|m| pred(m)).cloned().collect::<Vec<_>>();
+
+    assert!(find(&|m| m["type"] == "response").iter().all(|m| m["success"] == true), "{messages:#?}");
+    let stops = find(&|m| m["event"] == "stopped").iter().map(|m| m["body"]["reason"].as_str().unwrap().to_string()).collect::<Vec<_>>();
+    assert_eq!(stops, ["entry", "breakpoint", "step", "step"]);
+
+    let bps = &find(&|m| m["command"] == "setBreakpoints")[0]["body"]["breakpoints"];
+    assert_eq!(bps[0]["verified"], true);
+    assert_eq!(bps[1]["verified"], false);
+
+    let frames = &find(&|m| m["command"] == "stackTrace")[0]["body"]["stackFrames"];
+    assert_eq!(frames.as_array().unwrap().len(), 2);
+    assert_eq!(frames[0]["instructionPointerReference"], "0x208");
+    assert_eq!(frames[0]["line"], 6);
+    assert_eq!(frames[1]["line"], 1);
+    assert!(frames[0]["name"].as_str().unwrap().starts_with("sub_206"));
+
+    let vars = &find(&|m| m["command"] == "variables")[0]["body"]["variables"];
+    assert_eq!(vars[1], json!({ "name": "V1", "value": "0x05 (5)", "variablesReference": 0 }));
+    assert_eq!(vars[17]["value"], "0x208");
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test] fn test_keys() {
+    use serde_json::json;
+
+    let rom = std::env::temp_dir().join(format!("chip8-dap-test-keys-{}.ch8", std::process::id()));
+    std::fs::write(&rom, [0xF3, 0x0A, 0x00, 0xFD]).unwrap(); // V3 = key; exit
+    let mut out = Vec::new();
+    let mut session = session::Session::new(&mut out);
+    let mut seq = 0;
+    let mut request = |session: &mut session::Session<_>, command: &str, arguments: Value| {
+        seq += 1;
+        session.handle(&json!({ "seq": seq, "type": "request", "command": command, "arguments": arguments })).unwrap();
+    };
+
+    request(&mut session, "launch", json!({ "program": rom, "quirks": "schip" }));
+    request(&mut session, "configurationDone", json!({}));
+    for _ in 0 .. 3 { session.run_frame().unwrap() }
+    assert!(session.is_running());
+    request(&mut session, "keys", json!({ "keys": [16] }));
+    request(&mut session, "keys", json!({ "keys": [7, 10] }));
+    for _ in 0 .. 3 { session.run_frame().unwrap() }
+    assert!(session.is_terminated());
+    drop(session);
+    let _ = std::fs::remove_file(&rom);
+
+    let mut messages = Vec::new();
+    let mut r = &out[..];
+    while let Some(message) = read_message(&mut r).unwrap() { messages.push(message) }
+    let outputs = messages.iter().filter(|m| m["event"] == "output").count();
+    let keys = messages.iter().filter(|m| m["command"] == "keys").map(|m| m["success"].clone()).collect::<Vec<_>>();
+    assert_eq!(outputs, 1, "{messages:#?}");
+    assert_eq!(keys, [false, true]);
+}
+
+#[test] fn test_malformed() {
+    let body = r#"{"seq":1,"type":"request","command":"threads"}"#;
+    let stdin = format!("Content-Length: 8\r\n\r\nnot jsonContent-Length: {}\r\n\r\n{body}", body.len());
+    let (requests, rx) = mpsc::channel();
+    let mut log = Vec::new();
+    forward_requests(&mut stdin.as_bytes(), &mut log, &requests);
+    assert_eq!(rx.try_iter().map(|r| r["command"].clone()).collect::<Vec<_>>(), ["threads"]);
+    assert!(String::from_utf8(log).unwrap().starts_with("chip8-dap: skipping malformed message: "));
+}
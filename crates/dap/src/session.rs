@@ -0,0 +1,350 @@
+use crate::target::{self, Target};
+use maulingmonkey_chip8_interpreter::*;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+
+
+const THREAD        : i64   = 1;
+const REGISTERS     : i64   = 1; // variablesReference
+const TIMERS        : i64   = 2; // variablesReference
+
+/// Where a step request is headed, checked after every instruction while running.
+#[derive(Clone, Copy)] enum Goal {
+    Continue,
+    Over { ret: Addr, depth: usize },
+    Out { depth: usize },
+}
+
+/// One debug session: a [`Target`], its breakpoints, and the DAP messages to the client.
+pub struct Session<W: Write> {
+    out:                        W,
+    seq:                        i64,
+    target:                     Option<Box<dyn Target>>,
+    debugger:                   Debugger,
+    source_map:                 SourceMap,
+    source_map_dir:             PathBuf,
+    source_breakpoints:         BTreeMap<String, Vec<Addr>>,
+    instruction_breakpoints:    Vec<Addr>,
+    stop_on_entry:              bool,
+    goal:                       Option<Goal>, // Some(_) while running
+    pending:                    Option<StopReason>, // reported after a synchronous step's response
+    check:                      bool, // check breakpoints before the next instruction (false when resuming from a stop)
+    awaiting_key:               bool, // reported FX0A blocking until a `keys` request
+    terminated:                 bool,
+
+    /// Instructions executed per [`run_frame`](Self::run_frame) (≈ 500 Hz at 60 Hz.)
+    pub steps_per_frame:        usize,
+}
+
+impl<W: Write> Session<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            seq:                        1,
+            target:                     None,
+            debugger:                   Debugger::new(),
+            source_map:                 SourceMap::new(),
+            source_map_dir:             PathBuf::new(),
+            source_breakpoints:         BTreeMap::new(),
+            instruction_breakpoints:    Vec::new(),
+            stop_on_entry:              false,
+            goal:                       None,
+            pending:                    None,
+            check:                      false,
+            awaiting_key:               false,
+            terminated:                 false,
+            steps_per_frame:            8,
+        }
+    }
+
+    pub fn is_running(&self) -> bool { self.goal.is_some() && !self.terminated }
+    pub fn is_terminated(&self) -> bool { self.terminated }
+
+    /// Handle one DAP request.
+    pub fn handle(&mut self, request: &Value) -> io::Result<()> {
+        let command = request["command"].as_str().unwrap_or_default();
+        let args = &request["arguments"];
+        let result = match command {
+            "initialize"                => Ok(json!({
+                "supportsConfigurationDoneRequest":     true,
+                "supportsInstructionBreakpoints":       true,
+                "supportsSteppingGranularity":          false,
+                "supportsTerminateRequest":             true,
+            })),
+            "launch"                    => self.launch(args),
+            "setBreakpoints"            => Ok(self.set_breakpoints(args)),
+            "setInstructionBreakpoints" => Ok(self.set_instruction_breakpoints(args)),
+            "setExceptionBreakpoints"   => Ok(json!({})),
+            "configurationDone"         => { self.configuration_done(); Ok(json!({})) },
+            "threads"                   => Ok(json!({ "threads": [{ "id": THREAD, "name": "CHIP-8" }] })),
+            "stackTrace"                => self.stack_trace(),
+            "scopes"                    => Ok(json!({ "scopes": [
+                { "name": "Registers",  "variablesReference": REGISTERS,    "expensive": false },
+                { "name": "Timers",     "variablesReference": TIMERS,       "expensive": false },
+            ]})),
+            "variables"                 => self.variables(args),
+            "continue"                  => { self.resume(Goal::Continue); Ok(json!({ "allThreadsContinued": true })) },
+            "next"                      => self.step_over(),
+            "stepIn"                    => self.step_in(),
+            "stepOut"                   => self.step_out(),
+            "pause"                     => { if self.is_running() { self.goal = None; self.stopped("pause", None)? } Ok(json!({})) },
+            "disconnect" | "terminate"  => { self.terminated = true; Ok(json!({})) },
+            "keys"                      => self.keys(args),
+            _                           => Err(format!("unsupported request {command:?}")),
+        };
+
+        let (success, body, message) = match result {
+            Ok(body)    => (true, body, None),
+            Err(msg)    => (false, Value::Null, Some(msg)),
+        };
+        self.send(json!({
+            "type":         "response",
+            "request_seq":  request["seq"],
+            "success":      success,
+            "command":      command,
+            "message":      message,
+            "body":         body,
+        }))?;
+
+        // events that must follow their response
+        match command {
+            "initialize"                            => self.event("initialized", json!({})),
+            "configurationDone" if !self.is_running() && self.target.is_some() => self.stopped("entry", None),
+            "next" | "stepIn" if success && !self.is_running() => self.after_step(),
+            "disconnect" | "terminate"              => self.event("terminated", json!({})),
+            _                                       => Ok(()),
+        }
+    }
+
+    /// Run one frame's worth of instructions while a `continue`/`next`/`stepOut` is in progress.
+    pub fn run_frame(&mut self) -> io::Result<()> {
+        let (Some(goal), Some(target)) = (self.goal, self.target.as_mut()) else { return Ok(()) };
+        if self.check {
+            if let Some(stop) = target.check(&self.debugger) { return self.stop(stop) }
+        }
+        let mut done = |pc: Addr, depth: usize| match goal {
+            Goal::Continue          => false,
+            Goal::Over { ret, depth: d } => depth <= d && pc == ret,
+            Goal::Out { depth: d }  => depth < d,
+        };
+        let stop = target.run_until(&self.debugger, self.steps_per_frame.max(1), &mut done);
+        let awaiting_key = stop == StopReason::Blocked(StepOutcome::AwaitingKey);
+        match stop {
+            StopReason::StepLimit   => self.check = true,
+            StopReason::Blocked(_)  => self.check = false, // already checked
+            stop                    => return self.stop(stop),
+        }
+        target.step_clocks();
+        if awaiting_key && !self.awaiting_key {
+            self.event("output", json!({ "category": "console", "output": "FX0A is waiting for a key: send a \"keys\" request to press some\n" }))?;
+        }
+        self.awaiting_key = awaiting_key;
+        Ok(())
+    }
+
+    fn launch(&mut self, args: &Value) -> Result<Value, String> {
+        let program = PathBuf::from(args["program"].as_str().ok_or("launch: expected a \"program\" path to a .ch8 ROM")?);
+        let rom = std::fs::read(&program).map_err(|err| format!("unable to read {}: {err}", program.display()))?;
+        let quirks = args["quirks"].as_str().unwrap_or("vip").parse::<Quirks>().map_err(|err| format!("launch: {err}"))?;
+
+        let map_path = match args["sourceMap"].as_str() {
+            Some(path)  => Some(PathBuf::from(path)),
+            None        => Some(PathBuf::from(format!("{}.map", program.display()))).filter(|p| p.exists()),
+        };
+        if let Some(map_path) = map_path {
+            let file = std::fs::File::open(&map_path).map_err(|err| format!("unable to open {}: {err}", map_path.display()))?;
+            self.source_map = SourceMap::read(io::BufReader::new(file)).map_err(|err| format!("unable to read {}: {err}", map_path.display()))?;
+            self.source_map_dir = map_path.parent().map(Path::to_path_buf).unwrap_or_default();
+        }
+
+        self.target = Some(target::create(&rom, quirks)?);
+        self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+        Ok(json!({}))
+    }
+
+    /// Hold exactly `keys` (`0x0` ..= `0xF`), until the next `keys` request.
+    fn keys(&mut self, args: &Value) -> Result<Value, String> {
+        let keys = args["keys"].as_array().ok_or("keys: expected a \"keys\" array of keys to hold")?;
+        let held = keys.iter().try_fold(0u16, |held, key| match key.as_u64() {
+            Some(key @ 0 ..= 0xF)   => Ok(held | 1 << key),
+            _                       => Err(format!("keys: {key} isn't on the keypad (0 ..= 15)")),
+        })?;
+        self.target.as_mut().ok_or("not launched")?.set_keys(held);
+        Ok(json!({}))
+    }
+
+    fn configuration_done(&mut self) {
+        if self.target.is_some() && !self.stop_on_entry { self.resume(Goal::Continue) }
+    }
+
+    fn set_breakpoints(&mut self, args: &Value) -> Value {
+        let path = args["source"]["path"].as_str().unwrap_or_default().to_string();
+        let mut addrs = Vec::new();
+        let breakpoints = args["breakpoints"].as_array().into_iter().flatten().map(|bp| {
+            let line = bp["line"].as_u64().unwrap_or(0) as u32;
+            let found = self.source_map.addrs(&path, line).collect::<Vec<_>>();
+            addrs.extend_from_slice(&found);
+            match found.first() {
+                Some(addr)  => json!({ "verified": true, "line": line, "instructionReference": reference(*addr) }),
+                None        => json!({ "verified": false, "line": line, "message": "no code at this line in the source map" }),
+            }
+        }).collect::<Vec<_>>();
+        self.source_breakpoints.insert(path, addrs);
+        self.sync_breakpoints();
+        json!({ "breakpoints": breakpoints })
+    }
+
+    fn set_instruction_breakpoints(&mut self, args: &Value) -> Value {
+        self.instruction_breakpoints.clear();
+        let breakpoints = args["breakpoints"].as_array().into_iter().flatten().map(|bp| {
+            let addr = bp["instructionReference"].as_str().and_then(parse_reference).map(|a| a + bp["offset"].as_i64().unwrap_or(0));
+            match addr.and_then(|a| u16::try_from(a).ok()) {
+                Some(addr)  => { self.instruction_breakpoints.push(Addr(addr)); json!({ "verified": true, "instructionReference": reference(Addr(addr)) }) },
+                None        => json!({ "verified": false, "message": "invalid instruction reference" }),
+            }
+        }).collect::<Vec<_>>();
+        self.sync_breakpoints();
+        json!({ "breakpoints": breakpoints })
+    }
+
+    fn sync_breakpoints(&mut self) {
+        for addr in self.debugger.breakpoints().collect::<Vec<_>>() { self.debugger.remove_breakpoint(addr); }
+        for &addr in self.source_breakpoints.values().flatten().chain(self.instruction_breakpoints.iter()) { self.debugger.add_breakpoint(addr); }
+    }
+
+    fn stack_trace(&self) -> Result<Value, String> {
+        let target = self.target.as_ref().ok_or("not launched")?;
+        let memory = target.memory();
+        let op_at = |addr: Addr| memory.get(addr.to_usize() .. addr.to_usize() + 2).map_or(0, |b| u16::from_be_bytes([b[0], b[1]]));
+
+        // innermost first: the current pc, then each call site (return address - 2)
+        let calls = target.call_stack();
+        let mut locations = vec![target.registers().pc];
+        locations.extend(calls.iter().rev().map(|ret| Addr(ret.0.wrapping_sub(2))));
+
+        let frames = locations.iter().enumerate().map(|(i, &addr)| {
+            let name = match locations.get(i + 1) {
+                Some(&call) => format!("sub_{:03X}", op_at(call) & 0xFFF),
+                None        => "main".to_string(),
+            };
+            let mut frame = json!({
+                "id":                           i,
+                "name":                         format!("{name} @ {addr}: {:?}", Op(op_at(addr))),
+                "line":                         0,
+                "column":                       0,
+                "instructionPointerReference":  reference(addr),
+            });
+            if let Some(loc) = self.source_map.location(addr) {
+                frame["source"] = json!({ "path": self.source_map_dir.join(&loc.file) });
+                frame["line"] = json!(loc.line);
+                frame["column"] = json!(1);
+            }
+            frame
+        }).collect::<Vec<_>>();
+        Ok(json!({ "stackFrames": frames, "totalFrames": locations.len() }))
+    }
+
+    fn variables(&self, args: &Value) -> Result<Value, String> {
+        let regs = self.target.as_ref().ok_or("not launched")?.registers();
+        let var = |name: String, value: String| json!({ "name": name, "value": value, "variablesReference": 0 });
+        let vars = match args["variablesReference"].as_i64() {
+            Some(REGISTERS) => {
+                let mut vars = V::iter().map(|v| var(format!("{v}"), format!("0x{0:02X} ({0})", regs[v]))).collect::<Vec<_>>();
+                vars.push(var("I".into(), format!("0x{:03X}", regs.i.0)));
+                vars.push(var("PC".into(), format!("0x{:03X}", regs.pc.0)));
+                vars
+            },
+            Some(TIMERS) => vec![
+                var("DT".into(), regs.delay_timer().to_string()),
+                var("ST".into(), regs.sound_timer().to_string()),
+            ],
+            _ => return Err("unknown variablesReference".into()),
+        };
+        Ok(json!({ "variables": vars }))
+    }
+
+    fn step_over(&mut self) -> Result<Value, String> {
+        let target = self.target.as_ref().ok_or("not launched")?;
+        let pc = target.registers().pc;
+        let op = target.memory().get(pc.to_usize() .. pc.to_usize() + 2).map_or(0, |b| u16::from_be_bytes([b[0], b[1]]));
+        if op & 0xF000 != 0x2000 { return self.step_in() }
+        let depth = target.call_stack().len();
+        self.resume(Goal::Over { ret: Addr(pc.0.wrapping_add(2)), depth });
+        Ok(json!({}))
+    }
+
+    fn step_out(&mut self) -> Result<Value, String> {
+        let depth = self.target.as_ref().ok_or("not launched")?.call_stack().len();
+        self.resume(Goal::Out { depth });
+        Ok(json!({}))
+    }
+
+    fn step_in(&mut self) -> Result<Value, String> {
+        let target = self.target.as_mut().ok_or("not launched")?;
+        let mut stop = target.run_until(&self.debugger, 1, &mut |_, _| true);
+        if stop == StopReason::Blocked(StepOutcome::AwaitingVBlank) {
+            target.step_clocks();
+            stop = target.run_until(&self.debugger, 1, &mut |_, _| true);
+        }
+        self.pending = Some(stop);
+        Ok(json!({}))
+    }
+
+    fn after_step(&mut self) -> io::Result<()> {
+        match self.pending.take() {
+            Some(stop)  => self.stop(stop),
+            None        => Ok(()),
+        }
+    }
+
+    fn resume(&mut self, goal: Goal) {
+        self.goal = Some(goal);
+        self.check = false;
+    }
+
+    fn stop(&mut self, stop: StopReason) -> io::Result<()> {
+        self.goal = None;
+        match stop {
+            StopReason::Stepped | StopReason::StepLimit | StopReason::Blocked(_) => self.stopped("step", None),
+            StopReason::Breakpoint(_)               => self.stopped("breakpoint", None),
+            StopReason::Watchpoint { .. }           => self.stopped("data breakpoint", None),
+            StopReason::Fault(fault)                => {
+                self.event("output", json!({ "category": "stderr", "output": format!("{fault}\n") }))?;
+                self.stopped("exception", Some(fault.to_string()))
+            },
+            StopReason::Exited                      => {
+                self.terminated = true;
+                self.event("exited", json!({ "exitCode": 0 }))?;
+                self.event("terminated", json!({}))
+            },
+        }
+    }
+
+    fn stopped(&mut self, reason: &str, text: Option<String>) -> io::Result<()> {
+        self.event("stopped", json!({ "reason": reason, "description": text, "text": text, "threadId": THREAD, "allThreadsStopped": true }))
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        message["seq"] = json!(self.seq);
+        self.seq += 1;
+        let message = message.to_string();
+        write!(self.out, "Content-Length: {}\r\n\r\n{message}", message.len())?;
+        self.out.flush()
+    }
+}
+
+fn reference(addr: Addr) -> String { format!("0x{:03X}", addr.0) }
+fn parse_reference(r: &str) -> Option<i64> {
+    let r = r.trim();
+    match r.strip_prefix("0x").or_else(|| r.strip_prefix("0X")) {
+        Some(hex)   => i64::from_str_radix(hex, 16).ok(),
+        None        => r.parse().ok(),
+    }
+}
@@ -0,0 +1,55 @@
+use maulingmonkey_chip8_interpreter::*;
+use std::cell::Cell;
+
+
+
+/// An object safe [`Context`], so a session can debug either [`Memory4K`] or [`Memory64K`] (XO-CHIP) programs.
+pub trait Target {
+    fn registers(&self) -> &Registers;
+    fn memory(&self) -> &[u8];
+    fn call_stack(&self) -> Vec<Addr>;
+    fn check(&self, debugger: &Debugger) -> Option<StopReason>;
+    fn run_until(&mut self, debugger: &Debugger, max_steps: usize, done: &mut dyn FnMut(Addr, usize) -> bool) -> StopReason;
+    fn step_clocks(&mut self);
+    fn set_keys(&mut self, held: u16);
+}
+
+/// The [`Syscalls`] of a [`Target`]: keys held by the client (bit N for key N), set with the `keys` request.
+#[derive(Default)] pub struct Keypad {
+    held: Cell<u16>,
+}
+
+impl Syscalls for Keypad {
+    fn rand(&self) -> u8 { ().rand() }
+    fn get_key(&self) -> Option<u8> { (self.held.get() != 0).then(|| self.held.get().trailing_zeros() as u8) }
+    fn is_pressed(&self, key: u8) -> bool { key < 16 && self.held.get() & (1 << key) != 0 }
+    fn sound_play(&self) {}
+    fn sound_stop(&self) {}
+    fn render(&self, _screen: &ScreenMonochrome64x32) {}
+}
+
+impl<M: Memory> Target for Context<Keypad, M> {
+    fn registers(&self) -> &Registers { &self.registers }
+    fn memory(&self) -> &[u8] { self.memory.as_slice_ref() }
+    fn call_stack(&self) -> Vec<Addr> { Context::call_stack(self).collect() }
+    fn check(&self, debugger: &Debugger) -> Option<StopReason> { debugger.check(self) }
+    fn run_until(&mut self, debugger: &Debugger, max_steps: usize, done: &mut dyn FnMut(Addr, usize) -> bool) -> StopReason {
+        debugger.run_until(self, max_steps, |ctx| done(ctx.registers.pc, ctx.registers.call_depth().into()))
+    }
+    fn step_clocks(&mut self) { Context::step_clocks(self) }
+    fn set_keys(&mut self, held: u16) { self.syscalls.held.set(held) }
+}
+
+/// Create a [`Target`] running `rom` with `quirks`, loaded by [`Context::load_rom`].
+pub fn create(rom: &[u8], quirks: Quirks) -> Result<Box<dyn Target>, String> {
+    fn create<M: Memory + 'static>(rom: &[u8], quirks: Quirks) -> Result<Box<dyn Target>, String> {
+        let mut ctx = Context::<Keypad, M>::default();
+        ctx.load_rom(rom, quirks).map_err(|err| err.to_string())?;
+        Ok(Box::new(ctx))
+    }
+
+    match quirks.instruction_set {
+        InstructionSet::XoChip  => create::<Memory64K>(rom, quirks),
+        _                       => create::<Memory4K >(rom, quirks),
+    }
+}
@@ -0,0 +1,22 @@
+//! Headless, deterministic CHIP-8 ROM tests.
+//!
+//! ```no_run
+//! use maulingmonkey_chip8_harness::*;
+//!
+//! let mut rom = Harness::load("game.ch8", Quirks::CHIP_48, FrameTiming::default()).unwrap();
+//! rom.keys_at(10, &[0x5]).keys_at(12, &[]);   // tap 5 on frame 10
+//! rom.frames(60);
+//! rom.assert_v(0x3, 1).assert_sound(false).assert_screen("
+//!     .####.
+//!     .#..#.
+//!     .####.
+//! ");
+//! ```
+
+#![deny(non_snake_case)]            // match { ... } bugs
+#![deny(unreachable_patterns)]      // match { ... } bugs
+
+mod ascii;                          pub use ascii::*;
+mod harness;                        pub use harness::*;
+
+pub use maulingmonkey_chip8_interpreter::{Addr, Context, Fault, FrameTiming, Quirks, ScreenMonochrome64x32, VipTiming};
@@ -0,0 +1,62 @@
+use crate::*;
+
+
+
+/// Parse ASCII art into a screen: `#` (or `X`, `█`) is a lit pixel, `.` an unlit one.
+///
+/// Each line is trimmed, and leading and trailing blank lines are skipped, so literals can be indented like code.
+/// The art is the screen's top left corner: missing rows and columns are unlit.
+///
+/// ### Panics
+/// On any other characters, or art larger than 64 x 32.
+#[track_caller] pub fn screen_from_ascii(art: &str) -> ScreenMonochrome64x32 {
+    let mut screen = ScreenMonochrome64x32::new();
+    let lines = art.lines().map(str::trim).collect::<Vec<_>>();
+    let first = lines.iter().position(|l| !l.is_empty()).unwrap_or(lines.len());
+    let last  = lines.iter().rposition(|l| !l.is_empty()).map_or(first, |l| l + 1);
+    let lines = &lines[first .. last];
+    assert!(lines.len() <= 32, "ASCII art screen has {} rows, expected at most 32", lines.len());
+    for (y, line) in lines.iter().enumerate() {
+        assert!(line.chars().count() <= 64, "ASCII art screen row {y} has {} columns, expected at most 64", line.chars().count());
+        for (x, ch) in line.chars().enumerate() {
+            let lit = match ch {
+                '#' | 'X' | '█' => true,
+                '.'             => false,
+                other           => panic!("ASCII art screen row {y} column {x}: expected `#` or `.`, got {other:?}"),
+            };
+            screen.set_pixel(x, y, lit);
+        }
+    }
+    screen
+}
+
+/// Format a screen as 32 lines of 64 `#`s and `.`s, for [`screen_from_ascii`].
+pub fn screen_to_ascii(screen: &ScreenMonochrome64x32) -> String {
+    let mut art = String::with_capacity(65 * 32);
+    for y in 0 .. 32 {
+        art.extend((0 .. 64).map(|x| if screen.get_pixel(x, y) { '#' } else { '.' }));
+        art.push('\n');
+    }
+    art
+}
+
+/// Compare screens by pixel.
+pub fn screens_eq(a: &ScreenMonochrome64x32, b: &ScreenMonochrome64x32) -> bool {
+    (0 .. 32).all(|y| (0 .. 64).all(|x| a.get_pixel(x, y) == b.get_pixel(x, y)))
+}
+
+#[test] fn test_ascii() {
+    let screen = screen_from_ascii("
+
+        #.#
+        .█
+
+    ");
+    assert!(screen.get_pixel(0, 0) && !screen.get_pixel(1, 0) && screen.get_pixel(2, 0) && screen.get_pixel(1, 1));
+    assert_eq!((0 .. 32).flat_map(|y| (0 .. 64).map(move |x| (x, y))).filter(|&(x, y)| screen.get_pixel(x, y)).count(), 3);
+
+    let art = screen_to_ascii(&screen);
+    assert!(art.starts_with("#.#...") && art.lines().count() == 32);
+    assert!(screens_eq(&screen_from_ascii(&art), &screen));
+    assert!(!screens_eq(&screen_from_ascii("#"), &screen));
+}
@@ -0,0 +1,230 @@
+use crate::*;
+use maulingmonkey_chip8_interpreter::{Memory, Rng, Syscalls};
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+
+
+/// A ROM loaded into a [`Context`], run a frame at a time with scripted keys.
+///
+/// Runs are deterministic: [`Context::rng`] is [seeded](Rng::seeded) with 0, and nothing depends on wall clock time.
+/// Assertions [`panic`] with context, and return `self` so they can be chained.
+pub struct Harness {
+    pub ctx:    Context<Keypad>,
+
+    /// How each frame's instructions are timed: <code>[FrameTiming]::[Vip](FrameTiming::Vip)</code> for the COSMAC VIP, or a flat <code>[FrameTiming]::[Steps](FrameTiming::Steps)</code> rate.
+    pub timing: FrameTiming,
+
+    frame:      u64,
+    script:     BTreeMap<u64, u16>,
+}
+
+/// The [`Syscalls`] of a [`Harness`]: scripted keys in, sound and rendered screens out.
+#[derive(Default)] pub struct Keypad {
+    held:       Cell<u16>,
+    sound:      Cell<bool>,
+    screen:     Cell<ScreenMonochrome64x32>,
+}
+
+impl Syscalls for Keypad {
+    fn rand(&self) -> u8 { 0 } // unused with a seeded Context::rng
+    fn get_key(&self) -> Option<u8> { (self.held.get() != 0).then(|| self.held.get().trailing_zeros() as u8) }
+    fn is_pressed(&self, key: u8) -> bool { key < 16 && self.held.get() & (1 << key) != 0 }
+    fn sound_play(&self) { self.sound.set(true) }
+    fn sound_stop(&self) { self.sound.set(false) }
+    fn render(&self, screen: &ScreenMonochrome64x32) { self.screen.set(*screen) }
+}
+
+impl Harness {
+    /// Load `rom` at [`Addr::PROGRAM_START_TYPICAL`], with fonts, like [`Context::load_rom`].
+    ///
+    /// Fails with [`io::ErrorKind::InvalidData`] if `rom` doesn't fit in memory.
+    pub fn new(rom: &[u8], quirks: Quirks, timing: FrameTiming) -> io::Result<Self> {
+        let mut ctx = Context::<Keypad>::default();
+        ctx.rng = Rng::seeded(0);
+        ctx.load_rom(rom, quirks)?;
+        Ok(Self { ctx, timing, frame: 0, script: BTreeMap::new() })
+    }
+
+    /// Read and [`new`](Self::new) a `.ch8` file.
+    pub fn load(path: impl AsRef<Path>, quirks: Quirks, timing: FrameTiming) -> io::Result<Self> {
+        Self::new(&std::fs::read(path)?, quirks, timing)
+    }
+
+    /// Frames run so far.
+    pub fn frame(&self) -> u64 { self.frame }
+
+    /// Hold exactly `keys` (`0x0` ..= `0xF`) from the start of `frame` on, until the next scripted frame.
+    #[track_caller] pub fn keys_at(&mut self, frame: u64, keys: &[u8]) -> &mut Self {
+        assert!(frame >= self.frame, "frame {frame} has already run (now at frame {})", self.frame);
+        self.script.insert(frame, keys_mask(keys));
+        self
+    }
+
+    /// Hold exactly `keys` now.
+    #[track_caller] pub fn hold(&mut self, keys: &[u8]) -> &mut Self {
+        self.ctx.syscalls.held.set(keys_mask(keys));
+        self
+    }
+
+    /// Run `frames` frames, or until the program [exits](Self::exited).  Frames after the exit aren't run or counted.
+    pub fn try_frames(&mut self, frames: u64) -> Result<&mut Self, Fault> {
+        for _ in 0 .. frames {
+            if self.exited() { break }
+            if let Some(keys) = self.script.remove(&self.frame) { self.ctx.syscalls.held.set(keys) }
+            self.frame += 1;
+            self.ctx.try_step_frame(&mut self.timing)?;
+        }
+        Ok(self)
+    }
+
+    /// Run `frames` frames, or until the program [exits](Self::exited).  Panics on [`Fault`]s.
+    #[track_caller] pub fn frames(&mut self, frames: u64) -> &mut Self {
+        let frame = self.frame;
+        match self.try_frames(frames) {
+            Ok(_) => self,
+            Err(fault) => panic!("{fault} (while running frames {frame} .. {})", frame + frames),
+        }
+    }
+
+    /// `V0` ..= `VF`.
+    #[track_caller] pub fn v(&self, v: u8) -> u8 { self.ctx.registers.v[usize::from(v)] }
+
+    /// `len` bytes of memory starting at `addr`.
+    pub fn memory(&self, addr: Addr, len: usize) -> Vec<u8> {
+        (0 .. len).map(|offset| self.ctx.memory.read(Addr(addr.0.wrapping_add(offset as u16)))).collect()
+    }
+
+    /// The screen as last rendered (at the end of the last frame.)  SUPER-CHIP and XO-CHIP screens are downsampled.
+    pub fn screen(&self) -> ScreenMonochrome64x32 { self.ctx.syscalls.screen.get() }
+
+    /// `true` if sound is playing.
+    pub fn sound(&self) -> bool { self.ctx.syscalls.sound.get() }
+
+    /// `true` if the program has exited (SUPER-CHIP `00FD`.)
+    pub fn exited(&self) -> bool { self.ctx.has_exited() }
+
+    #[track_caller] pub fn assert_v(&self, v: u8, value: u8) -> &Self {
+        assert_eq!(self.v(v), value, "V{v:X} after frame {}", self.frame);
+        self
+    }
+
+    #[track_caller] pub fn assert_i(&self, i: u16) -> &Self {
+        assert_eq!(self.ctx.registers.i, Addr(i), "I after frame {}", self.frame);
+        self
+    }
+
+    #[track_caller] pub fn assert_pc(&self, pc: u16) -> &Self {
+        assert_eq!(self.ctx.registers.pc, Addr(pc), "PC after frame {}", self.frame);
+        self
+    }
+
+    /// Assert memory starting at `addr` matches `expected`.
+    #[track_caller] pub fn assert_memory(&self, addr: u16, expected: &[u8]) -> &Self {
+        assert_eq!(self.memory(Addr(addr), expected.len()), expected, "memory at 0x{addr:03X} after frame {}", self.frame);
+        self
+    }
+
+    /// Assert the screen matches ASCII `art` (see [`screen_from_ascii`]): unspecified pixels must be unlit.
+    #[track_caller] pub fn assert_screen(&self, art: &str) -> &Self {
+        let (expected, actual) = (screen_from_ascii(art), self.screen());
+        if !screens_eq(&expected, &actual) {
+            panic!("screen mismatch after frame {}\nexpected:\n{}actual:\n{}", self.frame, screen_to_ascii(&expected), screen_to_ascii(&actual));
+        }
+        self
+    }
+
+    #[track_caller] pub fn assert_sound(&self, playing: bool) -> &Self {
+        assert_eq!(self.sound(), playing, "sound playing after frame {}", self.frame);
+        self
+    }
+}
+
+#[track_caller] fn keys_mask(keys: &[u8]) -> u16 {
+    keys.iter().fold(0, |mask, &key| {
+        assert!(key < 16, "key 0x{key:X} isn't on the keypad (0x0 ..= 0xF)");
+        mask | 1 << key
+    })
+}
+
+
+
+#[test] fn test_keys_and_sound() {
+    let rom = [
+        0xE0, 0xA1,             // 200: skip unless V0 pressed
+        0x71, 0x01,             // 202: V1 += 1
+        0xE0, 0x9E,             // 204: skip if V0 pressed
+        0x72, 0x01,             // 206: V2 += 1
+        0xF3, 0x0A,             // 208: V3 <- key
+        0x64, 0x03,             // 20A: V4 <- 3
+        0xF4, 0x18,             // 20C: ST <- V4
+        0x12, 0x0E,             // 20E: goto 20E
+    ];
+    let mut h = Harness::new(&rom, Quirks::CHIP_48, FrameTiming::default()).unwrap();
+    h.keys_at(2, &[0x7, 0xA]).keys_at(3, &[]);
+    h.frames(2).assert_v(1, 0).assert_v(2, 1).assert_pc(0x208).assert_sound(false);
+    h.frames(1).assert_v(3, 0x7).assert_i(0).assert_sound(true);
+    h.frames(2).assert_sound(false).assert_memory(0x20A, &[0x64, 0x03]);
+    assert_eq!(h.frame(), 5);
+}
+
+#[test] fn test_exit() {
+    let rom = [
+        0x60, 0x01,             // 200: V0 <- 1
+        0x70, 0x01,             // 202: V0 += 1
+        0x00, 0xFD,             // 204: exit
+    ];
+    let mut h = Harness::new(&rom, Quirks::SUPER_CHIP, FrameTiming::default()).unwrap();
+    assert!(!h.exited());
+    h.frames(10).assert_v(0, 2).assert_pc(0x204);
+    assert!(h.exited());
+    assert_eq!(h.frame(), 1);
+    h.frames(1);
+    assert_eq!(h.frame(), 1);
+}
+
+#[test] fn test_sierpinski() {
+    let mut h = Harness::load(concat!(env!("CARGO_MANIFEST_DIR"), "/../../examples/sierpinski.ch8"), Quirks::COSMAC_VIP, FrameTiming::Vip(VipTiming::new())).unwrap();
+    h.frames(4).assert_screen("
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        .......#........
+        ......#.#.......
+        .....#...#......
+        ....#.#.#.#.....
+        ...#.......#....
+        ..#.#.....#.#...
+        .#...#...#...#..
+        #.#.#.#.#.#.#.#.
+    ");
+}
+
+#[test] fn test_rom_too_large() {
+    assert!(Harness::new(&[0; 0x1000 - 0x200], Quirks::CHIP_48, FrameTiming::default()).is_ok());
+    let err = Harness::new(&[0; 0x1000 - 0x200 + 1], Quirks::CHIP_48, FrameTiming::default()).err().expect("ROM should be too large");
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
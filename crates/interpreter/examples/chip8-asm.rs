@@ -0,0 +1,25 @@
+use maulingmonkey_chip8_interpreter::*;
+use std::path::PathBuf;
+
+
+
+fn main() {
+    let mut args = std::env::args_os();
+    let _exe = args.next();
+    let src = PathBuf::from(args.next().expect("Usage: chip8-asm some/rom.8o [some/rom.ch8]"));
+    let ch8 = args.next().map_or_else(|| src.with_extension("ch8"), PathBuf::from);
+    let source = std::fs::read_to_string(&src).unwrap_or_else(|err| panic!("unable to read {}: {err}", src.display()));
+
+    let file = src.file_name().map_or_else(|| src.display().to_string(), |f| f.to_string_lossy().into_owned());
+    let asm = asm::assemble(&source, &file).unwrap_or_else(|err| {
+        eprintln!("{}:{err}", src.display());
+        std::process::exit(1);
+    });
+
+    let map = PathBuf::from(format!("{}.map", ch8.display()));
+    let sym = ch8.with_extension("sym");
+    std::fs::write(&ch8, &asm.rom).unwrap_or_else(|err| panic!("unable to write {}: {err}", ch8.display()));
+    asm.source_map.write(std::fs::File::create(&map).unwrap_or_else(|err| panic!("unable to create {}: {err}", map.display()))).expect("failed to write source map");
+    asm.write_symbols(std::fs::File::create(&sym).unwrap_or_else(|err| panic!("unable to create {}: {err}", sym.display()))).expect("failed to write symbols");
+    println!("{}: {} bytes, {} labels", ch8.display(), asm.rom.len(), asm.labels.len());
+}
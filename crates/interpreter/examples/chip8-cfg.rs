@@ -0,0 +1,22 @@
+use maulingmonkey_chip8_interpreter::*;
+
+
+
+fn main() {
+    let mut args = std::env::args_os();
+    let _exe = args.next();
+    let usage = "Usage: chip8-cfg some/rom.ch8 [dot|calls|json] [chip8|schip|xochip]";
+    let ch8 = std::path::PathBuf::from(args.next().expect(usage));
+    let rom = std::fs::read(&ch8).unwrap_or_else(|err| panic!("unable to read {}: {err}", ch8.display()));
+    let format = args.next().map_or_else(|| "dot".into(), |f| f.to_string_lossy().into_owned());
+    let instruction_set = args.next().map_or(Ok(InstructionSet::XoChip), |isa| isa.to_string_lossy().parse()).unwrap_or_else(|err| panic!("{err}\n{usage}"));
+
+    let cfg = cfg::analyze(&rom, instruction_set);
+    let out = std::io::stdout().lock();
+    match format.as_str() {
+        "dot"   => cfg.write_dot(out),
+        "calls" => cfg.write_call_graph_dot(out),
+        "json"  => cfg.write_json(out),
+        other   => panic!("unknown format {other:?}\n{usage}"),
+    }.expect("failed to write to stdout");
+}
@@ -0,0 +1,26 @@
+use maulingmonkey_chip8_interpreter::*;
+use maulingmonkey_chip8_interpreter::coverage::Coverage;
+use std::path::PathBuf;
+
+
+
+fn main() {
+    let mut args = std::env::args_os();
+    let _exe = args.next();
+    let usage = "Usage: chip8-coverage some/rom.ch8 [frames] [vip|chip48|schip|xochip] > coverage\nWrites LCOV if some/rom.ch8.map exists (see chip8-asm), or an annotated listing otherwise.";
+    let ch8 = PathBuf::from(args.next().expect(usage));
+    let rom = std::fs::read(&ch8).unwrap_or_else(|err| panic!("unable to read {}: {err}", ch8.display()));
+    let frames = args.next().map_or(600, |f| f.to_str().and_then(|f| f.parse().ok()).unwrap_or_else(|| panic!("expected a number of frames\n{usage}")));
+    let quirks = args.next().map_or(Ok(Quirks::COSMAC_VIP), |q| q.to_string_lossy().parse()).unwrap_or_else(|err| panic!("{err}\n{usage}"));
+    let map = PathBuf::from(format!("{}.map", ch8.display()));
+    let source_map = std::fs::File::open(&map).ok().map(|file| SourceMap::read(std::io::BufReader::new(file)).unwrap_or_else(|err| panic!("unable to read {}: {err}", map.display())));
+
+    let (coverage, fault) = headless::run(&rom, quirks, FrameTiming::default(), frames, Coverage::new()).unwrap_or_else(|err| panic!("{}: {err}", ch8.display()));
+    if let Some(fault) = fault { eprintln!("{}: {fault}", ch8.display()) }
+
+    let out = std::io::stdout().lock();
+    match source_map {
+        Some(source_map)    => coverage.write_lcov(out, &source_map),
+        None                => coverage.write_listing(out, &rom, quirks.instruction_set),
+    }.expect("failed to write to stdout");
+}
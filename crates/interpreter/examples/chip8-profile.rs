@@ -0,0 +1,26 @@
+use maulingmonkey_chip8_interpreter::*;
+use maulingmonkey_chip8_interpreter::profile::Profiler;
+use std::collections::BTreeMap;
+
+
+
+fn main() {
+    let mut args = std::env::args_os();
+    let _exe = args.next();
+    let usage = "Usage: chip8-profile some/rom.ch8 [frames] [vip|chip48|schip|xochip]\nLabels are read from some/rom.sym if it exists (see chip8-asm.)";
+    let ch8 = std::path::PathBuf::from(args.next().expect(usage));
+    let rom = std::fs::read(&ch8).unwrap_or_else(|err| panic!("unable to read {}: {err}", ch8.display()));
+    let frames = args.next().map_or(600, |f| f.to_str().and_then(|f| f.parse().ok()).unwrap_or_else(|| panic!("expected a number of frames\n{usage}")));
+    let quirks = args.next().map_or(Ok(Quirks::COSMAC_VIP), |q| q.to_string_lossy().parse()).unwrap_or_else(|err| panic!("{err}\n{usage}"));
+    let sym = ch8.with_extension("sym");
+    let symbols = match std::fs::File::open(&sym) {
+        Ok(file) => asm::read_symbols(std::io::BufReader::new(file)).unwrap_or_else(|err| panic!("unable to read {}: {err}", sym.display())),
+        Err(_) => BTreeMap::new(),
+    };
+
+    // the COSMAC VIP's instruction budget, or a flat 500 Hz for later platforms
+    let timing = if quirks == Quirks::COSMAC_VIP { FrameTiming::Vip(VipTiming::new()) } else { FrameTiming::default() };
+    let (profile, fault) = headless::run(&rom, quirks, timing, frames, Profiler::new()).unwrap_or_else(|err| panic!("{}: {err}", ch8.display()));
+    if let Some(fault) = fault { eprintln!("{}: {fault}", ch8.display()) }
+    profile.write_report(std::io::stdout().lock(), &symbols).expect("failed to write to stdout");
+}
@@ -0,0 +1,19 @@
+use maulingmonkey_chip8_interpreter::*;
+use std::path::PathBuf;
+
+
+
+fn main() {
+    let mut args = std::env::args_os();
+    let _exe = args.next();
+    let usage = "Usage: chip8-recompile some/rom.ch8 [some/rom.rs] [vip|chip48|schip]";
+    let ch8 = PathBuf::from(args.next().expect(usage));
+    let rs = args.next().map_or_else(|| ch8.with_extension("rs"), PathBuf::from);
+    let quirks = args.next().map_or(Ok(Quirks::COSMAC_VIP), |q| q.to_string_lossy().parse()).unwrap_or_else(|err| panic!("{err}\n{usage}"));
+
+    let rom = std::fs::read(&ch8).unwrap_or_else(|err| panic!("unable to read {}: {err}", ch8.display()));
+    let name = ch8.file_name().map_or_else(|| ch8.display().to_string(), |f| f.to_string_lossy().into_owned());
+    let out = std::fs::File::create(&rs).unwrap_or_else(|err| panic!("unable to create {}: {err}", rs.display()));
+    aot::recompile(&rom, quirks, &name, std::io::BufWriter::new(out)).unwrap_or_else(|err| panic!("unable to write {}: {err}", rs.display()));
+    println!("{}: recompiled {}", rs.display(), ch8.display());
+}
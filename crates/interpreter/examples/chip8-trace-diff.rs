@@ -0,0 +1,21 @@
+use maulingmonkey_chip8_interpreter::trace;
+use std::io::BufReader;
+
+
+
+fn main() {
+    let mut args = std::env::args_os();
+    let _exe = args.next();
+    let usage = "Usage: chip8-trace-diff a.log b.log";
+    let a = std::path::PathBuf::from(args.next().expect(usage));
+    let b = std::path::PathBuf::from(args.next().expect(usage));
+    let open = |path: &std::path::Path| BufReader::new(std::fs::File::open(path).unwrap_or_else(|err| panic!("unable to open {}: {err}", path.display())));
+
+    match trace::diff(open(&a), open(&b)).expect("failed to read traces") {
+        None => println!("traces match"),
+        Some(divergence) => {
+            println!("{divergence}");
+            std::process::exit(1);
+        },
+    }
+}
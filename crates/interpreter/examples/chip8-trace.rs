@@ -0,0 +1,19 @@
+use maulingmonkey_chip8_interpreter::*;
+use maulingmonkey_chip8_interpreter::trace::Tracer;
+
+
+
+fn main() {
+    let mut args = std::env::args_os();
+    let _exe = args.next();
+    let usage = "Usage: chip8-trace some/rom.ch8 [frames] [vip|chip48|schip|xochip] > some/rom.log";
+    let ch8 = std::path::PathBuf::from(args.next().expect(usage));
+    let rom = std::fs::read(&ch8).unwrap_or_else(|err| panic!("unable to read {}: {err}", ch8.display()));
+    let frames = args.next().map_or(60, |f| f.to_str().and_then(|f| f.parse().ok()).unwrap_or_else(|| panic!("expected a number of frames\n{usage}")));
+    let quirks = args.next().map_or(Ok(Quirks::COSMAC_VIP), |q| q.to_string_lossy().parse()).unwrap_or_else(|err| panic!("{err}\n{usage}"));
+
+    let tracer = Tracer::new(std::io::BufWriter::new(std::io::stdout().lock()));
+    let (tracer, fault) = headless::run(&rom, quirks, FrameTiming::default(), frames, tracer).unwrap_or_else(|err| panic!("{}: {err}", ch8.display()));
+    if let Some(fault) = fault { eprintln!("{}: {fault}", ch8.display()) }
+    tracer.finish().expect("failed to write to stdout");
+}
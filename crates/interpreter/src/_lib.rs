@@ -1,16 +1,44 @@
 // #![warn(missing_docs)]              // full docs
 #![deny(non_snake_case)]            // match { ... } bugs
 #![deny(unreachable_patterns)]      // match { ... } bugs
+#![allow(clippy::identity_op)]      // `op>>0` keeps nibble extraction aligned
+#![allow(clippy::result_unit_err)]  // `Result<_, ()>` for simple bounds checks
 
+#[cfg(test)] extern crate self as maulingmonkey_chip8_interpreter; // recompiled test ROMs `use` the crate by name
+
+mod access;                         pub use access::*;
 mod addr;                           pub use addr::*;
+pub mod aot;
+pub mod asm;
+mod cache;                          pub use cache::*;
+pub mod cdp1802;
+pub mod cfg;
+pub mod coverage;
 mod context;                        pub use context::*;
+mod debugger;                       pub use debugger::*;
 mod decode;                         pub use decode::*;
+pub mod disasm;
+mod fault;                          pub use fault::*;
 pub mod font;
+pub mod gdb;
+pub mod headless;
+#[cfg(feature = "jit")] pub mod jit;
 mod memory;                         pub use memory::*;
+pub mod movie;
 mod nibble;                         pub use nibble::*;
+mod observer;                       pub use observer::*;
 mod op;                             pub use op::*;
+pub mod profile;
+mod quirks;                         pub use quirks::*;
 mod registers;                      pub use registers::*;
+pub mod rewind;
+mod rng;                            pub use rng::*;
+pub mod savestate;
 mod screen;                         pub use screen::*;
+mod source_map;                     pub use source_map::*;
 mod syscalls;                       pub use syscalls::*;
+mod timing;                         pub use timing::*;
 pub mod tls;
+pub mod trace;
 mod v;                              pub use v::*;
+pub mod vip;
@@ -0,0 +1,189 @@
+use crate::*;
+
+
+
+/// `len` bytes of memory starting at `start`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)] pub struct MemoryRange {
+    pub start:  Addr,
+    pub len:    u32,
+}
+
+impl MemoryRange {
+    pub const fn new(start: Addr, len: u32) -> Self { Self { start, len } }
+
+    pub fn contains(&self, addr: Addr) -> bool { self.start <= addr && addr.to_usize() < self.start.to_usize() + self.len as usize }
+
+    pub fn overlaps(&self, other: &MemoryRange) -> bool {
+        let (a, b) = (self.start.to_usize(), other.start.to_usize());
+        self.len > 0 && other.len > 0 && a < b + other.len as usize && b < a + self.len as usize
+    }
+}
+
+/// The registers and memory an instruction will read and write, as predicted by [`Context::accesses`].
+///
+/// `V` masks have bit N set for `VN`.  Memory ranges are conservative: a `DXYN` on the memory mapped CHIP-8 screen
+/// "writes" the whole screen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)] pub struct Accesses {
+    pub v_read:         u16,
+    pub v_write:        u16,
+    pub i_read:         bool,
+    pub i_write:        bool,
+    pub memory_read:    Option<MemoryRange>,
+    pub memory_write:   Option<MemoryRange>,
+}
+
+impl Accesses {
+    pub fn reads_v (&self, v: V) -> bool { self.v_read  & (1 << v.0.to_u16()) != 0 }
+    pub fn writes_v(&self, v: V) -> bool { self.v_write & (1 << v.0.to_u16()) != 0 }
+    pub fn reads_memory (&self, range: &MemoryRange) -> bool { self.memory_read .is_some_and(|r| r.overlaps(range)) }
+    pub fn writes_memory(&self, range: &MemoryRange) -> bool { self.memory_write.is_some_and(|r| r.overlaps(range)) }
+}
+
+impl<S: Syscalls, M: Memory, O: Observer> Context<S, M, O> {
+    /// Predict what the instruction at the program counter will access when executed, without executing it.
+    ///
+    /// Instructions that would [`Fault`] access nothing.
+    pub fn accesses(&self) -> Accesses {
+        if self.check_range(self.registers.pc, 2).is_err() { return Accesses::default() }
+        Op(self.memory.read16(self.registers.pc)).decode(&mut Analyze(self))
+    }
+}
+
+/// [`Decode`]s an instruction into the [`Accesses`] it would make in a [`Context`].
+pub(crate) struct Analyze<'a, S: Syscalls, M: Memory, O: Observer>(pub(crate) &'a Context<S, M, O>);
+impl<S: Syscalls, M: Memory, O: Observer> Analyze<'_, S, M, O> {
+    fn schip(&self) -> bool { self.0.quirks.instruction_set >= InstructionSet::SuperChip }
+    fn xo(&self) -> bool { self.0.quirks.instruction_set >= InstructionSet::XoChip }
+
+    /// `len` bytes at `I`, if in range.
+    fn at_i(&self, len: usize) -> Option<MemoryRange> {
+        self.0.check_range(self.0.registers.i, len).ok().map(|()| MemoryRange::new(self.0.registers.i, len as u32))
+    }
+
+    fn rw(v_read: u16, v_write: u16) -> Accesses { Accesses { v_read, v_write, ..Accesses::default() } }
+
+    fn draw(&self, vx: V, vy: V, width: usize, height: usize) -> Accesses {
+        let len = width / 8 * height * self.0.planes.count_ones().max(1) as usize;
+        let Some(sprite) = self.at_i(len) else { return Accesses::default() };
+        Accesses {
+            v_read:         bit(vx) | bit(vy),
+            v_write:        bit(VF),
+            i_read:         true,
+            memory_read:    Some(sprite),
+            memory_write:   (!self.schip()).then_some(SCREEN),
+            ..Accesses::default()
+        }
+    }
+
+    fn stack_entry(&self, i: Option<u8>) -> Option<MemoryRange> {
+        let i = i.filter(|_| self.0.quirks.stack_in_memory)?;
+        Some(MemoryRange::new(Addr(Addr::SYSTEM_STACK_ETC_START.0 + 2 * u16::from(i)), 2))
+    }
+}
+
+const SCREEN : MemoryRange = MemoryRange::new(Addr::SYSTEM_DISPLAY_START, 0x100);
+fn bit(v: V) -> u16 { 1 << v.0.to_u16() }
+fn through(v: V) -> u16 { (2u32 << v.0.to_u16()).wrapping_sub(1) as u16 } // V0 ..= v
+fn range(vx: V, vy: V) -> u16 { v_range(vx, vy).fold(0, |m, v| m | bit(v)) }
+
+impl<S: Syscalls, M: Memory, O: Observer> Decode for Analyze<'_, S, M, O> {
+    type Result = Accesses;
+
+    fn invalid                  (&mut self, _op: u16)               -> Accesses { Accesses::default() }
+    fn call_mcs                 (&mut self, _addr: Addr)            -> Accesses {
+        if !self.0.quirks.machine_code { return Accesses::default() }
+        let all = Some(MemoryRange::new(Addr(0), M::SIZE as u32)); // machine code could touch anything
+        Accesses { v_read: !0, v_write: !0, i_read: true, i_write: true, memory_read: all, memory_write: all }
+    }
+    fn display_clear            (&mut self)                         -> Accesses { Accesses { memory_write: (!self.schip()).then_some(SCREEN), ..Accesses::default() } }
+    fn flow_return              (&mut self)                         -> Accesses { Accesses { memory_read: self.stack_entry(self.0.registers.sp.checked_sub(1)), ..Accesses::default() } }
+    fn flow_goto                (&mut self, _addr: Addr)            -> Accesses { Accesses::default() }
+    fn flow_call                (&mut self, _addr: Addr)            -> Accesses {
+        let full = self.0.registers.sp >= self.0.stack_capacity();
+        Accesses { memory_write: self.stack_entry((!full).then_some(self.0.registers.sp)), ..Accesses::default() }
+    }
+    fn skip_if_v_eq_c           (&mut self, v: V, _c: u8)           -> Accesses { Self::rw(bit(v), 0) }
+    fn skip_if_v_ne_c           (&mut self, v: V, _c: u8)           -> Accesses { Self::rw(bit(v), 0) }
+    fn skip_if_v_eq_v           (&mut self, vx: V, vy: V)           -> Accesses { Self::rw(bit(vx) | bit(vy), 0) }
+    fn set_v_c                  (&mut self, vx: V, _c: u8)          -> Accesses { Self::rw(0, bit(vx)) }
+    fn add_v_c                  (&mut self, vx: V, _c: u8)          -> Accesses { Self::rw(bit(vx), bit(vx)) }
+    fn set_v_v                  (&mut self, vx: V, vy: V)           -> Accesses { Self::rw(bit(vy), bit(vx)) }
+    fn bitor_v_v                (&mut self, vx: V, vy: V)           -> Accesses { Self::rw(bit(vx) | bit(vy), bit(vx) | if self.0.quirks.vf_reset { bit(VF) } else { 0 }) }
+    fn bitand_v_v               (&mut self, vx: V, vy: V)           -> Accesses { self.bitor_v_v(vx, vy) }
+    fn bitxor_v_v               (&mut self, vx: V, vy: V)           -> Accesses { self.bitor_v_v(vx, vy) }
+    fn add_v_v                  (&mut self, vx: V, vy: V)           -> Accesses { Self::rw(bit(vx) | bit(vy), bit(vx) | bit(VF)) }
+    fn sub_v_v                  (&mut self, vx: V, vy: V)           -> Accesses { self.add_v_v(vx, vy) }
+    fn shr1_v                   (&mut self, vx: V, vy: V)           -> Accesses { Self::rw(if self.0.quirks.shift_vy { bit(vy) } else { bit(vx) }, bit(vx) | bit(VF)) }
+    fn sub_v_v_alt              (&mut self, vx: V, vy: V)           -> Accesses { self.add_v_v(vx, vy) }
+    fn shl1_v                   (&mut self, vx: V, vy: V)           -> Accesses { self.shr1_v(vx, vy) }
+    fn skip_if_v_ne_v           (&mut self, vx: V, vy: V)           -> Accesses { self.skip_if_v_eq_v(vx, vy) }
+    fn set_i_c                  (&mut self, _c: Addr)               -> Accesses { Accesses { i_write: true, ..Accesses::default() } }
+    fn set_pc_v0_plus_c         (&mut self, _v0: (), c: Addr)       -> Accesses { Self::rw(if self.0.quirks.jump_vx { bit(V(Nibble::truncate16(c.0 >> 8))) } else { bit(V0) }, 0) }
+    fn set_v_rand_mask          (&mut self, v: V, _mask: u8)        -> Accesses {
+        let memory_read = match self.0.rng {
+            Rng::CosmacVip { seed } => Some(MemoryRange::new(Addr(0x100 | (seed as u8).wrapping_add(1) as u16), 1)), // see Rng::next
+            _ => None,
+        };
+        Accesses { memory_read, ..Self::rw(0, bit(v)) }
+    }
+    fn draw_x_y_h               (&mut self, vx: V, vy: V, h: Nibble)-> Accesses { self.draw(vx, vy, 8, h.to_usize()) }
+    fn skip_if_pressed          (&mut self, key: V)                 -> Accesses { Self::rw(bit(key), 0) }
+    fn skip_unless_pressed      (&mut self, key: V)                 -> Accesses { Self::rw(bit(key), 0) }
+    fn get_delay_timer          (&mut self, v: V)                   -> Accesses { Self::rw(0, bit(v)) }
+    fn await_key                (&mut self, v: V)                   -> Accesses { Self::rw(0, bit(v)) }
+    fn set_delay_timer          (&mut self, v: V)                   -> Accesses { Self::rw(bit(v), 0) }
+    fn set_sound_timer          (&mut self, v: V)                   -> Accesses { Self::rw(bit(v), 0) }
+    fn add_i_v                  (&mut self, v: V)                   -> Accesses { Accesses { i_read: true, i_write: true, ..Self::rw(bit(v), 0) } }
+    fn set_i_sprite             (&mut self, v: V)                   -> Accesses { Accesses { i_write: true, ..Self::rw(bit(v), 0) } }
+    fn set_i_bcd                (&mut self, v: V)                   -> Accesses {
+        let Some(dst) = self.at_i(3) else { return Accesses::default() };
+        Accesses { i_read: true, memory_write: Some(dst), ..Self::rw(bit(v), 0) }
+    }
+    fn reg_dump                 (&mut self, v: V)                   -> Accesses {
+        let Some(dst) = self.at_i(v.0.to_usize()+1) else { return Accesses::default() };
+        Accesses { i_read: true, i_write: self.0.quirks.increment_i, memory_write: Some(dst), ..Self::rw(through(v), 0) }
+    }
+    fn reg_load                 (&mut self, v: V)                   -> Accesses {
+        let Some(src) = self.at_i(v.0.to_usize()+1) else { return Accesses::default() };
+        Accesses { i_read: true, i_write: self.0.quirks.increment_i, memory_read: Some(src), ..Self::rw(0, through(v)) }
+    }
+
+    fn draw_x_y_16x16           (&mut self, vx: V, vy: V)           -> Accesses { if self.schip() { self.draw(vx, vy, 16, 16) } else { self.draw_x_y_h(vx, vy, N0) } }
+    fn set_i_sprite_large       (&mut self, v: V)                   -> Accesses { if !self.schip() { return Accesses::default() } self.set_i_sprite(v) }
+    fn rpl_dump                 (&mut self, v: V)                   -> Accesses { if !self.schip() { return Accesses::default() } Self::rw(through(v), 0) }
+    fn rpl_load                 (&mut self, v: V)                   -> Accesses { if !self.schip() { return Accesses::default() } Self::rw(0, through(v)) }
+
+    fn reg_dump_range           (&mut self, vx: V, vy: V)           -> Accesses {
+        let Some(dst) = self.at_i(v_range(vx, vy).count()).filter(|_| self.xo()) else { return Accesses::default() };
+        Accesses { i_read: true, memory_write: Some(dst), ..Self::rw(range(vx, vy), 0) }
+    }
+    fn reg_load_range           (&mut self, vx: V, vy: V)           -> Accesses {
+        let Some(src) = self.at_i(v_range(vx, vy).count()).filter(|_| self.xo()) else { return Accesses::default() };
+        Accesses { i_read: true, memory_read: Some(src), ..Self::rw(0, range(vx, vy)) }
+    }
+    fn set_i_long               (&mut self)                         -> Accesses { Accesses { i_write: self.xo(), ..Accesses::default() } }
+    fn audio_pattern            (&mut self)                         -> Accesses {
+        let Some(src) = self.at_i(16).filter(|_| self.xo()) else { return Accesses::default() };
+        Accesses { i_read: true, memory_read: Some(src), ..Accesses::default() }
+    }
+    fn set_pitch                (&mut self, v: V)                   -> Accesses { if !self.xo() { return Accesses::default() } Self::rw(bit(v), 0) }
+}
+
+#[test] fn test_accesses() {
+    let mut ctx = Context::<()> { quirks: Quirks::COSMAC_VIP, ..Context::default() };
+    ctx.registers.pc = Addr(0x200);
+    ctx.registers.i = Addr(0x300);
+    ctx.rng = Rng::CosmacVip { seed: 0x12FF };
+    let mut at = |op: u16| { ctx.memory.copy_from_slice(Addr(0x200), &op.to_be_bytes()).unwrap(); ctx.accesses() };
+
+    assert_eq!(at(0x8124), Accesses { v_read: 0b110, v_write: 0x8002, ..Accesses::default() });
+    assert_eq!(at(0xF355), Accesses { v_read: 0b1111, i_read: true, i_write: true, memory_write: Some(MemoryRange::new(Addr(0x300), 4)), ..Accesses::default() });
+    assert_eq!(at(0xD125).memory_read, Some(MemoryRange::new(Addr(0x300), 5)));
+    assert_eq!(at(0x2400).memory_write, Some(MemoryRange::new(Addr::SYSTEM_STACK_ETC_START, 2)));
+    assert_eq!(at(0x00FF), Accesses::default()); // SUPER-CHIP instruction faults on the COSMAC VIP
+    assert!(MemoryRange::new(Addr(0x300), 4).overlaps(&MemoryRange::new(Addr(0x303), 1)));
+    assert!(!MemoryRange::new(Addr(0x300), 4).overlaps(&MemoryRange::new(Addr(0x304), 1)));
+    assert!(MemoryRange::new(Addr(0x300), 4).contains(Addr(0x303)) && !MemoryRange::new(Addr(0x300), 4).contains(Addr(0x304)));
+    assert!(MemoryRange::new(Addr(0xFFFF), 1).contains(Addr(0xFFFF)));
+    assert_eq!(at(0xC1FF).memory_read, Some(MemoryRange::new(Addr(0x100), 1))); // COSMAC VIP rand reads its own code
+}
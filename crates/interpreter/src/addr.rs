@@ -20,7 +20,11 @@ impl Addr {
     // "For some reason, it’s become popular to put it at 050–09F, so you can follow that convention if you want."
     // https://tobiasvl.github.io/blog/write-a-chip-8-emulator/#font
     pub const TYPICAL_FONTS_START               : Addr = Addr(0x050);
-    pub const TYPICAL_FONTS_END                 : Addr = Addr(0x050);
+    pub const TYPICAL_FONTS_END                 : Addr = Addr(0x0A0); // 16 * 5 bytes
+
+    // SUPER-CHIP's 8x10 hexadecimal digits, immediately following the small font
+    pub const TYPICAL_LARGE_FONTS_START         : Addr = Addr(0x0A0);
+    pub const TYPICAL_LARGE_FONTS_END           : Addr = Addr(0x140); // 16 * 10 bytes
 
     pub const PROGRAM_START_TYPICAL             : Addr = Addr(0x200);
     pub const PROGRAM_START_ETI_660             : Addr = Addr(0x600);
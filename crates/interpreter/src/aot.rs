@@ -0,0 +1,373 @@
+//! Ahead-of-time recompilation of ROMs into Rust modules, with a [`Runtime`] for the recompiled code.
+//!
+//! ```no_run
+//! # use maulingmonkey_chip8_interpreter::*;
+//! let rom = std::fs::read("game.ch8").unwrap();
+//! aot::recompile(&rom, Quirks::CHIP_48, "game.ch8", std::fs::File::create("src/game.rs").unwrap()).unwrap();
+//! ```
+//!
+//! # Generated code
+//!
+//! Each [`cfg::Function`] becomes a native function over [`Context`]'s [`Registers`] and [`Memory4K`], with a `match` on
+//! the program counter to move between its [`cfg::BasicBlock`]s.  Register-only instructions are inlined (specialized
+//! for the [`Quirks`] given), `2NNN`/`00EE` become direct calls and returns, and everything else — sprites, timers,
+//! input, memory access — is executed by [`Context::try_step_single`].
+//!
+//! Jumps that leave a function, including indirect `BNNN` jumps, go through a generated `dispatch` fn that matches
+//! every known block.  Unknown targets (code that static analysis couldn't find) are interpreted until they reach a
+//! known block again.  Self modifying code isn't supported: instructions are recompiled as they were in the ROM.
+//!
+//! The generated module exposes:
+//!
+//! ```text
+//! pub const ROM       : &[u8];
+//! pub const QUIRKS    : Quirks;
+//! pub fn load<S: Syscalls>(ctx: &mut Context<S>);
+//! pub fn run<S: Syscalls>(ctx: &mut Context<S>, steps_per_frame: usize, frame: &mut dyn FnMut(&mut Context<S>) -> bool) -> Stop;
+//! ```
+
+use crate::*;
+use crate::cfg::{ControlFlowGraph, Edge};
+use crate::disasm::Next;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+
+
+/// Why a recompiled program stopped [`run`](Runtime::run)ning.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)] pub enum Stop {
+    /// `00FD` (SUPER-CHIP) exited the program.
+    Exited,
+
+    /// An instruction couldn't be executed.  Recompiled code only updates <code>[Registers]::pc</code> when interpreting, so [`Fault::pc`] is more reliable.
+    Fault(Fault),
+
+    /// The `frame` callback returned `false`.
+    Quit,
+}
+
+/// A recompiled program's result type: <code>Ok(Some(pc))</code> to continue at `pc`, or <code>Ok(None)</code> after a `00EE` return.
+pub type Flow = Result<Option<u16>, Stop>;
+
+/// Support code called by recompiled programs.
+pub struct Runtime<'c, S: Syscalls> {
+    pub ctx:            &'c mut Context<S>,
+    frame:              &'c mut dyn FnMut(&mut Context<S>) -> bool,
+    steps_per_frame:    usize,
+    steps:              usize, // since the last frame
+    depth:              u8, // native calls in progress
+}
+
+impl<'c, S: Syscalls> Runtime<'c, S> {
+    /// `frame` is called every `steps_per_frame` instructions, and while waiting on input or vblank.
+    /// It'll typically [`step_clocks`](Context::step_clocks) and wait for the next frame, or return `false` to [`Stop::Quit`].
+    pub fn new(ctx: &'c mut Context<S>, steps_per_frame: usize, frame: &'c mut dyn FnMut(&mut Context<S>) -> bool) -> Self {
+        Self { ctx, frame, steps_per_frame: steps_per_frame.max(1), steps: 0, depth: 0 }
+    }
+
+    /// Run from `pc` until the program stops.
+    pub fn run(&mut self, mut pc: u16, dispatch: impl Fn(&mut Self, u16) -> Flow) -> Stop {
+        loop {
+            match dispatch(self, pc) {
+                Ok(Some(next))  => pc = next,
+                Ok(None)        => unreachable!("returned from the outermost function"), // see ret
+                Err(stop)       => return stop,
+            }
+        }
+    }
+
+    /// Count `steps` instructions towards the next frame.
+    pub fn tick(&mut self, steps: usize) -> Result<(), Stop> {
+        self.steps += steps;
+        if self.steps >= self.steps_per_frame { self.frame()? }
+        Ok(())
+    }
+
+    /// Interpret the instruction at `pc`, waiting on frames while it blocks.  Returns the new program counter.
+    pub fn exec(&mut self, pc: u16) -> Result<u16, Stop> {
+        self.ctx.registers.pc = Addr(pc);
+        loop {
+            match self.ctx.try_step_single() {
+                Ok(StepOutcome::Stepped)                                    => return Ok(self.ctx.registers.pc.0),
+                Ok(StepOutcome::AwaitingKey | StepOutcome::AwaitingVBlank) => self.frame()?,
+                Ok(StepOutcome::Exited)                                     => return Err(Stop::Exited),
+                Err(fault)                                                  => return Err(Stop::Fault(fault)),
+            }
+        }
+    }
+
+    /// `2NNN` at `at`: call `f` at `target`, then keep `dispatch`ing until it returns.
+    pub fn call(&mut self, at: u16, target: u16, f: impl Fn(&mut Self, u16) -> Flow, dispatch: impl Fn(&mut Self, u16) -> Flow) -> Result<(), Stop> {
+        if self.depth + self.ctx.registers.sp >= self.ctx.stack_capacity() {
+            self.ctx.registers.pc = Addr(at);
+            return Err(Stop::Fault(self.ctx.fault(FaultKind::StackOverflow)));
+        }
+        self.depth += 1;
+        let mut next = f(self, target);
+        while let Ok(Some(pc)) = next { next = dispatch(self, pc) }
+        self.depth -= 1;
+        next.map(|_| ())
+    }
+
+    /// `00EE` at `at`.
+    pub fn ret(&mut self, at: u16) -> Flow {
+        if self.depth == 0 { self.exec(at)?; } // StackUnderflow, unless interpreted code called us
+        Ok(None)
+    }
+
+    /// Interpret from `pc` (not a `known` block) until reaching a `known` block, or returning from the current function.
+    pub fn interpret(&mut self, mut pc: u16, known: impl Fn(u16) -> bool) -> Flow {
+        let sp = self.ctx.registers.sp;
+        loop {
+            if self.ctx.registers.sp == sp {
+                if known(pc) { return Ok(Some(pc)) }
+                if self.depth > 0 && self.ctx.memory.read16(Addr(pc)) == 0x00EE { return Ok(None) }
+            }
+            self.tick(1)?;
+            pc = self.exec(pc)?;
+        }
+    }
+
+    fn frame(&mut self) -> Result<(), Stop> {
+        self.steps = 0;
+        if (self.frame)(self.ctx) { Ok(()) } else { Err(Stop::Quit) }
+    }
+}
+
+/// Write `rom` (loaded at [`Addr::PROGRAM_START_TYPICAL`]) as a Rust module (see [module docs](self)), specialized for `quirks`.
+/// `name` is only used for documentation.
+///
+/// Fails with [`io::ErrorKind::InvalidInput`] for XO-CHIP `quirks`, or [`io::ErrorKind::InvalidData`] if `rom` doesn't fit in [`Memory4K`].
+pub fn recompile(rom: &[u8], quirks: Quirks, name: &str, mut w: impl Write) -> io::Result<()> {
+    if quirks.instruction_set >= InstructionSet::XoChip { return Err(io::Error::new(io::ErrorKind::InvalidInput, "XO-CHIP programs need Memory64K, which recompiled code doesn't support")) }
+    if Addr::PROGRAM_START_TYPICAL.0 as usize + rom.len() > Memory4K::SIZE { return Err(io::Error::new(io::ErrorKind::InvalidData, format!("ROM too large ({} bytes)", rom.len()))) }
+    let cfg = cfg::analyze(rom, quirks.instruction_set);
+
+    // blocks to generate for each function: its own, plus any found by following indirect jumps from their base
+    let mut bodies = BTreeMap::new();
+    for f in cfg.functions.values() {
+        let mut body = f.blocks.clone();
+        let mut pending = f.blocks.iter().flat_map(|b| cfg.blocks[b].edges.iter()).filter_map(|e| match *e { Edge::Indirect { base } => Some(base), _ => None }).collect::<Vec<_>>();
+        while let Some(addr) = pending.pop() {
+            let Some(block) = cfg.blocks.get(&addr) else { continue };
+            if body.insert(addr) { pending.extend(block.edges.iter().filter_map(Edge::to)) }
+        }
+        bodies.insert(f.entry, body);
+    }
+
+    // the function to enter each block with, preferring the function it's the entry of
+    let mut owners = BTreeMap::new();
+    for &entry in bodies.keys() { owners.insert(entry, entry); }
+    for (&entry, body) in bodies.iter() { for &block in body.iter() { owners.entry(block).or_insert(entry); } }
+
+    writeln!(w, "//! `{name}`, recompiled to Rust by `maulingmonkey_chip8_interpreter::aot`.  Don't edit by hand.")?;
+    writeln!(w, "#![allow(unused_mut, unreachable_code, clippy::all)]")?;
+    writeln!(w)?;
+    writeln!(w, "use maulingmonkey_chip8_interpreter::*;")?;
+    writeln!(w, "use maulingmonkey_chip8_interpreter::aot::{{Flow, Runtime, Stop}};")?;
+    writeln!(w)?;
+    writeln!(w)?;
+    writeln!(w)?;
+    write!(w, "pub const ROM : &[u8] = &[")?;
+    for (i, b) in rom.iter().enumerate() {
+        if i % 16 == 0 { write!(w, "\n   ")? }
+        write!(w, " 0x{b:02X},")?;
+    }
+    writeln!(w, "\n];")?;
+    writeln!(w)?;
+    let Quirks { instruction_set, shift_vy, jump_vx, increment_i, clip_sprites, vf_reset, display_wait, stack_depth, stack_in_memory, machine_code } = quirks;
+    writeln!(w, "pub const QUIRKS : Quirks = Quirks {{")?;
+    writeln!(w, "    instruction_set: InstructionSet::{instruction_set:?},")?;
+    writeln!(w, "    shift_vy:       {shift_vy},")?;
+    writeln!(w, "    jump_vx:        {jump_vx},")?;
+    writeln!(w, "    increment_i:    {increment_i},")?;
+    writeln!(w, "    clip_sprites:   {clip_sprites},")?;
+    writeln!(w, "    vf_reset:       {vf_reset},")?;
+    writeln!(w, "    display_wait:   {display_wait},")?;
+    writeln!(w, "    stack_depth:    {stack_depth},")?;
+    writeln!(w, "    stack_in_memory: {stack_in_memory},")?;
+    writeln!(w, "    machine_code:   {machine_code},")?;
+    writeln!(w, "}};")?;
+    writeln!(w)?;
+    writeln!(w, "/// Load [`ROM`], fonts, and [`QUIRKS`] into `ctx`.")?;
+    writeln!(w, "pub fn load<S: Syscalls>(ctx: &mut Context<S>) {{ ctx.load_rom(ROM, QUIRKS).expect(\"failed to copy ROM into memory\") }}")?;
+    writeln!(w)?;
+    writeln!(w, "/// Run until the program exits, faults, or `frame` returns `false`.  `frame` is called every `steps_per_frame` instructions, and while waiting on input or vblank.")?;
+    writeln!(w, "pub fn run<S: Syscalls>(ctx: &mut Context<S>, steps_per_frame: usize, frame: &mut dyn FnMut(&mut Context<S>) -> bool) -> Stop {{")?;
+    writeln!(w, "    Runtime::new(ctx, steps_per_frame, frame).run(0x{:03X}, dispatch)", Addr::PROGRAM_START_TYPICAL.0)?;
+    writeln!(w, "}}")?;
+    writeln!(w)?;
+
+    let blocks = owners.keys().map(|a| format!("0x{:03X}", a.0)).collect::<Vec<_>>().join(" | ");
+    writeln!(w, "fn is_block(pc: u16) -> bool {{ matches!(pc, {}) }}", if blocks.is_empty() { "_ if false" } else { &blocks })?;
+    writeln!(w)?;
+    writeln!(w, "fn dispatch<S: Syscalls>(rt: &mut Runtime<'_, S>, pc: u16) -> Flow {{")?;
+    writeln!(w, "    match pc {{")?;
+    for f in cfg.functions.values() {
+        let blocks = owners.iter().filter(|(_, &owner)| owner == f.entry).map(|(block, _)| format!("0x{:03X}", block.0)).collect::<Vec<_>>().join(" | ");
+        writeln!(w, "        {blocks} => f_{:03x}(rt, pc),", f.entry.0)?;
+    }
+    writeln!(w, "        _ => rt.interpret(pc, is_block),")?;
+    writeln!(w, "    }}")?;
+    writeln!(w, "}}")?;
+
+    for f in cfg.functions.values() {
+        writeln!(w)?;
+        writeln!(w, "/// `{}`", f.name)?;
+        writeln!(w, "fn f_{:03x}<S: Syscalls>(rt: &mut Runtime<'_, S>, mut pc: u16) -> Flow {{", f.entry.0)?;
+        writeln!(w, "    loop {{")?;
+        writeln!(w, "        match pc {{")?;
+        for block in bodies[&f.entry].iter() { write_block(&mut w, &cfg, quirks, *block)? }
+        writeln!(w, "            _ => return Ok(Some(pc)),")?;
+        writeln!(w, "        }}")?;
+        writeln!(w, "    }}")?;
+        writeln!(w, "}}")?;
+    }
+    Ok(())
+}
+
+fn write_block(w: &mut impl Write, cfg: &ControlFlowGraph, quirks: Quirks, start: Addr) -> io::Result<()> {
+    let d = &cfg.disassembly;
+    let block = &cfg.blocks[&start];
+    let steps = d.code.range(block.start .. block.end).count();
+    let rom = |addr: Addr| u16::from_be_bytes([d.rom[addr.to_usize() - 0x200], d.rom[addr.to_usize() - 0x200 + 1]]);
+
+    writeln!(w, "            0x{:03X} => {{", start.0)?;
+    writeln!(w, "                rt.tick({steps})?;")?;
+    for (&pc, &len) in d.code.range(block.start .. block.end) {
+        let after = pc.0 + u16::from(len);
+        let op = Op(rom(pc));
+        let Some((text, next)) = d.flow(pc) else { break };
+        let stmt = match next {
+            Next::Goto(to)  => format!("pc = 0x{:03X}; continue;", to.0),
+            Next::Jump0(c)  => {
+                let v = if quirks.jump_vx { V(Nibble::truncate16(c.0 >> 8)) } else { V0 };
+                format!("pc = (u16::from({}) + 0x{:03X}) & 0xFFF; continue;", reg(v), c.0)
+            },
+            Next::Call(to)  => format!("rt.call(0x{:03X}, 0x{:03X}, f_{:03x}, dispatch)?;", pc.0, to.0, to.0),
+            Next::Return    => format!("return rt.ret(0x{:03X});", pc.0),
+            Next::Skip      => match op.decode(&mut Inline { quirks }) {
+                Some(cond)  => format!("pc = if {cond} {{ 0x{:03X} }} else {{ 0x{after:03X} }}; continue;", d.skip_target(Addr(after)).0),
+                None        => format!("pc = rt.exec(0x{:03X})?; continue;", pc.0),
+            },
+            Next::Continue  => match op.decode(&mut Inline { quirks }) {
+                Some(stmt)  => stmt,
+                None        => format!("rt.exec(0x{:03X})?;", pc.0),
+            },
+            Next::Exit | Next::Data(_) | Next::Long(_) => format!("rt.exec(0x{:03X})?;", pc.0),
+        };
+        writeln!(w, "                {stmt:<80} // {:03X}: {text}", pc.0)?;
+    }
+    if block.returns || block.edges.iter().any(|e| !matches!(e, Edge::Next(_))) {
+        // the last instruction already continued or returned
+    } else {
+        writeln!(w, "                pc = 0x{:03X}; continue;", block.end.0)?;
+    }
+    writeln!(w, "            }},")?;
+    Ok(())
+}
+
+fn reg(v: V) -> String { format!("rt.ctx.registers.v[0x{:X}]", v.0.to_u8()) }
+
+/// Inline Rust for register-only instructions, or skip conditions.  [`None`] if the instruction should be interpreted instead.
+struct Inline { quirks: Quirks }
+impl Inline {
+    fn set_vf(&self, stmt: String) -> Option<String> {
+        if self.quirks.vf_reset { Some(format!("{stmt} {} = 0;", reg(VF))) } else { Some(stmt) }
+    }
+}
+impl Decode for Inline {
+    type Result = Option<String>;
+
+    fn invalid              (&mut self, _op: u16)                   -> Self::Result { None }
+    fn call_mcs             (&mut self, _addr: Addr)                -> Self::Result { None }
+    fn flow_goto            (&mut self, _addr: Addr)                -> Self::Result { None }
+    fn flow_call            (&mut self, _addr: Addr)                -> Self::Result { None }
+    fn skip_if_v_eq_c       (&mut self, v: V, c: u8)                -> Self::Result { Some(format!("{} == 0x{c:02X}", reg(v))) }
+    fn skip_if_v_ne_c       (&mut self, v: V, c: u8)                -> Self::Result { Some(format!("{} != 0x{c:02X}", reg(v))) }
+    fn skip_if_v_eq_v       (&mut self, vx: V, vy: V)               -> Self::Result { Some(format!("{} == {}", reg(vx), reg(vy))) }
+    fn set_v_c              (&mut self, vx: V, c: u8)               -> Self::Result { Some(format!("{} = 0x{c:02X};", reg(vx))) }
+    fn add_v_c              (&mut self, vx: V, c: u8)               -> Self::Result { Some(format!("{0} = {0}.wrapping_add(0x{c:02X});", reg(vx))) }
+    fn set_v_v              (&mut self, vx: V, vy: V)               -> Self::Result { Some(format!("{} = {};", reg(vx), reg(vy))) }
+    fn bitor_v_v            (&mut self, vx: V, vy: V)               -> Self::Result { self.set_vf(format!("{} |= {};", reg(vx), reg(vy))) }
+    fn bitand_v_v           (&mut self, vx: V, vy: V)               -> Self::Result { self.set_vf(format!("{} &= {};", reg(vx), reg(vy))) }
+    fn bitxor_v_v           (&mut self, vx: V, vy: V)               -> Self::Result { self.set_vf(format!("{} ^= {};", reg(vx), reg(vy))) }
+    fn add_v_v              (&mut self, vx: V, vy: V)               -> Self::Result { Some(format!("{{ let (r, c) = {0}.overflowing_add({1}); {0} = r; {2} = c.into(); }}", reg(vx), reg(vy), reg(VF))) }
+    fn sub_v_v              (&mut self, vx: V, vy: V)               -> Self::Result { Some(format!("{{ let (r, b) = {0}.overflowing_sub({1}); {0} = r; {2} = (!b).into(); }}", reg(vx), reg(vy), reg(VF))) }
+    fn shr1_v               (&mut self, vx: V, vy: V)               -> Self::Result { Some(format!("{{ let s = {}; {} = s >> 1; {} = s & 1; }}", reg(if self.quirks.shift_vy { vy } else { vx }), reg(vx), reg(VF))) }
+    fn sub_v_v_alt          (&mut self, vx: V, vy: V)               -> Self::Result { Some(format!("{{ let (r, b) = {1}.overflowing_sub({0}); {0} = r; {2} = (!b).into(); }}", reg(vx), reg(vy), reg(VF))) }
+    fn shl1_v               (&mut self, vx: V, vy: V)               -> Self::Result { Some(format!("{{ let s = {}; {} = s << 1; {} = s >> 7; }}", reg(if self.quirks.shift_vy { vy } else { vx }), reg(vx), reg(VF))) }
+    fn skip_if_v_ne_v       (&mut self, vx: V, vy: V)               -> Self::Result { Some(format!("{} != {}", reg(vx), reg(vy))) }
+    fn set_i_c              (&mut self, c: Addr)                    -> Self::Result { Some(format!("rt.ctx.registers.i = Addr(0x{:03X});", c.0)) }
+    fn set_pc_v0_plus_c     (&mut self, _v0: (), _c: Addr)          -> Self::Result { None }
+    fn set_v_rand_mask      (&mut self, _v: V, _mask: u8)           -> Self::Result { None }
+    fn draw_x_y_h           (&mut self, _: V, _: V, _: Nibble)      -> Self::Result { None }
+    fn skip_if_pressed      (&mut self, _key: V)                    -> Self::Result { None }
+    fn skip_unless_pressed  (&mut self, _key: V)                    -> Self::Result { None }
+    fn get_delay_timer      (&mut self, _v: V)                      -> Self::Result { None }
+    fn await_key            (&mut self, _v: V)                      -> Self::Result { None }
+    fn set_delay_timer      (&mut self, _v: V)                      -> Self::Result { None }
+    fn set_sound_timer      (&mut self, _v: V)                      -> Self::Result { None }
+    fn add_i_v              (&mut self, v: V)                       -> Self::Result { Some(format!("rt.ctx.registers.i.0 = rt.ctx.registers.i.0.wrapping_add({}.into());", reg(v))) }
+    fn set_i_sprite         (&mut self, v: V)                       -> Self::Result { Some(format!("rt.ctx.registers.i = Addr(0x{:03X} + u16::from({}) * 5);", Addr::TYPICAL_FONTS_START.0, reg(v))) }
+    fn set_i_bcd            (&mut self, _v: V)                      -> Self::Result { None }
+    fn reg_dump             (&mut self, _v: V)                      -> Self::Result { None }
+    fn reg_load             (&mut self, _v: V)                      -> Self::Result { None }
+}
+
+#[test] fn test_recompile() {
+    let rom = [
+        0x60, 0x05,             // 200: main:   v0 := 5
+        0x22, 0x0A,             // 202: loop:   sub_20a
+        0x30, 0x00,             // 204:         if v0 != 0 then
+        0x12, 0x02,             // 206:         jump loop
+        0x00, 0xFD,             // 208:         exit
+        0x70, 0xFF,             // 20A: sub:    v0 += -1
+        0x00, 0xEE,             // 20C:         return
+    ];
+    let mut rust = Vec::new();
+    recompile(&rom, Quirks::SUPER_CHIP, "test.ch8", &mut rust).unwrap();
+    let rust = String::from_utf8(rust).unwrap();
+
+    for expected in [
+        "fn is_block(pc: u16) -> bool { matches!(pc, 0x200 | 0x202 | 0x206 | 0x208 | 0x20A) }",
+        "        0x200 | 0x202 | 0x206 | 0x208 => f_200(rt, pc),",
+        "        0x20A => f_20a(rt, pc),",
+        "rt.call(0x202, 0x20A, f_20a, dispatch)?;",
+        "pc = if rt.ctx.registers.v[0x0] == 0x00 { 0x208 } else { 0x206 }; continue;",
+        "rt.exec(0x208)?;",
+        "rt.ctx.registers.v[0x0] = rt.ctx.registers.v[0x0].wrapping_add(0xFF);",
+        "return rt.ret(0x20C);",
+    ] {
+        assert!(rust.contains(expected), "missing {expected:?} in:\n{rust}");
+    }
+}
+
+#[cfg(test)] #[path = "aot_test.rs"] mod aot_test;
+
+#[test] fn test_recompiled() {
+    let mut rust = Vec::new();
+    recompile(include_bytes!("aot_test.ch8"), Quirks::SUPER_CHIP, "aot_test.ch8", &mut rust).unwrap();
+    assert!(rust == include_bytes!("aot_test.rs"), "stale src/aot_test.rs: cargo run --example chip8-recompile -- src/aot_test.ch8 src/aot_test.rs schip");
+
+    let mut interpreted = Context::<()>::default();
+    interpreted.load_rom(aot_test::ROM, aot_test::QUIRKS).unwrap();
+    loop {
+        match interpreted.try_step_single() {
+            Ok(StepOutcome::Stepped)    => {},
+            Ok(StepOutcome::Exited)     => break,
+            other                       => panic!("unexpected {other:?} at {:?}", interpreted.registers.pc),
+        }
+    }
+
+    let mut recompiled = Context::<()>::default();
+    aot_test::load(&mut recompiled);
+    assert_eq!(aot_test::run(&mut recompiled, 8, &mut |ctx| { ctx.step_clocks(); true }), Stop::Exited);
+
+    assert_eq!(recompiled.registers.v, interpreted.registers.v);
+    assert_eq!(recompiled.registers.i, interpreted.registers.i);
+    assert_eq!(recompiled.registers.sp, interpreted.registers.sp);
+    assert_eq!(recompiled.memory.as_bytes_ref()[..], interpreted.memory.as_bytes_ref()[..]);
+    assert!(bytemuck::bytes_of(recompiled.screen_bitplanes()) == bytemuck::bytes_of(interpreted.screen_bitplanes()), "screens differ");
+    assert_eq!(interpreted.registers.v[..7], [13, 227, 2, 6, 13, 0, 11]); // v0 ..= v6, so the comparison above isn't of two no-ops
+}
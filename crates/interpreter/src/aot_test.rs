@@ -0,0 +1,129 @@
+//! `aot_test.ch8`, recompiled to Rust by `maulingmonkey_chip8_interpreter::aot`.  Don't edit by hand.
+#![allow(unused_mut, unreachable_code, clippy::all)]
+
+use maulingmonkey_chip8_interpreter::*;
+use maulingmonkey_chip8_interpreter::aot::{Flow, Runtime, Stop};
+
+
+
+pub const ROM : &[u8] = &[
+    0x65, 0x05, 0x61, 0x03, 0x62, 0x07, 0x22, 0x20, 0x75, 0xFF, 0x35, 0x00, 0x12, 0x06, 0xA3, 0x00,
+    0xF6, 0x55, 0x62, 0x02, 0xB2, 0x18, 0x00, 0xFD, 0x00, 0xFD, 0xD0, 0x15, 0x00, 0xFD, 0x00, 0x00,
+    0x80, 0x14, 0x86, 0xF4, 0x81, 0x25, 0x86, 0xF4, 0x82, 0x17, 0x86, 0xF4, 0x83, 0x00, 0x83, 0x06,
+    0x86, 0xF4, 0x84, 0x30, 0x84, 0x0E, 0x86, 0xF4, 0x51, 0x20, 0x74, 0x01, 0x93, 0x40, 0x73, 0x01,
+    0xF0, 0x29, 0xF4, 0x1E, 0x00, 0xEE,
+];
+
+pub const QUIRKS : Quirks = Quirks {
+    instruction_set: InstructionSet::SuperChip,
+    shift_vy:       false,
+    jump_vx:        true,
+    increment_i:    false,
+    clip_sprites:   true,
+    vf_reset:       false,
+    display_wait:   false,
+    stack_depth:    16,
+    stack_in_memory: false,
+    machine_code:   false,
+};
+
+/// Load [`ROM`], fonts, and [`QUIRKS`] into `ctx`.
+pub fn load<S: Syscalls>(ctx: &mut Context<S>) { ctx.load_rom(ROM, QUIRKS).expect("failed to copy ROM into memory") }
+
+/// Run until the program exits, faults, or `frame` returns `false`.  `frame` is called every `steps_per_frame` instructions, and while waiting on input or vblank.
+pub fn run<S: Syscalls>(ctx: &mut Context<S>, steps_per_frame: usize, frame: &mut dyn FnMut(&mut Context<S>) -> bool) -> Stop {
+    Runtime::new(ctx, steps_per_frame, frame).run(0x200, dispatch)
+}
+
+fn is_block(pc: u16) -> bool { matches!(pc, 0x200 | 0x206 | 0x20C | 0x20E | 0x218 | 0x220 | 0x23A | 0x23C | 0x23E | 0x240) }
+
+fn dispatch<S: Syscalls>(rt: &mut Runtime<'_, S>, pc: u16) -> Flow {
+    match pc {
+        0x200 | 0x206 | 0x20C | 0x20E | 0x218 => f_200(rt, pc),
+        0x220 | 0x23A | 0x23C | 0x23E | 0x240 => f_220(rt, pc),
+        _ => rt.interpret(pc, is_block),
+    }
+}
+
+/// `main`
+fn f_200<S: Syscalls>(rt: &mut Runtime<'_, S>, mut pc: u16) -> Flow {
+    loop {
+        match pc {
+            0x200 => {
+                rt.tick(3)?;
+                rt.ctx.registers.v[0x5] = 0x05;                                                  // 200: v5 := 0x05
+                rt.ctx.registers.v[0x1] = 0x03;                                                  // 202: v1 := 0x03
+                rt.ctx.registers.v[0x2] = 0x07;                                                  // 204: v2 := 0x07
+                pc = 0x206; continue;
+            },
+            0x206 => {
+                rt.tick(3)?;
+                rt.call(0x206, 0x220, f_220, dispatch)?;                                         // 206: sub_220
+                rt.ctx.registers.v[0x5] = rt.ctx.registers.v[0x5].wrapping_add(0xFF);            // 208: v5 += 0xFF
+                pc = if rt.ctx.registers.v[0x5] == 0x00 { 0x20E } else { 0x20C }; continue;      // 20A: if v5 != 0x00 then
+            },
+            0x20C => {
+                rt.tick(1)?;
+                pc = 0x206; continue;                                                            // 20C: jump label_206
+            },
+            0x20E => {
+                rt.tick(4)?;
+                rt.exec(0x20E)?;                                                                 // 20E: i := 0x300
+                rt.exec(0x210)?;                                                                 // 210: save v6
+                rt.ctx.registers.v[0x2] = 0x02;                                                  // 212: v2 := 0x02
+                pc = (u16::from(rt.ctx.registers.v[0x2]) + 0x218) & 0xFFF; continue;             // 214: jump0 label_218
+            },
+            0x218 => {
+                rt.tick(1)?;
+                rt.exec(0x218)?;                                                                 // 218: exit
+                pc = 0x21A; continue;
+            },
+            _ => return Ok(Some(pc)),
+        }
+    }
+}
+
+/// `sub_220`
+fn f_220<S: Syscalls>(rt: &mut Runtime<'_, S>, mut pc: u16) -> Flow {
+    loop {
+        match pc {
+            0x220 => {
+                rt.tick(13)?;
+                { let (r, c) = rt.ctx.registers.v[0x0].overflowing_add(rt.ctx.registers.v[0x1]); rt.ctx.registers.v[0x0] = r; rt.ctx.registers.v[0xF] = c.into(); } // 220: v0 += v1
+                { let (r, c) = rt.ctx.registers.v[0x6].overflowing_add(rt.ctx.registers.v[0xF]); rt.ctx.registers.v[0x6] = r; rt.ctx.registers.v[0xF] = c.into(); } // 222: v6 += vf
+                { let (r, b) = rt.ctx.registers.v[0x1].overflowing_sub(rt.ctx.registers.v[0x2]); rt.ctx.registers.v[0x1] = r; rt.ctx.registers.v[0xF] = (!b).into(); } // 224: v1 -= v2
+                { let (r, c) = rt.ctx.registers.v[0x6].overflowing_add(rt.ctx.registers.v[0xF]); rt.ctx.registers.v[0x6] = r; rt.ctx.registers.v[0xF] = c.into(); } // 226: v6 += vf
+                { let (r, b) = rt.ctx.registers.v[0x1].overflowing_sub(rt.ctx.registers.v[0x2]); rt.ctx.registers.v[0x2] = r; rt.ctx.registers.v[0xF] = (!b).into(); } // 228: v2 =- v1
+                { let (r, c) = rt.ctx.registers.v[0x6].overflowing_add(rt.ctx.registers.v[0xF]); rt.ctx.registers.v[0x6] = r; rt.ctx.registers.v[0xF] = c.into(); } // 22A: v6 += vf
+                rt.ctx.registers.v[0x3] = rt.ctx.registers.v[0x0];                               // 22C: v3 := v0
+                { let s = rt.ctx.registers.v[0x3]; rt.ctx.registers.v[0x3] = s >> 1; rt.ctx.registers.v[0xF] = s & 1; } // 22E: v3 >>= v0
+                { let (r, c) = rt.ctx.registers.v[0x6].overflowing_add(rt.ctx.registers.v[0xF]); rt.ctx.registers.v[0x6] = r; rt.ctx.registers.v[0xF] = c.into(); } // 230: v6 += vf
+                rt.ctx.registers.v[0x4] = rt.ctx.registers.v[0x3];                               // 232: v4 := v3
+                { let s = rt.ctx.registers.v[0x4]; rt.ctx.registers.v[0x4] = s << 1; rt.ctx.registers.v[0xF] = s >> 7; } // 234: v4 <<= v0
+                { let (r, c) = rt.ctx.registers.v[0x6].overflowing_add(rt.ctx.registers.v[0xF]); rt.ctx.registers.v[0x6] = r; rt.ctx.registers.v[0xF] = c.into(); } // 236: v6 += vf
+                pc = if rt.ctx.registers.v[0x1] == rt.ctx.registers.v[0x2] { 0x23C } else { 0x23A }; continue; // 238: if v1 != v2 then
+            },
+            0x23A => {
+                rt.tick(1)?;
+                rt.ctx.registers.v[0x4] = rt.ctx.registers.v[0x4].wrapping_add(0x01);            // 23A: v4 += 0x01
+                pc = 0x23C; continue;
+            },
+            0x23C => {
+                rt.tick(1)?;
+                pc = if rt.ctx.registers.v[0x3] != rt.ctx.registers.v[0x4] { 0x240 } else { 0x23E }; continue; // 23C: if v3 == v4 then
+            },
+            0x23E => {
+                rt.tick(1)?;
+                rt.ctx.registers.v[0x3] = rt.ctx.registers.v[0x3].wrapping_add(0x01);            // 23E: v3 += 0x01
+                pc = 0x240; continue;
+            },
+            0x240 => {
+                rt.tick(3)?;
+                rt.ctx.registers.i = Addr(0x050 + u16::from(rt.ctx.registers.v[0x0]) * 5);       // 240: i := hex v0
+                rt.ctx.registers.i.0 = rt.ctx.registers.i.0.wrapping_add(rt.ctx.registers.v[0x4].into()); // 242: i += v4
+                return rt.ret(0x244);                                                            // 244: return
+            },
+            _ => return Ok(Some(pc)),
+        }
+    }
+}
@@ -0,0 +1,795 @@
+//! Assemble [Octo](https://github.com/JohnEarnest/Octo) syntax into CHIP-8, SUPER-CHIP, and XO-CHIP ROMs.
+//!
+//! ### Supported syntax
+//! *   Labels (`: name`), `:next name`, `:org`, `:byte`, `:pointer`, `:call`, `:unpack`
+//! *   `:alias name vX`, `:const name value`, `:calc name { expression }`, and `{ expression }` wherever a value is expected
+//! *   `:macro name args... { body }`, invoked as `name args...`
+//! *   Register mnemonics (`v0 := 5`, `v1 += v2`, `i := hex v0`, `delay := v3`, `sprite v0 v1 5`, ...)
+//! *   Control flow (`if ... then`, `if ... begin ... else ... end`, `loop ... while ... again`) including the `<`, `>`, `<=`, `>=` pseudo-ops (which clobber `vF`)
+//! *   Bare numbers and constants emit bytes, and bare labels are subroutine calls.
+//!
+//! Like Octo, `:calc` expressions have no operator precedence: they're evaluated right to left, so use parentheses.
+//! Unless the program starts with `: main`, a `jump main` is emitted at `0x200`.
+
+use crate::*;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, BufRead, Write};
+
+
+
+/// A syntax or semantic error, at a 1-based `line` and `column` of the source.
+#[derive(Clone, Debug, PartialEq, Eq)] pub struct Error {
+    pub line:       u32,
+    pub column:     u32,
+    pub message:    String,
+}
+
+impl Display for Error { fn fmt(&self, fmt: &mut Formatter) -> fmt::Result { write!(fmt, "{}:{}: {}", self.line, self.column, self.message) } }
+impl std::error::Error for Error {}
+
+/// The result of [`assemble`].
+#[derive(Clone, Debug, Default)] pub struct Assembly {
+    /// The program, to be loaded at [`Addr::PROGRAM_START_TYPICAL`].
+    pub rom:        Vec<u8>,
+    pub labels:     BTreeMap<String, Addr>,
+    pub constants:  BTreeMap<String, f64>,
+    pub source_map: SourceMap,
+}
+
+impl Assembly {
+    /// Write [`labels`](Self::labels) as `ADDR NAME` lines, sorted by address.
+    pub fn write_symbols(&self, mut w: impl Write) -> io::Result<()> {
+        let mut labels = self.labels.iter().collect::<Vec<_>>();
+        labels.sort_by_key(|(name, addr)| (**addr, *name));
+        for (name, addr) in labels { writeln!(w, "{:04X} {name}", addr.0)? }
+        Ok(())
+    }
+}
+
+/// Read symbols written by [`Assembly::write_symbols`], by address.  The first name at each address wins.
+/// Blank lines and lines starting with `#` are ignored.
+pub fn read_symbols(r: impl BufRead) -> io::Result<BTreeMap<Addr, String>> {
+    let mut symbols = BTreeMap::new();
+    for (n, line) in r.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') { continue }
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("line {}: expected `ADDR NAME`, got {line:?}", n+1));
+        let (addr, name) = line.split_once(' ').ok_or_else(invalid)?;
+        let addr = u16::from_str_radix(addr, 16).map_err(|_| invalid())?;
+        symbols.entry(Addr(addr)).or_insert_with(|| name.trim().to_string());
+    }
+    Ok(symbols)
+}
+
+/// Assemble Octo `source`.  `file` is only used for the [`Assembly::source_map`].
+pub fn assemble(source: &str, file: &str) -> Result<Assembly, Error> {
+    let mut asm = Assembler {
+        file,
+        tokens:     tokenize(source),
+        last:       Token { text: String::new(), line: 1, column: 1 },
+        rom:        Vec::new(),
+        here:       START,
+        labels:     BTreeMap::new(),
+        constants:  BTreeMap::new(),
+        aliases:    HashMap::new(),
+        macros:     HashMap::new(),
+        fixups:     Vec::new(),
+        flow:       Vec::new(),
+        source_map: SourceMap::new(),
+        jump_main:  true,
+        expansions: 0,
+    };
+    asm.emit_op(0x0000, None)?; // jump main
+    while !asm.tokens.is_empty() { asm.statement()? }
+    asm.finish()
+}
+
+
+
+const START : u32 = Addr::PROGRAM_START_TYPICAL.0 as u32;
+const MAX_EXPANSIONS : usize = 65536;
+
+#[derive(Clone, Debug)] struct Token { text: String, line: u32, column: u32 }
+
+struct Macro { args: Vec<String>, body: Vec<Token> }
+
+enum Fix { Nnn, Word, Unpack(u8), UnpackLong }
+struct Fixup { at: u32, fix: Fix, name: Token }
+
+enum Flow { If { token: Token, jump: u32 }, Else { token: Token, jump: u32 }, Loop { token: Token, start: u32, breaks: Vec<u32> } }
+
+#[derive(Clone, Copy)] enum Operand { V(u8), Byte(u8) }
+#[derive(Clone, Copy)] enum Cond { Eq(u8, Operand), Ne(u8, Operand), Key(u8), NotKey(u8), Lt(u8, Operand), Ge(u8, Operand), Gt(u8, Operand), Le(u8, Operand) }
+
+impl Cond {
+    fn not(self) -> Self {
+        match self {
+            Cond::Eq(x, y)  => Cond::Ne(x, y),
+            Cond::Ne(x, y)  => Cond::Eq(x, y),
+            Cond::Key(x)    => Cond::NotKey(x),
+            Cond::NotKey(x) => Cond::Key(x),
+            Cond::Lt(x, y)  => Cond::Ge(x, y),
+            Cond::Ge(x, y)  => Cond::Lt(x, y),
+            Cond::Gt(x, y)  => Cond::Le(x, y),
+            Cond::Le(x, y)  => Cond::Gt(x, y),
+        }
+    }
+}
+
+struct Assembler<'s> {
+    file:       &'s str,
+    tokens:     VecDeque<Token>,
+    last:       Token,
+    rom:        Vec<u8>,
+    here:       u32,
+    labels:     BTreeMap<String, Addr>,
+    constants:  BTreeMap<String, f64>,
+    aliases:    HashMap<String, u8>,
+    macros:     HashMap<String, Macro>,
+    fixups:     Vec<Fixup>,
+    flow:       Vec<Flow>,
+    source_map: SourceMap,
+    jump_main:  bool,
+    expansions: usize,
+}
+
+fn tokenize(source: &str) -> VecDeque<Token> {
+    let mut tokens = VecDeque::new();
+    for (l, text) in source.lines().enumerate() {
+        let mut chars = text.char_indices().peekable();
+        while let Some(&(start, c)) = chars.peek() {
+            if c.is_whitespace() { chars.next(); continue }
+            if c == '#' { break }
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                if c.is_whitespace() { break }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            let column = text[..start].chars().count() as u32 + 1;
+            tokens.push_back(Token { text: text[start..end].into(), line: l as u32 + 1, column });
+        }
+    }
+    tokens
+}
+
+fn number(text: &str) -> Option<f64> {
+    let (neg, digits) = match text.strip_prefix('-') { Some(d) => (true, d), None => (false, text) };
+    if !digits.starts_with(|c: char| c.is_ascii_digit()) { return None }
+    let value = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()? as f64
+    } else if let Some(bin) = digits.strip_prefix("0b").or_else(|| digits.strip_prefix("0B")) {
+        i64::from_str_radix(bin, 2).ok()? as f64
+    } else {
+        digits.parse().ok()?
+    };
+    Some(if neg { -value } else { value })
+}
+
+fn register(text: &str) -> Option<u8> {
+    let mut chars = text.chars();
+    if !matches!(chars.next(), Some('v' | 'V')) { return None }
+    let n = chars.next()?.to_digit(16)?;
+    if chars.next().is_some() { return None }
+    Some(n as u8)
+}
+
+fn error<T>(token: &Token, message: impl Into<String>) -> Result<T, Error> {
+    Err(Error { line: token.line, column: token.column, message: message.into() })
+}
+
+impl Assembler<'_> {
+    fn next(&mut self) -> Result<Token, Error> {
+        match self.tokens.pop_front() {
+            Some(token) => { self.last = token.clone(); Ok(token) },
+            None        => error(&self.last, "unexpected end of file"),
+        }
+    }
+
+    fn peek(&self) -> Option<&str> { self.tokens.front().map(|t| t.text.as_str()) }
+
+    fn expect(&mut self, text: &str) -> Result<Token, Error> {
+        let token = self.next()?;
+        if token.text != text { return error(&token, format!("expected `{text}`, got `{}`", token.text)) }
+        Ok(token)
+    }
+
+    fn emit(&mut self, byte: u8) -> Result<(), Error> {
+        if self.here > 0xFFFF { return error(&self.last, "program exceeds 64 KiB of memory") }
+        let idx = (self.here - START) as usize;
+        if idx >= self.rom.len() { self.rom.resize(idx + 1, 0) }
+        self.rom[idx] = byte;
+        self.here += 1;
+        Ok(())
+    }
+
+    fn emit_op(&mut self, op: u16, source: Option<&Token>) -> Result<(), Error> {
+        if let Some(source) = source { if self.here <= 0xFFFF { self.source_map.push(Addr(self.here as u16), self.file, source.line) } }
+        self.emit((op >> 8) as u8)?;
+        self.emit(op as u8)
+    }
+
+    fn patch(&mut self, at: u32, op: u16) {
+        let idx = (at - START) as usize;
+        self.rom[idx..idx+2].copy_from_slice(&op.to_be_bytes());
+    }
+
+    fn name(&mut self) -> Result<Token, Error> {
+        let token = self.next()?;
+        if number(&token.text).is_some() || register(&token.text).is_some() || token.text.starts_with([':', '{', '}']) {
+            return error(&token, format!("`{}` isn't a valid name", token.text))
+        }
+        Ok(token)
+    }
+
+    fn define_label(&mut self, name: &Token, addr: u32) -> Result<(), Error> {
+        if self.labels.contains_key(&name.text) || self.constants.contains_key(&name.text) { return error(name, format!("`{}` is already defined", name.text)) }
+        if addr > 0xFFFF { return error(name, "label exceeds 64 KiB of memory") }
+        self.labels.insert(name.text.clone(), Addr(addr as u16));
+        Ok(())
+    }
+
+    fn define_constant(&mut self, name: &Token, value: f64) -> Result<(), Error> {
+        if self.labels.contains_key(&name.text) { return error(name, format!("`{}` is already defined as a label", name.text)) }
+        self.constants.insert(name.text.clone(), value);
+        Ok(())
+    }
+
+    fn reg(&mut self) -> Result<u8, Error> {
+        let token = self.next()?;
+        self.as_reg(&token).map_or_else(|| error(&token, format!("expected a register, got `{}`", token.text)), Ok)
+    }
+
+    fn as_reg(&self, token: &Token) -> Option<u8> { register(&token.text).or_else(|| self.aliases.get(&token.text).copied()) }
+
+    fn is_reg(&self) -> bool { self.tokens.front().is_some_and(|t| self.as_reg(t).is_some()) }
+
+    /// A number, constant, defined label, or `{ expression }`.
+    fn value(&mut self) -> Result<(f64, Token), Error> {
+        let token = self.next()?;
+        let value = self.resolve(&token)?;
+        Ok((value, token))
+    }
+
+    fn resolve(&mut self, token: &Token) -> Result<f64, Error> {
+        if token.text == "{" { return self.braced() }
+        if let Some(value) = self.lookup(&token.text) { return Ok(value) }
+        error(token, format!("undefined name `{}`", token.text))
+    }
+
+    fn lookup(&self, text: &str) -> Option<f64> {
+        number(text)
+            .or_else(|| self.constants.get(text).copied())
+            .or_else(|| self.labels.get(text).map(|a| a.0.into()))
+    }
+
+    fn integer(&mut self, min: i64, max: i64, what: &str) -> Result<i64, Error> {
+        let (value, token) = self.value()?;
+        let int = value.floor() as i64;
+        if !(min ..= max).contains(&int) { return error(&token, format!("{value} doesn't fit in {what}")) }
+        Ok(int)
+    }
+
+    fn byte(&mut self) -> Result<u8, Error> { Ok(self.integer(-128, 255, "a byte")? as u8) }
+    fn nibble(&mut self) -> Result<u16, Error> { Ok(self.integer(0, 15, "a nibble")? as u16) }
+
+    /// An address, which may be a label that's defined later (in which case `fix` patches it at `at`.)
+    fn addr(&mut self, at: u32, fix: Fix, max: i64) -> Result<u16, Error> {
+        let forward = self.tokens.front().is_some_and(|t| t.text != "{" && self.lookup(&t.text).is_none() && self.as_reg(t).is_none());
+        if forward {
+            let name = self.name()?;
+            self.fixups.push(Fixup { at, fix, name });
+            return Ok(0)
+        }
+        Ok(self.integer(0, max, if max == 0xFFF { "12 bits" } else { "16 bits" })? as u16)
+    }
+
+    fn operand(&mut self) -> Result<Operand, Error> {
+        if self.is_reg() { Ok(Operand::V(self.reg()?)) } else { Ok(Operand::Byte(self.byte()?)) }
+    }
+
+    fn condition(&mut self) -> Result<Cond, Error> {
+        let x = self.reg()?;
+        let op = self.next()?;
+        Ok(match op.text.as_str() {
+            "key"   => Cond::Key(x),
+            "-key"  => Cond::NotKey(x),
+            "=="    => Cond::Eq(x, self.operand()?),
+            "!="    => Cond::Ne(x, self.operand()?),
+            "<"     => Cond::Lt(x, self.operand()?),
+            ">="    => Cond::Ge(x, self.operand()?),
+            ">"     => Cond::Gt(x, self.operand()?),
+            "<="    => Cond::Le(x, self.operand()?),
+            _       => return error(&op, format!("expected a comparison, got `{}`", op.text)),
+        })
+    }
+
+    /// Emit instructions that skip the next instruction if `cond` holds.
+    fn skip_if(&mut self, cond: Cond, token: &Token) -> Result<(), Error> {
+        let x = |x: u8| u16::from(x) << 8;
+        let y = |y: u8| u16::from(y) << 4;
+        match cond {
+            Cond::Eq(vx, Operand::Byte(n))  => self.emit_op(0x3000 | x(vx) | u16::from(n), Some(token)),
+            Cond::Eq(vx, Operand::V(vy))    => self.emit_op(0x5000 | x(vx) | y(vy), Some(token)),
+            Cond::Ne(vx, Operand::Byte(n))  => self.emit_op(0x4000 | x(vx) | u16::from(n), Some(token)),
+            Cond::Ne(vx, Operand::V(vy))    => self.emit_op(0x9000 | x(vx) | y(vy), Some(token)),
+            Cond::Key(vx)                   => self.emit_op(0xE09E | x(vx), Some(token)),
+            Cond::NotKey(vx)                => self.emit_op(0xE0A1 | x(vx), Some(token)),
+            Cond::Lt(a, b) | Cond::Ge(a, b) | Cond::Gt(a, b) | Cond::Le(a, b) => {
+                // vF <- (p >= q), then skip on vF
+                let (p, q) = match cond { Cond::Lt(..) | Cond::Ge(..) => (Operand::V(a), b), _ => (b, Operand::V(a)) };
+                match (p, q) {
+                    (Operand::V(p),    Operand::V(q))    => { self.emit_op(0x8F00 | y(p), Some(token))?; self.emit_op(0x8F05 | y(q), Some(token))? },
+                    (Operand::V(p),    Operand::Byte(n)) => { self.emit_op(0x6F00 | u16::from(n), Some(token))?; self.emit_op(0x8F07 | y(p), Some(token))? },
+                    (Operand::Byte(n), Operand::V(q))    => { self.emit_op(0x6F00 | u16::from(n), Some(token))?; self.emit_op(0x8F05 | y(q), Some(token))? },
+                    (Operand::Byte(_), Operand::Byte(_)) => unreachable!(),
+                }
+                let flag = match cond { Cond::Lt(..) | Cond::Gt(..) => 0, _ => 1 };
+                self.emit_op(0x3F00 | flag, Some(token))
+            },
+        }
+    }
+
+    fn statement(&mut self) -> Result<(), Error> {
+        let t = self.next()?;
+        let x = |x: u8| u16::from(x) << 8;
+        let y = |y: u8| u16::from(y) << 4;
+        match t.text.as_str() {
+            ":" => {
+                let name = self.name()?;
+                if name.text == "main" && self.jump_main && self.here == START + 2 && self.rom.len() == 2 && self.labels.is_empty() {
+                    // `: main` is the first thing in the program, no need to jump to it
+                    self.rom.clear();
+                    self.source_map = SourceMap::new();
+                    self.here = START;
+                    self.jump_main = false;
+                }
+                self.define_label(&name, self.here)?;
+            },
+            ":next" => {
+                let name = self.name()?;
+                self.define_label(&name, self.here + 1)?;
+            },
+            ":alias" => {
+                let name = self.name()?;
+                let v = self.reg()?;
+                self.aliases.insert(name.text, v);
+            },
+            ":const" => {
+                let name = self.name()?;
+                let (value, _) = self.value()?;
+                self.define_constant(&name, value)?;
+            },
+            ":calc" => {
+                let name = self.name()?;
+                self.expect("{")?;
+                let value = self.braced()?;
+                self.define_constant(&name, value)?;
+            },
+            ":org" => {
+                let (value, token) = self.value()?;
+                if !(f64::from(START) ..= 65535.0).contains(&value) { return error(&token, format!(":org {value} is outside of 0x200 ..= 0xFFFF")) }
+                self.here = value as u32;
+            },
+            ":byte" => {
+                let b = self.byte()?;
+                self.emit(b)?;
+            },
+            ":pointer" => {
+                let at = self.here;
+                let addr = self.addr(at, Fix::Word, 0xFFFF)?;
+                self.emit((addr >> 8) as u8)?;
+                self.emit(addr as u8)?;
+            },
+            ":call" => {
+                let nnn = self.addr(self.here, Fix::Nnn, 0xFFF)?;
+                self.emit_op(0x2000 | nnn, Some(&t))?;
+            },
+            ":unpack" => {
+                let long = self.peek() == Some("long");
+                let hi = if long { self.next()?; 0 } else { self.nibble()? as u8 };
+                let addr = if long { self.addr(self.here, Fix::UnpackLong, 0xFFFF)? } else { self.addr(self.here, Fix::Unpack(hi), 0xFFF)? };
+                self.emit_op(0x6000 | u16::from(hi) << 4 | addr >> 8, Some(&t))?;
+                self.emit_op(0x6100 | (addr & 0xFF), Some(&t))?;
+            },
+            ":macro" => {
+                let name = self.name()?;
+                let mut args = Vec::new();
+                while self.peek() != Some("{") { args.push(self.name()?.text) }
+                self.expect("{")?;
+                let mut body = Vec::new();
+                let mut depth = 0;
+                loop {
+                    let token = self.next()?;
+                    match token.text.as_str() {
+                        "{"             => depth += 1,
+                        "}" if depth == 0 => break,
+                        "}"             => depth -= 1,
+                        _               => {},
+                    }
+                    body.push(token);
+                }
+                self.macros.insert(name.text, Macro { args, body });
+            },
+
+            "return" | ";"      => self.emit_op(0x00EE, Some(&t))?,
+            "clear"             => self.emit_op(0x00E0, Some(&t))?,
+            "hires"             => self.emit_op(0x00FF, Some(&t))?,
+            "lores"             => self.emit_op(0x00FE, Some(&t))?,
+            "exit"              => self.emit_op(0x00FD, Some(&t))?,
+            "scroll-left"       => self.emit_op(0x00FC, Some(&t))?,
+            "scroll-right"      => self.emit_op(0x00FB, Some(&t))?,
+            "scroll-down"       => { let n = self.nibble()?; self.emit_op(0x00C0 | n, Some(&t))? },
+            "scroll-up"         => { let n = self.nibble()?; self.emit_op(0x00D0 | n, Some(&t))? },
+            "audio"             => self.emit_op(0xF002, Some(&t))?,
+            "plane"             => { let n = self.nibble()?; self.emit_op(0xF001 | n << 8, Some(&t))? },
+            "bcd"               => { let vx = self.reg()?; self.emit_op(0xF033 | x(vx), Some(&t))? },
+            "saveflags"         => { let vx = self.reg()?; self.emit_op(0xF075 | x(vx), Some(&t))? },
+            "loadflags"         => { let vx = self.reg()?; self.emit_op(0xF085 | x(vx), Some(&t))? },
+            "save" | "load"     => {
+                let vx = self.reg()?;
+                let (single, range) = if t.text == "save" { (0xF055, 0x5002) } else { (0xF065, 0x5003) };
+                if self.peek() == Some("-") {
+                    self.next()?;
+                    let vy = self.reg()?;
+                    self.emit_op(range | x(vx) | y(vy), Some(&t))?;
+                } else {
+                    self.emit_op(single | x(vx), Some(&t))?;
+                }
+            },
+            "sprite"            => {
+                let (vx, vy, n) = (self.reg()?, self.reg()?, self.nibble()?);
+                self.emit_op(0xD000 | x(vx) | y(vy) | n, Some(&t))?;
+            },
+            "jump"              => { let nnn = self.addr(self.here, Fix::Nnn, 0xFFF)?; self.emit_op(0x1000 | nnn, Some(&t))? },
+            "jump0"             => { let nnn = self.addr(self.here, Fix::Nnn, 0xFFF)?; self.emit_op(0xB000 | nnn, Some(&t))? },
+            "native"            => { let nnn = self.addr(self.here, Fix::Nnn, 0xFFF)?; self.emit_op(0x0000 | nnn, Some(&t))? },
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let vx = self.reg()?;
+                let op = match t.text.as_str() { "delay" => 0xF015, "buzzer" => 0xF018, _ => 0xF03A };
+                self.emit_op(op | x(vx), Some(&t))?;
+            },
+            "i"                 => {
+                let op = self.next()?;
+                match (op.text.as_str(), self.peek()) {
+                    (":=", Some("hex"))     => { self.next()?; let vx = self.reg()?; self.emit_op(0xF029 | x(vx), Some(&t))? },
+                    (":=", Some("bighex"))  => { self.next()?; let vx = self.reg()?; self.emit_op(0xF030 | x(vx), Some(&t))? },
+                    (":=", Some("long"))    => {
+                        self.next()?;
+                        let nnnn = self.addr(self.here + 2, Fix::Word, 0xFFFF)?;
+                        self.emit_op(0xF000, Some(&t))?;
+                        self.emit_op(nnnn, None)?;
+                    },
+                    (":=", _)               => { let nnn = self.addr(self.here, Fix::Nnn, 0xFFF)?; self.emit_op(0xA000 | nnn, Some(&t))? },
+                    ("+=", _)               => { let vx = self.reg()?; self.emit_op(0xF01E | x(vx), Some(&t))? },
+                    _                       => return error(&op, format!("expected `:=` or `+=`, got `{}`", op.text)),
+                }
+            },
+
+            "if" => {
+                let cond = self.condition()?;
+                let then = self.next()?;
+                match then.text.as_str() {
+                    "then"  => self.skip_if(cond.not(), &t)?,
+                    "begin" => {
+                        self.skip_if(cond, &t)?;
+                        let jump = self.here;
+                        self.emit_op(0x1000, Some(&t))?;
+                        self.flow.push(Flow::If { token: t, jump });
+                    },
+                    _ => return error(&then, format!("expected `then` or `begin`, got `{}`", then.text)),
+                }
+            },
+            "else" => {
+                let Some(Flow::If { token, jump }) = self.flow.pop() else { return error(&t, "`else` without `if ... begin`") };
+                let end = self.here;
+                self.emit_op(0x1000, Some(&t))?;
+                if self.here > 0xFFF { return error(&t, "`else` is beyond 12-bit addresses") }
+                self.patch(jump, 0x1000 | self.here as u16);
+                self.flow.push(Flow::Else { token, jump: end });
+            },
+            "end" => {
+                let (Some(Flow::If { jump, .. }) | Some(Flow::Else { jump, .. })) = self.flow.pop() else { return error(&t, "`end` without `if ... begin`") };
+                if self.here > 0xFFF { return error(&t, "`end` is beyond 12-bit addresses") }
+                self.patch(jump, 0x1000 | self.here as u16);
+            },
+            "loop" => self.flow.push(Flow::Loop { token: t, start: self.here, breaks: Vec::new() }),
+            "while" => {
+                let cond = self.condition()?;
+                self.skip_if(cond, &t)?;
+                let jump = self.here;
+                self.emit_op(0x1000, Some(&t))?;
+                match self.flow.iter_mut().rev().find_map(|f| match f { Flow::Loop { breaks, .. } => Some(breaks), _ => None }) {
+                    Some(breaks)    => breaks.push(jump),
+                    None            => return error(&t, "`while` outside of `loop`"),
+                }
+            },
+            "again" => {
+                let Some(Flow::Loop { start, breaks, .. }) = self.flow.pop() else { return error(&t, "`again` without `loop`") };
+                if start > 0xFFF { return error(&t, "`loop` is beyond 12-bit addresses") }
+                self.emit_op(0x1000 | start as u16, Some(&t))?;
+                if !breaks.is_empty() && self.here > 0xFFF { return error(&t, "`again` is beyond 12-bit addresses") }
+                for jump in breaks { self.patch(jump, 0x1000 | self.here as u16) }
+            },
+
+            _ if self.as_reg(&t).is_some() => {
+                let vx = self.as_reg(&t).unwrap();
+                let op = self.next()?;
+                let reg_op = match op.text.as_str() {
+                    ":=" => match self.peek() {
+                        Some("key")     => { self.next()?; return self.emit_op(0xF00A | x(vx), Some(&t)) },
+                        Some("delay")   => { self.next()?; return self.emit_op(0xF007 | x(vx), Some(&t)) },
+                        Some("random")  => { self.next()?; let n = self.byte()?; return self.emit_op(0xC000 | x(vx) | u16::from(n), Some(&t)) },
+                        _ => match self.operand()? {
+                            Operand::V(vy)      => 0x8000 | y(vy),
+                            Operand::Byte(n)    => return self.emit_op(0x6000 | x(vx) | u16::from(n), Some(&t)),
+                        },
+                    },
+                    "+=" => match self.operand()? {
+                        Operand::V(vy)      => 0x8004 | y(vy),
+                        Operand::Byte(n)    => return self.emit_op(0x7000 | x(vx) | u16::from(n), Some(&t)),
+                    },
+                    "-=" => match self.operand()? {
+                        Operand::V(vy)      => 0x8005 | y(vy),
+                        Operand::Byte(n)    => return self.emit_op(0x7000 | x(vx) | u16::from(n.wrapping_neg()), Some(&t)),
+                    },
+                    "=-"    => 0x8007 | y(self.reg()?),
+                    "|="    => 0x8001 | y(self.reg()?),
+                    "&="    => 0x8002 | y(self.reg()?),
+                    "^="    => 0x8003 | y(self.reg()?),
+                    ">>="   => 0x8006 | y(self.reg()?),
+                    "<<="   => 0x800E | y(self.reg()?),
+                    _       => return error(&op, format!("expected an assignment operator, got `{}`", op.text)),
+                };
+                self.emit_op(reg_op | x(vx), Some(&t))?;
+            },
+
+            _ if self.macros.contains_key(&t.text) => {
+                self.expansions += 1;
+                if self.expansions > MAX_EXPANSIONS { return error(&t, "too many macro expansions (recursive macro?)") }
+                let nargs = self.macros[&t.text].args.len();
+                let mut args = HashMap::new();
+                for i in 0 .. nargs {
+                    let arg = self.next()?;
+                    args.insert(self.macros[&t.text].args[i].clone(), arg.text);
+                }
+                let body = self.macros[&t.text].body.iter().rev().map(|token| match args.get(&token.text) {
+                    Some(arg)   => Token { text: arg.clone(), ..token.clone() },
+                    None        => token.clone(),
+                }).collect::<Vec<_>>();
+                for token in body { self.tokens.push_front(token) }
+            },
+
+            "{" => {
+                let value = self.braced()?;
+                self.emit_value_byte(value, &t)?;
+            },
+            _ if self.lookup(&t.text).is_some() && !self.labels.contains_key(&t.text) => {
+                let value = self.lookup(&t.text).unwrap();
+                self.emit_value_byte(value, &t)?;
+            },
+            _ if t.text.starts_with(':') => return error(&t, format!("unknown directive `{}`", t.text)),
+            _ => {
+                // bare label: call subroutine
+                self.tokens.push_front(t.clone());
+                let nnn = self.addr(self.here, Fix::Nnn, 0xFFF)?;
+                self.emit_op(0x2000 | nnn, Some(&t))?;
+            },
+        }
+        Ok(())
+    }
+
+    fn emit_value_byte(&mut self, value: f64, token: &Token) -> Result<(), Error> {
+        let int = value.floor() as i64;
+        if !(-128 ..= 255).contains(&int) { return error(token, format!("{value} doesn't fit in a byte")) }
+        self.emit(int as u8)
+    }
+
+    /// Evaluate an expression up to and including the closing `}` (the opening `{` has already been consumed.)
+    fn braced(&mut self) -> Result<f64, Error> {
+        let open = self.last.clone();
+        let mut tokens = Vec::new();
+        loop {
+            let token = self.next()?;
+            if token.text == "}" { break }
+            tokens.push(token);
+        }
+        if tokens.is_empty() { return error(&open, "empty expression") }
+        let mut pos = 0;
+        let value = self.expr(&tokens, &mut pos)?;
+        if let Some(token) = tokens.get(pos) { return error(token, format!("unexpected `{}` in expression", token.text)) }
+        Ok(value)
+    }
+
+    fn expr(&self, tokens: &[Token], pos: &mut usize) -> Result<f64, Error> {
+        let lhs = self.term(tokens, pos)?;
+        let Some(op) = tokens.get(*pos).filter(|t| t.text != ")") else { return Ok(lhs) };
+        *pos += 1;
+        let rhs = self.expr(tokens, pos)?;
+        let (a, b) = (lhs as i64, rhs as i64);
+        let bool = |b: bool| if b { 1.0 } else { 0.0 };
+        Ok(match op.text.as_str() {
+            "+"     => lhs + rhs,
+            "-"     => lhs - rhs,
+            "*"     => lhs * rhs,
+            "/"     => lhs / rhs,
+            "%"     => lhs % rhs,
+            "pow"   => lhs.powf(rhs),
+            "min"   => lhs.min(rhs),
+            "max"   => lhs.max(rhs),
+            "&"     => (a & b) as f64,
+            "|"     => (a | b) as f64,
+            "^"     => (a ^ b) as f64,
+            "<<"    => a.wrapping_shl(b as u32) as f64,
+            ">>"    => a.wrapping_shr(b as u32) as f64,
+            "<"     => bool(lhs <  rhs),
+            "<="    => bool(lhs <= rhs),
+            ">"     => bool(lhs >  rhs),
+            ">="    => bool(lhs >= rhs),
+            "=="    => bool(lhs == rhs),
+            "!="    => bool(lhs != rhs),
+            _       => return error(op, format!("unknown operator `{}`", op.text)),
+        })
+    }
+
+    fn term(&self, tokens: &[Token], pos: &mut usize) -> Result<f64, Error> {
+        let Some(token) = tokens.get(*pos) else { return error(tokens.last().unwrap_or(&self.last), "expected a value") };
+        *pos += 1;
+        let unary = |f: fn(f64) -> f64, pos: &mut usize| self.term(tokens, pos).map(f);
+        match token.text.as_str() {
+            "("     => {
+                let value = self.expr(tokens, pos)?;
+                match tokens.get(*pos) {
+                    Some(t) if t.text == ")" => { *pos += 1; Ok(value) },
+                    _ => error(token, "unclosed `(`"),
+                }
+            },
+            "-"     => unary(|v| -v, pos),
+            "~"     => unary(|v| !(v as i64) as f64, pos),
+            "!"     => unary(|v| if v == 0.0 { 1.0 } else { 0.0 }, pos),
+            "sin"   => unary(f64::sin, pos),
+            "cos"   => unary(f64::cos, pos),
+            "tan"   => unary(f64::tan, pos),
+            "exp"   => unary(f64::exp, pos),
+            "log"   => unary(f64::ln, pos),
+            "abs"   => unary(f64::abs, pos),
+            "sqrt"  => unary(f64::sqrt, pos),
+            "sign"  => unary(f64::signum, pos),
+            "ceil"  => unary(f64::ceil, pos),
+            "floor" => unary(f64::floor, pos),
+            "@"     => {
+                let addr = self.term(tokens, pos)? as i64;
+                Ok(addr.checked_sub(START.into()).and_then(|i| self.rom.get(usize::try_from(i).ok()?)).copied().unwrap_or(0).into())
+            },
+            "HERE"  => Ok(self.here.into()),
+            "PI"    => Ok(std::f64::consts::PI),
+            "E"     => Ok(std::f64::consts::E),
+            text    => match self.lookup(text) {
+                Some(value) => Ok(value),
+                None        => error(token, format!("undefined name `{text}` (expressions can't reference later labels)")),
+            },
+        }
+    }
+
+    fn finish(mut self) -> Result<Assembly, Error> {
+        if let Some(flow) = self.flow.last() {
+            let (token, what) = match flow { Flow::If { token, .. } | Flow::Else { token, .. } => (token, "`if ... begin` without `end`"), Flow::Loop { token, .. } => (token, "`loop` without `again`") };
+            return error(token, what)
+        }
+
+        for Fixup { at, fix, name } in std::mem::take(&mut self.fixups) {
+            let Some(addr) = self.labels.get(&name.text).map(|a| a.0) else { return error(&name, format!("undefined name `{}`", name.text)) };
+            let idx = (at - START) as usize;
+            match fix {
+                Fix::Nnn => {
+                    if addr > 0xFFF { return error(&name, format!("`{}` ({addr:#06x}) is beyond 12-bit addresses", name.text)) }
+                    self.rom[idx] |= (addr >> 8) as u8;
+                    self.rom[idx+1] = addr as u8;
+                },
+                Fix::Word => self.rom[idx..idx+2].copy_from_slice(&addr.to_be_bytes()),
+                Fix::Unpack(hi) => {
+                    if addr > 0xFFF { return error(&name, format!("`{}` ({addr:#06x}) is beyond 12-bit addresses", name.text)) }
+                    self.rom[idx+1] = hi << 4 | (addr >> 8) as u8;
+                    self.rom[idx+3] = addr as u8;
+                },
+                Fix::UnpackLong => {
+                    self.rom[idx+1] = (addr >> 8) as u8;
+                    self.rom[idx+3] = addr as u8;
+                },
+            }
+        }
+
+        if self.jump_main {
+            let Some(main) = self.labels.get("main").map(|a| a.0) else { return error(&Token { text: String::new(), line: 1, column: 1 }, "no `: main` label") };
+            if main > 0xFFF { return error(&self.last, "`main` is beyond 12-bit addresses") }
+            self.patch(START, 0x1000 | main);
+        }
+
+        Ok(Assembly { rom: self.rom, labels: self.labels, constants: self.constants, source_map: self.source_map })
+    }
+}
+
+#[test] fn test_assemble() {
+    let src = "
+        :alias x v2
+        :const SPEED 3
+        :calc DOUBLE { SPEED * 2 }
+        :macro twice op { op op }
+
+        : main
+            x := SPEED
+            x += DOUBLE
+            i := sprite
+            loop
+                sprite x v3 5
+                while x != 0
+                x -= 1
+                if x key then x := random 0xF0
+            again
+            if v1 < 4 begin
+                twice clear
+            else
+                sub
+            end
+            :unpack 0xA sprite
+            save v0 - v3
+        : sub
+            i := long sprite
+            ;
+        : sprite
+            0x3C { 1 << 2 } :pointer main
+    ";
+    let asm = assemble(src, "test.8o").unwrap();
+    assert_eq!(asm.labels["main"], Addr(0x200));
+    assert_eq!(asm.labels["sub"], Addr(0x22A));
+    assert_eq!(asm.labels["sprite"], Addr(0x230));
+    assert_eq!(asm.rom, [
+        0x62, 0x03,             // 200: x := SPEED
+        0x72, 0x06,             // 202: x += DOUBLE
+        0xA2, 0x30,             // 204: i := sprite
+        0xD2, 0x35,             // 206: sprite x v3 5
+        0x42, 0x00, 0x12, 0x14, // 208: while x != 0
+        0x72, 0xFF,             // 20C: x -= 1
+        0xE2, 0xA1, 0xC2, 0xF0, // 20E: if x key then x := random 0xF0
+        0x12, 0x06,             // 212: again
+        0x6F, 0x04, 0x8F, 0x17, 0x3F, 0x00, 0x12, 0x22, // 214: if v1 < 4 begin
+        0x00, 0xE0, 0x00, 0xE0, // 21C: twice clear
+        0x12, 0x24,             // 220: else
+        0x22, 0x2A,             // 222: sub
+        0x60, 0xA2, 0x61, 0x30, // 224: :unpack 0xA sprite
+        0x50, 0x32,             // 228: save v0 - v3
+        0xF0, 0x00, 0x02, 0x30, // 22A: i := long sprite
+        0x00, 0xEE,             // 22E: ;
+        0x3C, 0x04, 0x02, 0x00, // 230: data
+    ][..]);
+    assert_eq!(asm.source_map.location(Addr(0x214)).map(|l| l.line), Some(17));
+    assert_eq!(asm.source_map.location(Addr(0x21E)).map(|l| l.line), Some(5)); // macro body
+
+    assert_eq!(assemble(": start\n  jump main\n: main ;", "").unwrap().rom, [0x12, 0x04, 0x12, 0x04, 0x00, 0xEE]);
+    assert_eq!(assemble(": main\n  v0 := 300", "").unwrap_err(), Error { line: 2, column: 9, message: "300 doesn't fit in a byte".into() });
+    assert_eq!(assemble(": main\n  jump nowhere", "").unwrap_err(), Error { line: 2, column: 8, message: "undefined name `nowhere`".into() });
+    assert_eq!(assemble(": main loop", "").unwrap_err().message, "`loop` without `again`");
+    assert_eq!(assemble(": main if v0 == 0 begin :org 0xFFE else end", "").unwrap_err().message, "`else` is beyond 12-bit addresses");
+
+    let xo = assemble(": main\n  :unpack long data\n  :unpack long 0x1234\n:org 0x1200\n: data 0x55", "").unwrap();
+    assert_eq!(xo.labels["data"], Addr(0x1200));
+    assert_eq!(xo.rom[..8], [0x60, 0x12, 0x61, 0x00, 0x60, 0x12, 0x61, 0x34]);
+    assert_eq!(xo.rom[0x1000], 0x55);
+}
+
+#[test] fn test_compare() {
+    for (a, b) in [(2, 4), (4, 4), (6, 4), (0, 255), (255, 0)] {
+        for (op, expected) in [("<", a < b), (">", a > b), ("<=", a <= b), (">=", a >= b)] {
+            let src = format!(": main  v1 := {a}  v2 := {b}  if v1 {op} v2 then v3 := 1  if v1 {op} {b} then v4 := 1  : halt  jump halt");
+            let asm = assemble(&src, "").unwrap();
+            let mut ctx = Context::<()>::default();
+            ctx.memory.copy_from_slice(Addr::PROGRAM_START_TYPICAL, &asm.rom).unwrap();
+            ctx.registers.pc = Addr::PROGRAM_START_TYPICAL;
+            ctx.try_step_many(20).unwrap();
+            assert_eq!([ctx.registers[V(N3)], ctx.registers[V(N4)]], [u8::from(expected); 2], "{src}");
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use crate::*;
+
+
+
+/// Pre-decoded instructions by address, for [`Context::try_step_cached`] / [`Context::try_step_many_cached`].
+///
+/// Memory writes made *by instructions* (`FX55`, `FX33`, `5XY2`, in-memory call stacks, and the COSMAC VIP screen) invalidate
+/// the instructions they overwrite automatically.  Anything else that writes to <code>[Context]::memory</code> — loading ROMs,
+/// [`savestate`]s, [`rewind`]ing, debugger memory writes... — must call [`invalidate`](Self::invalidate) or [`clear`](Self::clear).
+#[derive(Clone, Debug, Default)] pub struct DecodeCache {
+    insts:  Vec<(Op, Inst)>, // by address, Inst::Undecoded if not yet decoded
+    cached: Option<(usize, usize)>, // lowest and highest decoded addresses, to skip invalidating data writes
+}
+
+impl DecodeCache {
+    pub fn new() -> Self { Self::default() }
+
+    /// Forget all decoded instructions.
+    pub fn clear(&mut self) {
+        self.insts.fill((Op(0), Inst::Undecoded));
+        self.cached = None;
+    }
+
+    /// Forget decoded instructions overlapping `range`.
+    pub fn invalidate(&mut self, range: MemoryRange) {
+        let Some((lo, hi)) = self.cached else { return };
+        let start = range.start.to_usize().saturating_sub(1); // an instruction starting at the previous byte overlaps too
+        let end = (range.start.to_usize() + range.len as usize).min(self.insts.len());
+        if end <= lo || start > hi { return }
+        self.insts[start..end].fill((Op(0), Inst::Undecoded));
+    }
+
+    fn fit<M: Memory>(&mut self) {
+        if self.insts.len() != M::SIZE {
+            self.insts = vec![(Op(0), Inst::Undecoded); M::SIZE];
+            self.cached = None;
+        }
+    }
+}
+
+impl<S: Syscalls, M: Memory, O: Observer> Context<S, M, O> {
+    /// [`try_step_single`](Self::try_step_single), but reusing instructions previously decoded into `cache`.
+    pub fn try_step_cached(&mut self, cache: &mut DecodeCache) -> Result<StepOutcome, Fault> {
+        cache.fit::<M>();
+        self.step_cached(cache)
+    }
+
+    /// [`try_step_many`](Self::try_step_many), but reusing instructions previously decoded into `cache`.
+    pub fn try_step_many_cached(&mut self, cache: &mut DecodeCache, steps: usize) -> Result<usize, Fault> {
+        cache.fit::<M>();
+        for step in 0 .. steps {
+            if self.step_cached(cache)? != StepOutcome::Stepped { return Ok(step) }
+        }
+        Ok(steps)
+    }
+
+    #[inline(always)] fn step_cached(&mut self, cache: &mut DecodeCache) -> Result<StepOutcome, Fault> {
+        self.check_range(self.registers.pc, 2)?;
+        let pc = self.registers.pc.to_usize();
+        let (op, inst) = match cache.insts[pc] {
+            (_, Inst::Undecoded) => {
+                let op = Op(self.memory.read16(self.registers.pc));
+                cache.insts[pc] = (op, op.decode(&mut Predecode));
+                cache.cached = Some(cache.cached.map_or((pc, pc), |(lo, hi)| (lo.min(pc), hi.max(pc))));
+                cache.insts[pc]
+            },
+            cached => cached,
+        };
+
+        self.observed(op, |ctx| {
+            if !inst.may_write_memory() { return inst.execute(&mut Step(ctx)) }
+            let write = inst.execute(&mut Analyze(ctx)).memory_write;
+            let outcome = inst.execute(&mut Step(ctx));
+            if let Some(write) = write { cache.invalidate(write) }
+            outcome
+        })
+    }
+}
+
+
+
+/// An [`Op`], decoded.  Variants mirror [`Decode`]'s fns.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)] enum Inst {
+    #[default] Undecoded,
+    Invalid(u16),
+    CallMcs(Addr),
+    DisplayClear,
+    FlowReturn,
+    FlowGoto(Addr),
+    FlowCall(Addr),
+    SkipIfVEqC(V, u8),
+    SkipIfVNeC(V, u8),
+    SkipIfVEqV(V, V),
+    SetVC(V, u8),
+    AddVC(V, u8),
+    SetVV(V, V),
+    BitorVV(V, V),
+    BitandVV(V, V),
+    BitxorVV(V, V),
+    AddVV(V, V),
+    SubVV(V, V),
+    Shr1V(V, V),
+    SubVVAlt(V, V),
+    Shl1V(V, V),
+    SkipIfVNeV(V, V),
+    SetIC(Addr),
+    SetPcV0PlusC(Addr),
+    SetVRandMask(V, u8),
+    DrawXYH(V, V, Nibble),
+    SkipIfPressed(V),
+    SkipUnlessPressed(V),
+    GetDelayTimer(V),
+    AwaitKey(V),
+    SetDelayTimer(V),
+    SetSoundTimer(V),
+    AddIV(V),
+    SetISprite(V),
+    SetIBcd(V),
+    RegDump(V),
+    RegLoad(V),
+    ScrollDown(Nibble),
+    ScrollRight,
+    ScrollLeft,
+    Exit,
+    Lores,
+    Hires,
+    DrawXY16x16(V, V),
+    SetISpriteLarge(V),
+    RplDump(V),
+    RplLoad(V),
+    ScrollUp(Nibble),
+    RegDumpRange(V, V),
+    RegLoadRange(V, V),
+    SetILong,
+    SelectPlanes(Nibble),
+    AudioPattern,
+    SetPitch(V),
+}
+
+impl Inst {
+    fn may_write_memory(self) -> bool {
+        matches!(self, Inst::CallMcs(_) | Inst::DisplayClear | Inst::FlowCall(_) | Inst::DrawXYH(..) | Inst::DrawXY16x16(..) | Inst::SetIBcd(_) | Inst::RegDump(_) | Inst::RegDumpRange(..))
+    }
+
+    #[inline(always)] fn execute<D: Decode>(self, d: &mut D) -> D::Result {
+        match self {
+            Inst::Undecoded                 => unreachable!("Inst::Undecoded executed"),
+            Inst::Invalid(op)               => d.invalid(op),
+            Inst::CallMcs(addr)             => d.call_mcs(addr),
+            Inst::DisplayClear              => d.display_clear(),
+            Inst::FlowReturn                => d.flow_return(),
+            Inst::FlowGoto(addr)            => d.flow_goto(addr),
+            Inst::FlowCall(addr)            => d.flow_call(addr),
+            Inst::SkipIfVEqC(v, c)          => d.skip_if_v_eq_c(v, c),
+            Inst::SkipIfVNeC(v, c)          => d.skip_if_v_ne_c(v, c),
+            Inst::SkipIfVEqV(vx, vy)        => d.skip_if_v_eq_v(vx, vy),
+            Inst::SetVC(vx, c)              => d.set_v_c(vx, c),
+            Inst::AddVC(vx, c)              => d.add_v_c(vx, c),
+            Inst::SetVV(vx, vy)             => d.set_v_v(vx, vy),
+            Inst::BitorVV(vx, vy)           => d.bitor_v_v(vx, vy),
+            Inst::BitandVV(vx, vy)          => d.bitand_v_v(vx, vy),
+            Inst::BitxorVV(vx, vy)          => d.bitxor_v_v(vx, vy),
+            Inst::AddVV(vx, vy)             => d.add_v_v(vx, vy),
+            Inst::SubVV(vx, vy)             => d.sub_v_v(vx, vy),
+            Inst::Shr1V(vx, vy)             => d.shr1_v(vx, vy),
+            Inst::SubVVAlt(vx, vy)          => d.sub_v_v_alt(vx, vy),
+            Inst::Shl1V(vx, vy)             => d.shl1_v(vx, vy),
+            Inst::SkipIfVNeV(vx, vy)        => d.skip_if_v_ne_v(vx, vy),
+            Inst::SetIC(c)                  => d.set_i_c(c),
+            Inst::SetPcV0PlusC(c)           => d.set_pc_v0_plus_c((), c),
+            Inst::SetVRandMask(v, mask)     => d.set_v_rand_mask(v, mask),
+            Inst::DrawXYH(vx, vy, h)        => d.draw_x_y_h(vx, vy, h),
+            Inst::SkipIfPressed(key)        => d.skip_if_pressed(key),
+            Inst::SkipUnlessPressed(key)    => d.skip_unless_pressed(key),
+            Inst::GetDelayTimer(v)          => d.get_delay_timer(v),
+            Inst::AwaitKey(v)               => d.await_key(v),
+            Inst::SetDelayTimer(v)          => d.set_delay_timer(v),
+            Inst::SetSoundTimer(v)          => d.set_sound_timer(v),
+            Inst::AddIV(v)                  => d.add_i_v(v),
+            Inst::SetISprite(v)             => d.set_i_sprite(v),
+            Inst::SetIBcd(v)                => d.set_i_bcd(v),
+            Inst::RegDump(v)                => d.reg_dump(v),
+            Inst::RegLoad(v)                => d.reg_load(v),
+            Inst::ScrollDown(n)             => d.scroll_down(n),
+            Inst::ScrollRight               => d.scroll_right(),
+            Inst::ScrollLeft                => d.scroll_left(),
+            Inst::Exit                      => d.exit(),
+            Inst::Lores                     => d.lores(),
+            Inst::Hires                     => d.hires(),
+            Inst::DrawXY16x16(vx, vy)       => d.draw_x_y_16x16(vx, vy),
+            Inst::SetISpriteLarge(v)        => d.set_i_sprite_large(v),
+            Inst::RplDump(v)                => d.rpl_dump(v),
+            Inst::RplLoad(v)                => d.rpl_load(v),
+            Inst::ScrollUp(n)               => d.scroll_up(n),
+            Inst::RegDumpRange(vx, vy)      => d.reg_dump_range(vx, vy),
+            Inst::RegLoadRange(vx, vy)      => d.reg_load_range(vx, vy),
+            Inst::SetILong                  => d.set_i_long(),
+            Inst::SelectPlanes(n)           => d.select_planes(n),
+            Inst::AudioPattern              => d.audio_pattern(),
+            Inst::SetPitch(v)               => d.set_pitch(v),
+        }
+    }
+}
+
+struct Predecode;
+impl Decode for Predecode {
+    type Result = Inst;
+
+    fn invalid              (&mut self, op: u16)                    -> Inst { Inst::Invalid(op) }
+    fn call_mcs             (&mut self, addr: Addr)                 -> Inst { Inst::CallMcs(addr) }
+    fn display_clear        (&mut self)                             -> Inst { Inst::DisplayClear }
+    fn flow_return          (&mut self)                             -> Inst { Inst::FlowReturn }
+    fn flow_goto            (&mut self, addr: Addr)                 -> Inst { Inst::FlowGoto(addr) }
+    fn flow_call            (&mut self, addr: Addr)                 -> Inst { Inst::FlowCall(addr) }
+    fn skip_if_v_eq_c       (&mut self, v: V, c: u8)                -> Inst { Inst::SkipIfVEqC(v, c) }
+    fn skip_if_v_ne_c       (&mut self, v: V, c: u8)                -> Inst { Inst::SkipIfVNeC(v, c) }
+    fn skip_if_v_eq_v       (&mut self, vx: V, vy: V)               -> Inst { Inst::SkipIfVEqV(vx, vy) }
+    fn set_v_c              (&mut self, vx: V, c: u8)               -> Inst { Inst::SetVC(vx, c) }
+    fn add_v_c              (&mut self, vx: V, c: u8)               -> Inst { Inst::AddVC(vx, c) }
+    fn set_v_v              (&mut self, vx: V, vy: V)               -> Inst { Inst::SetVV(vx, vy) }
+    fn bitor_v_v            (&mut self, vx: V, vy: V)               -> Inst { Inst::BitorVV(vx, vy) }
+    fn bitand_v_v           (&mut self, vx: V, vy: V)               -> Inst { Inst::BitandVV(vx, vy) }
+    fn bitxor_v_v           (&mut self, vx: V, vy: V)               -> Inst { Inst::BitxorVV(vx, vy) }
+    fn add_v_v              (&mut self, vx: V, vy: V)               -> Inst { Inst::AddVV(vx, vy) }
+    fn sub_v_v              (&mut self, vx: V, vy: V)               -> Inst { Inst::SubVV(vx, vy) }
+    fn shr1_v               (&mut self, vx: V, vy: V)               -> Inst { Inst::Shr1V(vx, vy) }
+    fn sub_v_v_alt          (&mut self, vx: V, vy: V)               -> Inst { Inst::SubVVAlt(vx, vy) }
+    fn shl1_v               (&mut self, vx: V, vy: V)               -> Inst { Inst::Shl1V(vx, vy) }
+    fn skip_if_v_ne_v       (&mut self, vx: V, vy: V)               -> Inst { Inst::SkipIfVNeV(vx, vy) }
+    fn set_i_c              (&mut self, c: Addr)                    -> Inst { Inst::SetIC(c) }
+    fn set_pc_v0_plus_c     (&mut self, _v0: (), c: Addr)           -> Inst { Inst::SetPcV0PlusC(c) }
+    fn set_v_rand_mask      (&mut self, v: V, mask: u8)             -> Inst { Inst::SetVRandMask(v, mask) }
+    fn draw_x_y_h           (&mut self, vx: V, vy: V, h: Nibble)    -> Inst { Inst::DrawXYH(vx, vy, h) }
+    fn skip_if_pressed      (&mut self, key: V)                     -> Inst { Inst::SkipIfPressed(key) }
+    fn skip_unless_pressed  (&mut self, key: V)                     -> Inst { Inst::SkipUnlessPressed(key) }
+    fn get_delay_timer      (&mut self, v: V)                       -> Inst { Inst::GetDelayTimer(v) }
+    fn await_key            (&mut self, v: V)                       -> Inst { Inst::AwaitKey(v) }
+    fn set_delay_timer      (&mut self, v: V)                       -> Inst { Inst::SetDelayTimer(v) }
+    fn set_sound_timer      (&mut self, v: V)                       -> Inst { Inst::SetSoundTimer(v) }
+    fn add_i_v              (&mut self, v: V)                       -> Inst { Inst::AddIV(v) }
+    fn set_i_sprite         (&mut self, v: V)                       -> Inst { Inst::SetISprite(v) }
+    fn set_i_bcd            (&mut self, v: V)                       -> Inst { Inst::SetIBcd(v) }
+    fn reg_dump             (&mut self, v: V)                       -> Inst { Inst::RegDump(v) }
+    fn reg_load             (&mut self, v: V)                       -> Inst { Inst::RegLoad(v) }
+
+    fn scroll_down          (&mut self, n: Nibble)                  -> Inst { Inst::ScrollDown(n) }
+    fn scroll_right         (&mut self)                             -> Inst { Inst::ScrollRight }
+    fn scroll_left          (&mut self)                             -> Inst { Inst::ScrollLeft }
+    fn exit                 (&mut self)                             -> Inst { Inst::Exit }
+    fn lores                (&mut self)                             -> Inst { Inst::Lores }
+    fn hires                (&mut self)                             -> Inst { Inst::Hires }
+    fn draw_x_y_16x16       (&mut self, vx: V, vy: V)               -> Inst { Inst::DrawXY16x16(vx, vy) }
+    fn set_i_sprite_large   (&mut self, v: V)                       -> Inst { Inst::SetISpriteLarge(v) }
+    fn rpl_dump             (&mut self, v: V)                       -> Inst { Inst::RplDump(v) }
+    fn rpl_load             (&mut self, v: V)                       -> Inst { Inst::RplLoad(v) }
+
+    fn scroll_up            (&mut self, n: Nibble)                  -> Inst { Inst::ScrollUp(n) }
+    fn reg_dump_range       (&mut self, vx: V, vy: V)               -> Inst { Inst::RegDumpRange(vx, vy) }
+    fn reg_load_range       (&mut self, vx: V, vy: V)               -> Inst { Inst::RegLoadRange(vx, vy) }
+    fn set_i_long           (&mut self)                             -> Inst { Inst::SetILong }
+    fn select_planes        (&mut self, n: Nibble)                  -> Inst { Inst::SelectPlanes(n) }
+    fn audio_pattern        (&mut self)                             -> Inst { Inst::AudioPattern }
+    fn set_pitch            (&mut self, v: V)                       -> Inst { Inst::SetPitch(v) }
+}
+
+#[test] fn test_decode_cache() {
+    let program = [
+        0x60, 0x70,             // 200: V0 <- 0x70
+        0x61, 0x01,             // 202: V1 <- 0x01
+        0x62, 0x05,             // 204: V2 <- 5
+        0x12, 0x0E,             // 206: goto 20E
+        0xA2, 0x0E,             // 208: I <- 20E
+        0xF1, 0x55,             // 20A: [20E..=20F] <- [V0, V1]         rewrites 20E as `V0 += 1`
+        0x12, 0x0E,             // 20C: goto 20E
+        0x12, 0x14,             // 20E: goto 214                        (first pass)
+        0xA2, 0x14,             // 210: I <- 214
+        0xF2, 0x33,             // 212: [214..=216] <- bcd(V2)          rewrites 214 as `call_mcs 0`
+        0x12, 0x08,             // 214: goto 208                        (first pass)
+    ];
+    let mut cached = Context::<()> { quirks: Quirks::CHIP_48, ..Context::default() };
+    cached.memory.copy_from_slice(Addr(0x200), &program).unwrap();
+    cached.registers.pc = Addr(0x200);
+    let mut uncached = Context::<()> { quirks: Quirks::CHIP_48, ..Context::default() };
+    uncached.memory.copy_from_slice(Addr(0x200), &program).unwrap();
+    uncached.registers.pc = Addr(0x200);
+
+    let mut cache = DecodeCache::new();
+    for _ in 0 .. 100 {
+        assert_eq!(cached.try_step_cached(&mut cache), uncached.try_step_single());
+        assert_eq!(cached.registers, uncached.registers);
+    }
+    assert_eq!(cached.registers.pc, Addr(0x214));
+    assert_eq!(cached.registers[V0], 0x71);
+    assert_eq!(cached.memory.as_bytes_ref()[..], uncached.memory.as_bytes_ref()[..]);
+
+    for ctx in [&mut cached, &mut uncached] { // external writes need explicit invalidation
+        ctx.memory.write(Addr(0x214), 0x12);
+        ctx.memory.write(Addr(0x215), 0x12);
+    }
+    cache.invalidate(MemoryRange::new(Addr(0x214), 2));
+    assert_eq!(cached.try_step_many_cached(&mut cache, 100), uncached.try_step_many(100));
+    assert_eq!(cached.registers, uncached.registers);
+}
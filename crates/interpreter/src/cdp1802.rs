@@ -0,0 +1,270 @@
+//! [RCA CDP1802](https://en.wikipedia.org/wiki/RCA_1802) CPU core, as used by the [COSMAC VIP](crate::vip).
+//!
+//! ### References
+//! *   <https://en.wikipedia.org/wiki/RCA_1802#Instruction_set>
+
+
+
+/// Memory and I/O as seen by a [`Cpu`].
+pub trait Bus {
+    fn read(&mut self, addr: u16) -> u8;
+    fn write(&mut self, addr: u16, value: u8);
+
+    /// `OUT N` (`61` ..= `67`) put `value` on the bus, selecting `port` 1 ..= 7 via the N lines.
+    fn output(&mut self, _port: u8, _value: u8) {}
+
+    /// `INP N` (`69` ..= `6F`) read the bus, selecting `port` 1 ..= 7 via the N lines.
+    fn input(&mut self, _port: u8) -> u8 { 0 }
+
+    /// `true` if external flag `EF1` ..= `EF4` (`flag` 1 ..= 4) is asserted (the pin is low.)
+    fn ef(&mut self, _flag: u8) -> bool { false }
+
+    /// The `Q` output changed (`REQ` / `SEQ`.)
+    fn q(&mut self, _q: bool) {}
+}
+
+/// CDP1802 registers and execution state.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)] pub struct Cpu {
+    /// `R0` ..= `RF`: 16-bit scratchpad registers.  `R0` is the DMA pointer.
+    pub r:      [u16; 16],
+    /// Which of [`r`](Self::r) is the program counter (0 ..= 0xF.)
+    pub p:      u8,
+    /// Which of [`r`](Self::r) is the data pointer (0 ..= 0xF.)
+    pub x:      u8,
+    /// The accumulator.
+    pub d:      u8,
+    /// Data flag (carry / not borrow.)
+    pub df:     bool,
+    /// `X` and `P` saved on interrupt.
+    pub t:      u8,
+    /// Interrupt enable.
+    pub ie:     bool,
+    pub q:      bool,
+    /// `IDL` is waiting for an interrupt or DMA.
+    pub idle:   bool,
+}
+
+impl Default for Cpu { fn default() -> Self { Self::new() } }
+
+impl Cpu {
+    pub const fn new() -> Self { Self { r: [0; 16], p: 0, x: 0, d: 0, df: false, t: 0, ie: true, q: false, idle: false } }
+
+    /// Reset: `P`, `X`, `R0`, and `Q` are cleared, interrupts are enabled, and execution starts at address 0.
+    pub fn reset(&mut self, bus: &mut impl Bus) {
+        if self.q { bus.q(false) }
+        *self = Self { r: self.r, d: self.d, df: self.df, t: self.t, ..Self::new() };
+        self.r[0] = 0;
+    }
+
+    /// The program counter, <code>[r](Self::r)\[[p](Self::p)\]</code>.
+    pub fn pc(&self) -> u16 { self.r[usize::from(self.p)] }
+
+    /// Take an interrupt if [`ie`](Self::ie) allows it: save `X`/`P` in `T`, then continue with `P = 1`, `X = 2`.
+    /// Returns the machine cycles taken (0 if interrupts are disabled.)
+    pub fn interrupt(&mut self) -> u32 {
+        if !self.ie { return 0 }
+        self.t = self.x << 4 | self.p;
+        self.p = 1;
+        self.x = 2;
+        self.ie = false;
+        self.idle = false;
+        1
+    }
+
+    /// A DMA output cycle: read the byte at `R0` for a peripheral, and increment `R0`.  Takes 1 machine cycle.
+    pub fn dma_out(&mut self, bus: &mut impl Bus) -> u8 {
+        let value = bus.read(self.r[0]);
+        self.r[0] = self.r[0].wrapping_add(1);
+        self.idle = false;
+        value
+    }
+
+    /// Execute a single instruction, returning the machine cycles taken (2, or 3 for `CN` long branches and skips.)
+    /// While [`idle`](Self::idle), nothing is executed and 1 machine cycle passes.
+    pub fn step(&mut self, bus: &mut impl Bus) -> u32 {
+        if self.idle { return 1 }
+
+        let op = self.imm(bus);
+        let n = op & 0xF;
+        let rn = usize::from(n);
+        let rx = usize::from(self.x);
+        match op >> 4 {
+            0x0 if n == 0   => self.idle = true,                                            // IDL
+            0x0             => self.d = bus.read(self.r[rn]),                               // LDN
+            0x1             => self.r[rn] = self.r[rn].wrapping_add(1),                     // INC
+            0x2             => self.r[rn] = self.r[rn].wrapping_sub(1),                     // DEC
+            0x3             => {                                                            // BR, BQ, BZ, ... SKP, BNQ, BNZ, ...
+                let taken = match n & 7 {
+                    0       => true, // BR / SKP
+                    1       => self.q,
+                    2       => self.d == 0,
+                    3       => self.df,
+                    flag    => bus.ef(flag - 3),
+                } != (n >= 8);
+                self.short_branch(bus, taken);
+            },
+            0x4             => { self.d = bus.read(self.r[rn]); self.r[rn] = self.r[rn].wrapping_add(1) }, // LDA
+            0x5             => bus.write(self.r[rn], self.d),                               // STR
+            0x6 if n == 0   => self.r[rx] = self.r[rx].wrapping_add(1),                     // IRX
+            0x6 if n < 8    => { let m = bus.read(self.r[rx]); bus.output(n, m); self.r[rx] = self.r[rx].wrapping_add(1) }, // OUT
+            0x6 if n == 8   => {},                                                          // (CDP1804 prefix)
+            0x6             => { self.d = bus.input(n - 8); bus.write(self.r[rx], self.d) }, // INP
+            0x7             => match n {
+                0x0 | 0x1   => {                                                            // RET, DIS
+                    let t = bus.read(self.r[rx]);
+                    self.r[rx] = self.r[rx].wrapping_add(1);
+                    self.x = t >> 4;
+                    self.p = t & 0xF;
+                    self.ie = n == 0;
+                },
+                0x2         => { self.d = bus.read(self.r[rx]); self.r[rx] = self.r[rx].wrapping_add(1) }, // LDXA
+                0x3         => { bus.write(self.r[rx], self.d); self.r[rx] = self.r[rx].wrapping_sub(1) }, // STXD
+                0x4         => { let m = bus.read(self.r[rx]); self.add(m, self.df) },     // ADC
+                0x5         => { let m = bus.read(self.r[rx]); self.sub(m, self.d, self.df) }, // SDB
+                0x6         => { let df = self.d & 1 != 0; self.d = self.d >> 1 | u8::from(self.df) << 7; self.df = df }, // SHRC
+                0x7         => { let m = bus.read(self.r[rx]); self.sub(self.d, m, self.df) }, // SMB
+                0x8         => bus.write(self.r[rx], self.t),                               // SAV
+                0x9         => {                                                            // MARK
+                    self.t = self.x << 4 | self.p;
+                    bus.write(self.r[2], self.t);
+                    self.x = self.p;
+                    self.r[2] = self.r[2].wrapping_sub(1);
+                },
+                0xA | 0xB   => { self.q = n == 0xB; bus.q(self.q) },                        // REQ, SEQ
+                0xC         => { let m = self.imm(bus); self.add(m, self.df) },             // ADCI
+                0xD         => { let m = self.imm(bus); self.sub(m, self.d, self.df) },     // SDBI
+                0xE         => { let df = self.d & 0x80 != 0; self.d = self.d << 1 | u8::from(self.df); self.df = df }, // SHLC
+                _           => { let m = self.imm(bus); self.sub(self.d, m, self.df) },     // SMBI
+            },
+            0x8             => self.d = self.r[rn] as u8,                                   // GLO
+            0x9             => self.d = (self.r[rn] >> 8) as u8,                            // GHI
+            0xA             => self.r[rn] = self.r[rn] & 0xFF00 | u16::from(self.d),       // PLO
+            0xB             => self.r[rn] = self.r[rn] & 0x00FF | u16::from(self.d) << 8,  // PHI
+            0xC             => {                                                            // LBR, ... NOP, LSNQ, ... LSKP, ...
+                let cond = match n & 3 {
+                    0       => true, // LBR / LSKP (NOP, LSIE handled below)
+                    1       => self.q,
+                    2       => self.d == 0,
+                    _       => self.df,
+                };
+                let pc = usize::from(self.p);
+                match n {
+                    0x4                     => {},                                          // NOP
+                    0xC                     => if self.ie { self.r[pc] = self.r[pc].wrapping_add(2) }, // LSIE
+                    _ if n & 4 == 0         => if cond != (n >= 8) {                        // long branches
+                        let hi = self.imm(bus);
+                        let lo = bus.read(self.r[pc]);
+                        self.r[pc] = u16::from_be_bytes([hi, lo]);
+                    } else {
+                        self.r[pc] = self.r[pc].wrapping_add(2);
+                    },
+                    _                       => if cond != (n < 8) {                         // long skips
+                        self.r[pc] = self.r[pc].wrapping_add(2);
+                    },
+                }
+                return 3
+            },
+            0xD             => self.p = n,                                                  // SEP
+            0xE             => self.x = n,                                                  // SEX
+            0xF if n & 7 == 6 => {                                                          // SHR, SHL
+                if n < 8 { self.df = self.d & 1 != 0; self.d >>= 1 } else { self.df = self.d & 0x80 != 0; self.d <<= 1 }
+            },
+            _               => {
+                let m = if n < 8 { bus.read(self.r[rx]) } else { self.imm(bus) };           // LDX, OR, AND, ... / LDI, ORI, ANI, ...
+                match n & 7 {
+                    0       => self.d = m,
+                    1       => self.d |= m,
+                    2       => self.d &= m,
+                    3       => self.d ^= m,
+                    4       => self.add(m, false),
+                    5       => self.sub(m, self.d, true),
+                    _       => self.sub(self.d, m, true),
+                }
+            },
+        }
+        2
+    }
+
+    /// Read the byte at the program counter, and increment it.
+    fn imm(&mut self, bus: &mut impl Bus) -> u8 {
+        let pc = &mut self.r[usize::from(self.p)];
+        let value = bus.read(*pc);
+        *pc = pc.wrapping_add(1);
+        value
+    }
+
+    fn short_branch(&mut self, bus: &mut impl Bus, taken: bool) {
+        let pc = usize::from(self.p);
+        if taken {
+            let lo = bus.read(self.r[pc]);
+            self.r[pc] = self.r[pc] & 0xFF00 | u16::from(lo);
+        } else {
+            self.r[pc] = self.r[pc].wrapping_add(1);
+        }
+    }
+
+    fn add(&mut self, m: u8, carry: bool) {
+        let sum = u16::from(self.d) + u16::from(m) + u16::from(carry);
+        self.d = sum as u8;
+        self.df = sum > 0xFF;
+    }
+
+    /// `D = a - b`, borrowing if `!no_borrow`.  `DF` is set if there was no borrow out.
+    fn sub(&mut self, a: u8, b: u8, no_borrow: bool) {
+        let diff = i16::from(a) - i16::from(b) - i16::from(!no_borrow);
+        self.d = diff as u8;
+        self.df = diff >= 0;
+    }
+}
+
+
+
+#[cfg(test)] struct TestBus([u8; 256]);
+#[cfg(test)] impl Bus for TestBus {
+    fn read(&mut self, addr: u16) -> u8 { self.0[usize::from(addr as u8)] }
+    fn write(&mut self, addr: u16, value: u8) { self.0[usize::from(addr as u8)] = value }
+    fn ef(&mut self, flag: u8) -> bool { flag == 3 }
+}
+
+#[test] fn test_cpu() {
+    let mut bus = TestBus([0; 256]);
+    bus.0[..0x2A].copy_from_slice(&[
+        0xF8, 0x80,         // 00: D <- 0x80                        LDI
+        0xA2,               // 02: R2.0 <- D                        PLO R2
+        0xE2,               // 03: X <- 2                           SEX R2
+        0xF8, 0xF0,         // 04: D <- 0xF0                        LDI
+        0x73,               // 06: M(R2--) <- D                     STXD
+        0xF8, 0x20,         // 07: D <- 0x20                        LDI
+        0x60,               // 09: R2++                             IRX
+        0xF4,               // 0A: D, DF <- D + M(R2) = 0x110       ADD
+        0x33, 0x0E,         // 0B: goto 0E if DF                    BDF
+        0x00,               // 0D: halt                             IDL
+        0x7C, 0x01,         // 0E: D <- D + 1 + DF = 0x12           ADCI
+        0xFF, 0x13,         // 10: D <- D - 0x13 = 0xFF, !DF        SMI
+        0xC3, 0x00, 0x00,   // 12: goto 0000 if DF                  LBDF
+        0xFE,               // 15: D <<= 1, DF                      SHL
+        0x3E, 0x00,         // 16: goto 00 unless EF3               BN3
+        0xC6,               // 18: skip 2 if D != 0                 LSNZ
+        0x00, 0x00,         // 19:                                  IDL
+        0x7B,               // 1B: Q <- 1                           SEQ
+        0xF8, 0x1F,         // 1C: D <- 0x1F                        LDI
+        0xB3,               // 1E: R3.1 <- D                        PHI R3
+        0x93,               // 1F: D <- R3.1                        GHI R3
+        0x76,               // 20: D = D >> 1 | DF << 7 = 0x8F      SHRC
+        0x52,               // 21: M(R2) <- D                       STR R2
+        0x79,               // 22: T <- XP; M(R2--) <- T; X <- P    MARK
+        0xF8, 0x20,         // 23: D <- 0x20                        LDI
+        0xD4,               // 25: P <- 4                           SEP R4
+        0x00, 0x00, 0x00, 0x00,
+    ]);
+    let mut cpu = Cpu::new();
+    cpu.reset(&mut bus);
+    let mut cycles = 0;
+    while cpu.p == 0 && !cpu.idle { cycles += cpu.step(&mut bus) }
+    assert!(!cpu.idle, "halted at {:02X}", cpu.pc() - 1);
+    assert_eq!((cpu.d, cpu.df, cpu.q, cpu.t, cpu.x), (0x20, true, true, 0x20, 0));
+    assert_eq!(cpu.r[3], 0x1F00);
+    assert_eq!(cpu.r[2], 0x7F);
+    assert_eq!(bus.0[0x80], 0x20); // T
+    assert_eq!(cycles, 2 * 22 + 3 * 2);
+}
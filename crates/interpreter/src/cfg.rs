@@ -0,0 +1,264 @@
+//! Split ROMs into basic blocks, and build control flow and call graphs, exportable as [Graphviz](https://graphviz.org/) DOT or JSON.
+//!
+//! Built on [`disasm::disassemble`], so the same instructions are reachable:
+//! indirect `BNNN` jumps are followed to their base address, but flagged as [`Edge::Indirect`] since their real targets depend on `V0`.
+
+use crate::*;
+use crate::disasm::{Disassembly, Next};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+
+
+
+/// How control leaves a [`BasicBlock`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)] pub enum Edge {
+    /// Execution continues with the following instruction (including when a skip isn't taken.)
+    Next(Addr),
+
+    /// A skip (`3XNN`, `4XNN`, `5XY0`, `9XY0`, `EX9E`, `EXA1`) skipped the following instruction.
+    Skip(Addr),
+
+    /// `1NNN`
+    Jump(Addr),
+
+    /// `BNNN` jumps to `base` plus a register, so the real target is unresolved.
+    Indirect { base: Addr },
+}
+
+impl Edge {
+    /// The target, if resolved.
+    pub fn to(&self) -> Option<Addr> {
+        match *self {
+            Edge::Next(to) | Edge::Skip(to) | Edge::Jump(to) => Some(to),
+            Edge::Indirect { .. } => None,
+        }
+    }
+}
+
+/// A run of instructions only entered at `start` and only left after the last one.
+///
+/// Blocks without any [`edges`](Self::edges) that don't [`return`](Self::returns) either `exit` (`00FD`) or run into invalid instructions.
+#[derive(Clone, Debug, PartialEq, Eq)] pub struct BasicBlock {
+    pub start:      Addr,
+    /// One past the last byte of the last instruction.
+    pub end:        Addr,
+    /// `2NNN` subroutine calls made by this block, in order.
+    pub calls:      Vec<Addr>,
+    pub edges:      Vec<Edge>,
+    /// `true` if the block ends with `00EE`.
+    pub returns:    bool,
+}
+
+/// The entry point, or a `2NNN` call target, and the blocks reachable from it without following calls.
+#[derive(Clone, Debug, PartialEq, Eq)] pub struct Function {
+    pub entry:      Addr,
+    pub name:       String,
+    pub blocks:     BTreeSet<Addr>,
+    pub calls:      BTreeSet<Addr>,
+    /// `true` if any block returns (`00EE`.)
+    pub returns:    bool,
+    /// `true` if any block ends with an [`Edge::Indirect`] jump.
+    pub indirect:   bool,
+}
+
+/// The result of [`analyze`].
+#[derive(Clone, Debug)] pub struct ControlFlowGraph {
+    pub disassembly:    Disassembly,
+    pub blocks:         BTreeMap<Addr, BasicBlock>,
+    pub functions:      BTreeMap<Addr, Function>,
+}
+
+/// Build the control flow and call graphs of `rom` (loaded at [`Addr::PROGRAM_START_TYPICAL`].)
+pub fn analyze(rom: &[u8], instruction_set: InstructionSet) -> ControlFlowGraph {
+    let d = disasm::disassemble(rom, instruction_set);
+    let after = |addr: Addr| Addr(addr.0.wrapping_add(d.code[&addr].into()));
+
+    let mut leaders = BTreeSet::from([Addr::PROGRAM_START_TYPICAL]);
+    for &addr in d.code.keys() {
+        match d.flow(addr).map(|(_, next)| next) {
+            Some(Next::Goto(to) | Next::Jump0(to))  => leaders.extend([to, after(addr)]),
+            Some(Next::Call(to))                    => { leaders.insert(to); },
+            Some(Next::Skip)                        => leaders.extend([after(addr), d.skip_target(after(addr))]),
+            Some(Next::Return | Next::Exit)         => { leaders.insert(after(addr)); },
+            _                                       => {},
+        }
+    }
+
+    let mut blocks = BTreeMap::new();
+    for &start in leaders.iter().filter(|a| d.code.contains_key(a)) {
+        let mut block = BasicBlock { start, end: start, calls: Vec::new(), edges: Vec::new(), returns: false };
+        let mut pc = start;
+        loop {
+            let next = after(pc);
+            block.end = next;
+            match d.flow(pc).map(|(_, next)| next) {
+                Some(Next::Call(to))    => block.calls.push(to),
+                Some(Next::Goto(to))    => { block.edges.push(Edge::Jump(to)); break },
+                Some(Next::Jump0(base)) => { block.edges.push(Edge::Indirect { base }); break },
+                Some(Next::Skip)        => { block.edges.extend([Edge::Next(next), Edge::Skip(d.skip_target(next))]); break },
+                Some(Next::Return)      => { block.returns = true; break },
+                Some(Next::Exit) | None => break,
+                Some(Next::Continue | Next::Data(_) | Next::Long(_)) => {},
+            }
+            if !d.code.contains_key(&next) { break }
+            if leaders.contains(&next) { block.edges.push(Edge::Next(next)); break }
+            pc = next;
+        }
+        blocks.insert(start, block);
+    }
+
+    let mut functions = BTreeMap::new();
+    let entries = std::iter::once(Addr::PROGRAM_START_TYPICAL).chain(blocks.values().flat_map(|b| b.calls.iter().copied()));
+    for entry in entries.filter(|e| blocks.contains_key(e)).collect::<BTreeSet<_>>() {
+        let name = d.labels.get(&entry).cloned().unwrap_or_else(|| format!("sub_{:03x}", entry.0));
+        let mut f = Function { entry, name, blocks: BTreeSet::new(), calls: BTreeSet::new(), returns: false, indirect: false };
+        let mut pending = vec![entry];
+        while let Some(addr) = pending.pop() {
+            let Some(block) = blocks.get(&addr) else { continue };
+            if !f.blocks.insert(addr) { continue }
+            f.calls.extend(block.calls.iter().copied());
+            f.returns |= block.returns;
+            f.indirect |= block.edges.iter().any(|e| matches!(e, Edge::Indirect { .. }));
+            pending.extend(block.edges.iter().filter_map(Edge::to));
+        }
+        functions.insert(entry, f);
+    }
+
+    ControlFlowGraph { disassembly: d, blocks, functions }
+}
+
+impl ControlFlowGraph {
+    /// Ranges of the ROM that aren't part of any reachable instruction: data, or dead code.
+    pub fn unreachable(&self) -> Vec<MemoryRange> {
+        let start = Addr::PROGRAM_START_TYPICAL.0 as usize;
+        let end = start + self.disassembly.rom.len();
+        let mut ranges = Vec::new();
+        let mut addr = start;
+        for block in self.blocks.values() {
+            let block_start = usize::from(block.start.0);
+            if block_start > addr { ranges.push(MemoryRange::new(Addr(addr as u16), (block_start - addr) as u32)) }
+            addr = addr.max(block.end.0.into());
+        }
+        if end > addr { ranges.push(MemoryRange::new(Addr(addr as u16), (end - addr) as u32)) }
+        ranges
+    }
+
+    /// Write the control flow graph as a Graphviz `digraph`, with one node per [`BasicBlock`] listing its instructions.
+    pub fn write_dot(&self, mut w: impl Write) -> io::Result<()> {
+        writeln!(w, "digraph cfg {{")?;
+        writeln!(w, "    node [shape=box fontname=monospace];")?;
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            if let Some(name) = self.disassembly.labels.get(&block.start) { label += &format!("{name}:\\l") }
+            let mut pc = block.start;
+            while pc < block.end {
+                let Some((text, _)) = self.disassembly.flow(pc) else { break };
+                label += &format!("{:03X}  {}\\l", pc.0, escape(&text));
+                pc = Addr(pc.0 + u16::from(self.disassembly.code[&pc]));
+            }
+            writeln!(w, "    \"{}\" [label=\"{label}\"];", block.start)?;
+            for edge in block.edges.iter() {
+                match *edge {
+                    Edge::Next(to)          => writeln!(w, "    \"{}\" -> \"{to}\";", block.start)?,
+                    Edge::Skip(to)          => writeln!(w, "    \"{}\" -> \"{to}\" [label=skip style=dashed];", block.start)?,
+                    Edge::Jump(to)          => writeln!(w, "    \"{}\" -> \"{to}\" [style=bold];", block.start)?,
+                    Edge::Indirect { base } => {
+                        writeln!(w, "    \"{base} + v0\" [shape=ellipse color=red label=\"{base} + v0\\n(unresolved)\"];")?;
+                        writeln!(w, "    \"{}\" -> \"{base} + v0\" [color=red style=dashed];", block.start)?;
+                    },
+                }
+            }
+        }
+        writeln!(w, "}}")
+    }
+
+    /// Write the call graph as a Graphviz `digraph`, with one node per [`Function`].
+    pub fn write_call_graph_dot(&self, mut w: impl Write) -> io::Result<()> {
+        writeln!(w, "digraph calls {{")?;
+        writeln!(w, "    node [shape=box fontname=monospace];")?;
+        for f in self.functions.values() {
+            let style = if f.indirect { " color=red" } else { "" };
+            writeln!(w, "    \"{}\" [label=\"{}\\n{} blocks\"{style}];", f.entry, escape(&f.name), f.blocks.len())?;
+            for callee in f.calls.iter() { writeln!(w, "    \"{}\" -> \"{callee}\";", f.entry)? }
+        }
+        writeln!(w, "}}")
+    }
+
+    /// Write blocks, functions, and [`unreachable`](Self::unreachable) ranges as JSON.  Addresses are plain numbers.
+    ///
+    /// ```text
+    /// { "blocks": [{ "start": 512, "end": 518, "calls": [], "edges": [{ "kind": "jump", "to": 520 }], "returns": false }, ...],
+    ///   "functions": [{ "entry": 512, "name": "main", "blocks": [512, ...], "calls": [], "returns": false, "indirect": false }, ...],
+    ///   "unreachable": [{ "start": 518, "len": 2 }, ...] }
+    /// ```
+    pub fn write_json(&self, mut w: impl Write) -> io::Result<()> {
+        fn list<T>(items: impl IntoIterator<Item = T>, f: impl Fn(T) -> String) -> String { items.into_iter().map(f).collect::<Vec<_>>().join(", ") }
+
+        writeln!(w, "{{")?;
+        writeln!(w, "  \"blocks\": [")?;
+        for (i, b) in self.blocks.values().enumerate() {
+            let edges = list(&b.edges, |e| match *e {
+                Edge::Next(to)          => format!("{{ \"kind\": \"next\", \"to\": {} }}", to.0),
+                Edge::Skip(to)          => format!("{{ \"kind\": \"skip\", \"to\": {} }}", to.0),
+                Edge::Jump(to)          => format!("{{ \"kind\": \"jump\", \"to\": {} }}", to.0),
+                Edge::Indirect { base } => format!("{{ \"kind\": \"indirect\", \"base\": {}, \"to\": null }}", base.0),
+            });
+            let comma = if i + 1 < self.blocks.len() { "," } else { "" };
+            writeln!(w, "    {{ \"start\": {}, \"end\": {}, \"calls\": [{}], \"edges\": [{edges}], \"returns\": {} }}{comma}", b.start.0, b.end.0, list(&b.calls, |a| a.0.to_string()), b.returns)?;
+        }
+        writeln!(w, "  ],")?;
+        writeln!(w, "  \"functions\": [")?;
+        for (i, f) in self.functions.values().enumerate() {
+            let comma = if i + 1 < self.functions.len() { "," } else { "" };
+            writeln!(w, "    {{ \"entry\": {}, \"name\": \"{}\", \"blocks\": [{}], \"calls\": [{}], \"returns\": {}, \"indirect\": {} }}{comma}",
+                f.entry.0, escape(&f.name), list(&f.blocks, |a| a.0.to_string()), list(&f.calls, |a| a.0.to_string()), f.returns, f.indirect)?;
+        }
+        writeln!(w, "  ],")?;
+        writeln!(w, "  \"unreachable\": [{}]", list(self.unreachable(), |r| format!("{{ \"start\": {}, \"len\": {} }}", r.start.0, r.len)))?;
+        writeln!(w, "}}")
+    }
+}
+
+/// Escape `"` and `\` (the same for DOT and JSON strings.)
+fn escape(s: &str) -> String { s.replace('\\', "\\\\").replace('"', "\\\"") }
+
+#[test] fn test_cfg() {
+    let rom = [
+        0x22, 0x08,             // 200: call 208
+        0x3F, 0x01,             // 202: if vf != 1 then
+        0x12, 0x00,             // 204:     jump 200
+        0x00, 0xFD,             // 206: exit
+        0x60, 0x01,             // 208: v0 := 1
+        0x00, 0xEE,             // 20A: return
+        0xB2, 0x00,             // 20C: jump0 200 (dead code)
+    ];
+    let cfg = analyze(&rom, InstructionSet::SuperChip);
+    assert_eq!(cfg.blocks.keys().copied().collect::<Vec<_>>(), [Addr(0x200), Addr(0x204), Addr(0x206), Addr(0x208)]);
+    assert_eq!(cfg.blocks[&Addr(0x200)], BasicBlock { start: Addr(0x200), end: Addr(0x204), calls: vec![Addr(0x208)], edges: vec![Edge::Next(Addr(0x204)), Edge::Skip(Addr(0x206))], returns: false });
+    assert_eq!(cfg.blocks[&Addr(0x204)].edges, [Edge::Jump(Addr(0x200))]);
+    assert!(cfg.blocks[&Addr(0x206)].edges.is_empty());
+    assert!(cfg.blocks[&Addr(0x208)].returns);
+
+    assert_eq!(cfg.functions.len(), 2);
+    assert_eq!(cfg.functions[&Addr(0x200)].blocks.len(), 3);
+    assert_eq!(cfg.functions[&Addr(0x200)].calls, BTreeSet::from([Addr(0x208)]));
+    assert_eq!(cfg.functions[&Addr(0x208)].name, "sub_208");
+    assert!(cfg.functions[&Addr(0x208)].returns);
+    assert_eq!(cfg.unreachable(), [MemoryRange::new(Addr(0x20C), 2)]);
+
+    let mut dot = Vec::new();
+    cfg.write_dot(&mut dot).unwrap();
+    let dot = String::from_utf8(dot).unwrap();
+    assert!(dot.contains("\"0x200\" [label=\"main:\\l200  sub_208\\l202  if vf != 0x01 then\\l\"];"), "{dot}");
+    assert!(dot.contains("\"0x200\" -> \"0x206\" [label=skip style=dashed];"), "{dot}");
+
+    let mut json = Vec::new();
+    cfg.write_json(&mut json).unwrap();
+    let json = String::from_utf8(json).unwrap();
+    assert!(json.contains("{ \"start\": 512, \"end\": 516, \"calls\": [520], \"edges\": [{ \"kind\": \"next\", \"to\": 516 }, { \"kind\": \"skip\", \"to\": 518 }], \"returns\": false },"), "{json}");
+    assert!(json.contains("\"unreachable\": [{ \"start\": 524, \"len\": 2 }]"), "{json}");
+
+    let cfg = analyze(&[0xB2, 0x02, 0x00, 0xEE], InstructionSet::Chip8);
+    assert_eq!(cfg.blocks[&Addr(0x200)].edges, [Edge::Indirect { base: Addr(0x202) }]);
+    assert!(cfg.functions[&Addr(0x200)].indirect);
+}
@@ -1,313 +1,754 @@
 use crate::*;
+use std::io;
 
 
 
 /// Execution context with methods like [`try_step_single`](Self::try_step_single), [`try_step_many`](Self::try_step_many), etc.
-#[derive(Default)] pub struct Context<S: Syscalls> {
+///
+/// XO-CHIP programs typically need <code>Context&lt;S, [Memory64K]&gt;</code>.
+/// Debuggers, profilers, etc. can watch execution with an [`Observer`] <code>Context&lt;S, M, O&gt;</code>.
+pub struct Context<S: Syscalls, M: Memory = Memory4K, O: Observer = ()> {
     pub registers:  Registers,
-    pub memory:     Memory4K,
+    pub memory:     M,
     pub syscalls:   S,
-    // ...?
+    pub quirks:     Quirks,
+    pub rng:        Rng,
+    pub observer:   O,
+    pub(crate) vblank:          bool, // see Quirks::display_wait
+    pub(crate) hires:           bool,
+    pub(crate) screen_planes:   ScreenBitplanes128x64, // SUPER-CHIP+ draws here instead of memory
+    pub(crate) planes:          u8, // XO-CHIP bitplane selection mask
+    pub(crate) rpl:             [u8; 16], // SUPER-CHIP "RPL user flags" (HP-48 calculator registers)
+    pub(crate) audio_pattern:   [u8; 16], // XO-CHIP
+    pub(crate) pitch:           u8, // XO-CHIP
 }
 
-impl<S: Syscalls> core::fmt::Debug for Context<S> {
+impl<S: Syscalls + Default, M: Memory, O: Observer + Default> Default for Context<S, M, O> {
+    fn default() -> Self { Self::with_syscalls(S::default()) }
+}
+
+impl<S: Syscalls, M: Memory, O: Observer> Context<S, M, O> {
+    /// Create a context around `syscalls` that aren't [`Default`] (or shouldn't be default), such as a [`movie::Recorder`].
+    pub fn with_syscalls(syscalls: S) -> Self where O : Default { Self::with_observer(syscalls, O::default()) }
+
+    /// Create a context around `syscalls` and an `observer` that aren't [`Default`].
+    pub fn with_observer(syscalls: S, observer: O) -> Self {
+        Self {
+            registers:      Default::default(),
+            memory:         Default::default(),
+            syscalls,
+            quirks:         Default::default(),
+            rng:            Default::default(),
+            observer,
+            vblank:         false,
+            hires:          false,
+            screen_planes:  Default::default(),
+            planes:         1,
+            rpl:            [0; 16],
+            audio_pattern:  [0; 16],
+            pitch:          64, // 4000 Hz
+        }
+    }
+
+    /// Set `quirks`, load `rom` at [`Addr::PROGRAM_START_TYPICAL`] and the default fonts, and point the program counter at the ROM.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidData`], leaving the context untouched, if `rom` doesn't fit in memory.
+    pub fn load_rom(&mut self, rom: &[u8], quirks: Quirks) -> io::Result<()> {
+        self.memory.copy_from_slice(Addr::PROGRAM_START_TYPICAL, rom).map_err(|()| io::Error::new(io::ErrorKind::InvalidData, format!("ROM too large ({} bytes)", rom.len())))?;
+        self.memory.copy_from_slice(Addr::TYPICAL_FONTS_START, bytemuck::cast_slice(font::DEFAULT)).expect("failed to copy font into memory");
+        self.memory.copy_from_slice(Addr::TYPICAL_LARGE_FONTS_START, bytemuck::cast_slice(font::LARGE)).expect("failed to copy large font into memory");
+        self.quirks = quirks;
+        self.registers.pc = Addr::PROGRAM_START_TYPICAL;
+        Ok(())
+    }
+}
+
+impl<S: Syscalls, M: Memory, O: Observer> core::fmt::Debug for Context<S, M, O> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { write!(f, "Context {{ ... }}") }
 }
 
-impl<S: Syscalls> Context<S> {
-    pub fn new() -> Self where S : Default { Self::default() }
+impl<S: Syscalls, M: Memory, O: Observer> Context<S, M, O> {
+    pub fn new() -> Self where S : Default, O : Default { Self::default() }
 
     pub fn screen(&mut self) -> &mut ScreenMonochrome64x32 { self.memory.screen_monochrome_64x32_mut() }
 
-    /// Try to run a single [`Op`]/instruction.  Returns `true` if successful.
-    pub fn try_step_single(&mut self) -> bool {
+    /// The SUPER-CHIP screen, used instead of [`screen`](Self::screen) when <code>[quirks](Self::quirks).instruction_set >= [InstructionSet::SuperChip]</code>.
+    pub fn screen_128x64(&mut self) -> &mut ScreenMonochrome128x64 { &mut self.screen_planes.0[0] }
+
+    /// The XO-CHIP screen, of which [`screen_128x64`](Self::screen_128x64) is the first plane.
+    pub fn screen_bitplanes(&mut self) -> &mut ScreenBitplanes128x64 { &mut self.screen_planes }
+
+    /// `true` if SUPER-CHIP's 128 x 64 high resolution mode is enabled (`00FF`), `false` for 64 x 32 low resolution mode (`00FE`).
+    pub fn is_hires(&self) -> bool { self.hires }
+
+    /// `true` if the program has exited: the program counter is at a SUPER-CHIP `00FD`, which steps as <code>[StepOutcome]::[Exited](StepOutcome::Exited)</code> without advancing.
+    pub fn has_exited(&self) -> bool {
+        let pc = self.registers.pc;
+        self.quirks.instruction_set >= InstructionSet::SuperChip && pc.to_usize() + 2 <= M::SIZE && self.memory.read16(pc) == 0x00FD
+    }
+
+    /// Try to run a single [`Op`]/instruction.
+    ///
+    /// Returns <code>Ok([StepOutcome::Stepped])</code> if an instruction was executed, another [`StepOutcome`] if execution is blocked, or a [`Fault`] if the instruction couldn't be executed.
+    /// Neither blocking nor faulting advances the program counter.
+    pub fn try_step_single(&mut self) -> Result<StepOutcome, Fault> {
+        self.check_range(self.registers.pc, 2)?;
         let op = Op(self.memory.read16(self.registers.pc));
-        return op.decode(&mut Step(self));
+        self.observed(op, |ctx| op.decode(&mut Step(ctx)))
+    }
 
-        #[repr(transparent)] struct Step<'a, S: Syscalls>(&'a mut Context<S>);
-        impl<S: Syscalls> Decode for Step<'_, S> {
-            type Result = bool;
+    /// Execute `op` (the instruction at the program counter) with `exec`, reporting it to the [`observer`](Self::observer).
+    #[inline(always)] pub(crate) fn observed(&mut self, op: Op, exec: impl FnOnce(&mut Self) -> Result<StepOutcome, Fault>) -> Result<StepOutcome, Fault> {
+        self.observer.fetch(&self.registers, op);
+        let accesses = if O::ACCESSES { self.accesses() } else { Accesses::default() };
+        let outcome = exec(self).inspect_err(|&fault| self.observer.fault(fault))?;
+        match outcome {
+            StepOutcome::Stepped        => if O::ACCESSES { self.observe_accesses(&accesses) },
+            StepOutcome::AwaitingKey    => self.observer.key_wait(V(Nibble::truncate16(op.0 >> 8))),
+            StepOutcome::AwaitingVBlank => self.observer.vblank_wait(),
+            StepOutcome::Exited         => {},
+        }
+        Ok(outcome)
+    }
 
-            #[inline(always)] fn invalid(&mut self, op: u16) -> Self::Result {
-                panic!("invalid instruction: 0x{op:04x} @ {}", self.0.registers.pc)
-            }
+    fn observe_accesses(&mut self, accesses: &Accesses) {
+        if let Some(range) = accesses.memory_read  { self.observer.memory_read(range) }
+        if let Some(range) = accesses.memory_write { self.observer.memory_write(range) }
+        for v in V::iter().filter(|&v| accesses.writes_v(v)) { self.observer.v_write(v, self.registers[v]) }
+        if accesses.i_write { self.observer.i_write(self.registers.i) }
+    }
 
-            #[inline(always)] fn call_mcs(&mut self, addr: Addr) -> Self::Result {
-                panic!("invalid mcs call: {addr} @ {}", self.0.registers.pc)
-            }
+    /// Try to run `steps` instructions.  Returns the number of instructions actually executed (may be 0), stopping early if execution blocks, exits, or [`Fault`]s.
+    pub fn try_step_many(&mut self, steps: usize) -> Result<usize, Fault> {
+        for step in 0 .. steps {
+            if self.try_step_single()? != StepOutcome::Stepped { return Ok(step) }
+        }
+        Ok(steps)
+    }
 
-            #[inline(always)] fn display_clear(&mut self) -> Self::Result {
-                self.0.screen().clear();
-                self.0.step()
-            }
+    pub fn step_clocks(&mut self) {
+        self.vblank = true;
+        self.registers.delay_timer = self.registers.delay_timer.saturating_sub(1);
+        self.registers.sound_timer = self.registers.sound_timer.saturating_sub(1);
+        self.observer.timer_tick(self.registers.delay_timer, self.registers.sound_timer);
 
-            #[inline(always)] fn flow_return(&mut self) -> Self::Result {
-                self.0.registers.pc = self.0.registers.stack.pop().expect("return without any stack");
-                true
-            }
+        // N.B. by waiting until after the `saturating_sub`s to check timer state, sound will only play if sound_timer >= 2.
+        // This is intentional - as noted by https://github.com/mattmikolay/chip-8/wiki/Mastering-CHIP%E2%80%908#timers :
+        //
+        // "It should be noted that in the COSMAC VIP manual, it was made clear that the minimum value that the timer
+        // will respond to is 02. Thus, setting the timer to a value of 01 would have no audible effect."
+        //
+        // Other impls might have different behavior, but I've chosen to match the COSMAC VIP in this regard.
+        // https://en.wikipedia.org/wiki/COSMAC_VIP
+        //
+        let should_play = self.registers.sound_timer > 0;
+        match (self.registers.sound_playing, should_play) {
+            (true, false)   => { self.syscalls.sound_stop(); self.observer.sound_stop() },
+            (false, true)   => { self.syscalls.sound_play(); self.observer.sound_start() },
+            _               => {},
+        }
+        self.registers.sound_playing = should_play;
 
-            #[inline(always)] fn flow_goto(&mut self, addr: Addr) -> Self::Result {
-                self.0.registers.pc = addr;
-                true
-            }
+        if self.quirks.instruction_set >= InstructionSet::XoChip {
+            self.syscalls.render_bitplanes(&self.screen_planes);
+        } else if self.quirks.instruction_set >= InstructionSet::SuperChip {
+            self.syscalls.render_128x64(&self.screen_planes.0[0]);
+        } else {
+            let screen = *self.screen(); // XXX: extra 256-byte memcpy
+            self.syscalls.render(&screen);
+        }
+    }
 
-            #[inline(always)] fn flow_call(&mut self, addr: Addr) -> Self::Result {
-                self.0.registers.stack.push(Addr(self.0.registers.pc.0 + 2));
-                self.0.registers.pc = addr;
-                true
-            }
+    /// Draw a `width` x `height` sprite from `I` at (`VX`, `VY`) - `DXYN` / `DXY0`.
+    ///
+    /// XO-CHIP draws to each selected plane in turn, with each plane's sprite data following the previous plane's.
+    fn draw(&mut self, vx: V, vy: V, width: usize, height: usize) -> Result<StepOutcome, Fault> {
+        // ??? "High Res":  64 x 64 pixel screen
+        // https://tobiasvl.github.io/blog/write-a-chip-8-emulator/#dxyn-display
 
-            #[inline(always)] fn skip_if_v_eq_c(&mut self, v: V, c: u8) -> Self::Result {
-                self.0.step_skip_if(self.0.registers[v] == c)
-            }
+        if self.quirks.display_wait {
+            if !self.vblank { return Ok(StepOutcome::AwaitingVBlank) }
+            self.vblank = false;
+        }
 
-            #[inline(always)] fn skip_if_v_ne_c(&mut self, v: V, c: u8) -> Self::Result {
-                self.0.step_skip_if(self.0.registers[v] != c)
-            }
+        let bytes = width / 8 * height;
+        let mut sprite = [0u8; 64];
+        let sprite = &mut sprite[..bytes * self.planes.count_ones().max(1) as usize];
+        self.check_range(self.registers.i, sprite.len())?;
+        for (offset, b) in sprite.iter_mut().enumerate() {
+            *b = self.memory.read(Addr(self.registers.i.0.wrapping_add(offset as u16)));
+        }
 
-            #[inline(always)] fn skip_if_v_eq_v(&mut self, vx: V, vy: V) -> Self::Result {
-                self.0.step_skip_if(self.0.registers[vx] == self.0.registers[vy])
-            }
+        let overlap = if self.quirks.instruction_set >= InstructionSet::SuperChip {
+            let (scale, w, h) = if self.hires { (1, 128, 64) } else { (2, 64, 32) };
+            let x = usize::from(self.registers[vx]) % w * scale;
+            let y = usize::from(self.registers[vy]) % h * scale;
+            let wrap = !self.quirks.clip_sprites;
+            let mut overlap = false;
+            for (plane, sprite) in self.screen_planes.planes_mut(self.planes).zip(sprite.chunks(bytes)) {
+                let rows = sprite.chunks(width / 8).map(|row| match *row {
+                    [l, r]  => u16::from_be_bytes([l, r]),
+                    [l]     => u16::from(l) << 8,
+                    _       => 0,
+                });
+                overlap |= plane.draw_sprite_scaled(x, y, width, rows, scale, wrap);
+            }
+            overlap
+        } else {
+            let x = self.registers[vx] & 0x3F; // % 64 (screen width)
+            let y = self.registers[vy] & 0x1F; // % 32 (screen height)
+            if self.quirks.clip_sprites {
+                self.screen().draw_sprite(x.into(), y.into(), sprite)
+            } else {
+                self.screen().draw_sprite_wrapping(x.into(), y.into(), sprite)
+            }
+        };
+        self.observer.draw(self.registers[vx], self.registers[vy], width, height, overlap);
+        self.registers[VF] = overlap.into();
+        self.step()
+    }
 
-            #[inline(always)] fn set_v_c(&mut self, vx: V, c: u8) -> Self::Result {
-                self.0.registers[vx] = c;
-                self.0.step()
-            }
+    /// Return addresses of the current call stack, outermost call first.
+    pub fn call_stack(&self) -> impl Iterator<Item = Addr> + '_ {
+        (0 .. self.registers.sp).map(|i| self.stack_entry(i))
+    }
 
-            #[inline(always)] fn add_v_c(&mut self, vx: V, c: u8) -> Self::Result {
-                self.0.registers[vx] = self.0.registers[vx].wrapping_add(c);
-                self.0.step()
-            }
+    /// Maximum call depth: <code>[Quirks]::stack_depth</code>, limited by available storage.
+    pub(crate) fn stack_capacity(&self) -> u8 {
+        let storage = if self.quirks.stack_in_memory { STACK_MEMORY_ENTRIES } else { self.registers.stack.len() as u8 };
+        self.quirks.stack_depth.min(storage)
+    }
 
-            #[inline(always)] fn set_v_v(&mut self, vx: V, vy: V) -> Self::Result {
-                self.0.registers[vx] = self.0.registers[vy];
-                self.0.step()
-            }
+    fn stack_entry(&self, i: u8) -> Addr {
+        if self.quirks.stack_in_memory {
+            Addr(self.memory.read16(Addr(Addr::SYSTEM_STACK_ETC_START.0 + 2 * u16::from(i))))
+        } else {
+            self.registers.stack.get(usize::from(i)).copied().unwrap_or_default()
+        }
+    }
 
-            #[inline(always)] fn bitor_v_v(&mut self, vx: V, vy: V) -> Self::Result {
-                self.0.registers[vx] |= self.0.registers[vy];
-                self.0.step()
-            }
+    fn push_return(&mut self, addr: Addr) -> Result<(), Fault> {
+        let i = self.registers.sp;
+        if i >= self.stack_capacity() { return Err(self.fault(FaultKind::StackOverflow)) }
+        if self.quirks.stack_in_memory {
+            let entry = Addr(Addr::SYSTEM_STACK_ETC_START.0 + 2 * u16::from(i));
+            let _ = self.memory.copy_from_slice(entry, &addr.0.to_be_bytes()); // STACK_MEMORY_ENTRIES fits in any Memory
+        } else {
+            self.registers.stack[usize::from(i)] = addr;
+        }
+        self.registers.sp = i + 1;
+        Ok(())
+    }
 
-            #[inline(always)] fn bitand_v_v(&mut self, vx: V, vy: V) -> Self::Result {
-                self.0.registers[vx] &= self.0.registers[vy];
-                self.0.step()
-            }
+    fn pop_return(&mut self) -> Result<Addr, Fault> {
+        let Some(i) = self.registers.sp.checked_sub(1) else { return Err(self.fault(FaultKind::StackUnderflow)) };
+        let addr = self.stack_entry(i);
+        self.registers.sp = i;
+        Ok(addr)
+    }
 
-            #[inline(always)] fn bitxor_v_v(&mut self, vx: V, vy: V) -> Self::Result {
-                self.0.registers[vx] ^= self.0.registers[vy];
-                self.0.step()
-            }
+    /// A [`Fault`] for the instruction at the current program counter.
+    pub(crate) fn fault(&self, kind: FaultKind) -> Fault { Fault { kind, pc: self.registers.pc, op: Op(self.memory.read16(self.registers.pc)) } }
 
-            // on Vx vs VF write order:
-            //
-            // > Note that all these instructions overwrite variable VF.
-            // > This is used to show the status of the carry bit, which is copied into the least significant bit of VF.
-            // > All other bits of VF will be set to 0.
-            // > This means, if you use VF as the VX argument, the result will be overwritten by the flag status.
-            //
-            // https://laurencescotford.com/chip-8-on-the-cosmac-vip-arithmetic-and-logic-instructions/
-
-            #[inline(always)] fn add_v_v(&mut self, vx: V, vy: V) -> Self::Result {
-                let (x, y) = (self.0.registers[vx], self.0.registers[vy]);
-                self.0.registers[vx] = x.wrapping_add(y);
-                self.0.registers[VF] = x.checked_add(y).is_none().into(); // carry
-                self.0.step()
-            }
+    /// Ensure `len` bytes starting at `addr` are within memory.
+    pub(crate) fn check_range(&self, addr: Addr, len: usize) -> Result<(), Fault> {
+        if addr.to_usize() + len <= M::SIZE { Ok(()) } else { Err(self.fault(FaultKind::MemoryOutOfRange(addr))) }
+    }
 
-            #[inline(always)] fn sub_v_v(&mut self, vx: V, vy: V) -> Self::Result {
-                let (x, y) = (self.0.registers[vx], self.0.registers[vy]);
-                self.0.registers[vx] = x.wrapping_sub(y);
-                self.0.registers[VF] = x.checked_sub(y).is_none().into(); // borrow
-                self.0.step()
-            }
+    #[inline] fn advance(&mut self, n: u16) -> Result<StepOutcome, Fault> { self.registers.pc.0 = self.registers.pc.0.wrapping_add(n); Ok(StepOutcome::Stepped) }
+    fn step(&mut self) -> Result<StepOutcome, Fault> { self.advance(2) }
+    fn step_skip_if(&mut self, skip: bool) -> Result<StepOutcome, Fault> {
+        if !skip { return self.step() }
+        let xo_long = self.quirks.instruction_set >= InstructionSet::XoChip && self.memory.read16(Addr(self.registers.pc.0.wrapping_add(2))) == 0xF000;
+        self.advance(if xo_long { 6 } else { 4 }) // skip over all of `F000 NNNN`
+    }
+}
 
-            #[inline(always)] fn shr1_v(&mut self, vx: V, vy: V) -> Self::Result {
-                // XXX: behavior depends on machine!
-                // COSMAC VIP:  src = VY (current behavior)
-                // CHIP-48:     src = VX
-                // SUPER-CHIP:  src = VX
-                // https://tobiasvl.github.io/blog/write-a-chip-8-emulator/#8xy6-and-8xye-shift
-
-                let src = self.0.registers[vy];
-                self.0.registers[vx] = src >> 1;
-                self.0.registers[VF] = src & 0x01; // discarded bit
-                self.0.step()
-            }
+/// Executes a decoded instruction against a [`Context`] (see [`Context::try_step_single`].)
+#[repr(transparent)] pub(crate) struct Step<'a, S: Syscalls, M: Memory, O: Observer>(pub(crate) &'a mut Context<S, M, O>);
+impl<S: Syscalls, M: Memory, O: Observer> Step<'_, S, M, O> {
+    fn schip(&self) -> bool { self.0.quirks.instruction_set >= InstructionSet::SuperChip }
+    fn xo(&self) -> bool { self.0.quirks.instruction_set >= InstructionSet::XoChip }
+
+    /// Scroll the selected planes by `n` pixels (XO-CHIP scrolls low resolution mode by *logical* pixels.)
+    fn scroll(&mut self, n: usize, scroll: impl Fn(&mut ScreenMonochrome128x64, usize)) -> Result<StepOutcome, Fault> {
+        let n = if self.xo() && !self.0.hires { 2*n } else { n };
+        for plane in self.0.screen_planes.planes_mut(self.0.planes) { scroll(plane, n) }
+        self.0.step()
+    }
+}
+impl<S: Syscalls, M: Memory, O: Observer> Decode for Step<'_, S, M, O> {
+    type Result = Result<StepOutcome, Fault>;
 
-            #[inline(always)] fn sub_v_v_alt(&mut self, vx: V, vy: V) -> Self::Result {
-                let (x, y) = (self.0.registers[vx], self.0.registers[vy]);
-                self.0.registers[vx] = y.wrapping_sub(x);
-                self.0.registers[VF] = y.checked_sub(x).is_none().into(); // borrow
-                self.0.step()
-            }
+    #[inline(always)] fn invalid(&mut self, _op: u16) -> Self::Result {
+        Err(self.0.fault(FaultKind::InvalidOpcode))
+    }
 
-            #[inline(always)] fn shl1_v(&mut self, vx: V, vy: V) -> Self::Result {
-                // XXX: behavior depends on machine!
-                // COSMAC VIP:  src = VY (current behavior)
-                // CHIP-48:     src = VX
-                // SUPER-CHIP:  src = VX
-                // https://tobiasvl.github.io/blog/write-a-chip-8-emulator/#8xy6-and-8xye-shift
-
-                let src = self.0.registers[vy];
-                self.0.registers[vx] = src << 1;
-                self.0.registers[VF] = src >> 7; // discarded bit
-                self.0.step()
-            }
+    #[inline(always)] fn call_mcs(&mut self, addr: Addr) -> Self::Result {
+        if self.0.quirks.machine_code { return self.0.call_machine_code(addr) }
+        Err(self.0.fault(FaultKind::MachineCodeCall))
+    }
 
-            #[inline(always)] fn skip_if_v_ne_v(&mut self, vx: V, vy: V) -> Self::Result {
-                self.0.step_skip_if(self.0.registers[vx] != self.0.registers[vy])
-            }
+    #[inline(always)] fn display_clear(&mut self) -> Self::Result {
+        if self.schip() {
+            self.0.screen_planes.planes_mut(self.0.planes).for_each(|plane| plane.clear());
+        } else {
+            self.0.screen().clear();
+        }
+        self.0.step()
+    }
 
-            #[inline(always)] fn set_i_c(&mut self, c: Addr) -> Self::Result {
-                self.0.registers.i = c;
-                self.0.step()
-            }
+    #[inline(always)] fn flow_return(&mut self) -> Self::Result {
+        self.0.registers.pc = self.0.pop_return()?;
+        Ok(StepOutcome::Stepped)
+    }
 
-            #[inline(always)] fn set_pc_v0_plus_c(&mut self, _v0: (), c: Addr) -> Self::Result {
-                // XXX: behavior depends on machine!
-                // COSMAC VIP:  pc = V0 + NNN   (current behavior)
-                // CHIP-48:     pc = VX + NNN
-                // SUPER-CHIP:  pc = VX + NNN
-                // https://tobiasvl.github.io/blog/write-a-chip-8-emulator/#bnnn-jump-with-offset
+    #[inline(always)] fn flow_goto(&mut self, addr: Addr) -> Self::Result {
+        self.0.registers.pc = addr;
+        Ok(StepOutcome::Stepped)
+    }
 
-                // XXX: overflow?
-                self.0.registers.pc = Addr((u16::from(self.0.registers[V0]) + c.0) & 0xFFF);
-                true
-            }
+    #[inline(always)] fn flow_call(&mut self, addr: Addr) -> Self::Result {
+        self.0.push_return(Addr(self.0.registers.pc.0.wrapping_add(2)))?;
+        self.0.registers.pc = addr;
+        Ok(StepOutcome::Stepped)
+    }
 
-            #[inline(always)] fn set_v_rand_mask(&mut self, v: V, mask: u8) -> Self::Result {
-                self.0.registers[v] = self.0.syscalls.rand() & mask;
-                self.0.step()
-            }
+    #[inline(always)] fn skip_if_v_eq_c(&mut self, v: V, c: u8) -> Self::Result {
+        self.0.step_skip_if(self.0.registers[v] == c)
+    }
 
-            #[inline(always)] fn draw_x_y_h(&mut self, vx: V, vy: V, h: Nibble) -> Self::Result {
-                // XXX: behavior depends on machine!
-                // COSMAC VIP:      64 x 32 pixel screen, x/y wrap around, overflow clips (current behavior)
-                // ??? "High Res":  64 x 64 pixel screen
-                // https://tobiasvl.github.io/blog/write-a-chip-8-emulator/#dxyn-display
-
-                let x = self.0.registers[vx] & 0x3F; // % 64 (screen width)
-                let y = self.0.registers[vy] & 0x1F; // % 32 (screen height)
-                let h = h.to_usize();
-                let mut sprite = [0u8; 16];
-                let sprite = &mut sprite[..h];
-                sprite.copy_from_slice(&self.0.memory.as_bytes_ref()[self.0.registers.i.to_usize()..][..h]);
-                let overlap = self.0.screen().draw_sprite(x.into(), y.into(), sprite);
-                self.0.registers[VF] = overlap.into();
-                self.0.step()
-            }
+    #[inline(always)] fn skip_if_v_ne_c(&mut self, v: V, c: u8) -> Self::Result {
+        self.0.step_skip_if(self.0.registers[v] != c)
+    }
 
-            #[inline(always)] fn skip_if_pressed(&mut self, key: V) -> Self::Result {
-                self.0.step_skip_if(self.0.syscalls.is_pressed(self.0.registers[key]))
-            }
+    #[inline(always)] fn skip_if_v_eq_v(&mut self, vx: V, vy: V) -> Self::Result {
+        self.0.step_skip_if(self.0.registers[vx] == self.0.registers[vy])
+    }
 
-            #[inline(always)] fn skip_unless_pressed(&mut self, key: V) -> Self::Result {
-                self.0.step_skip_if(self.0.syscalls.is_pressed(self.0.registers[key]))
-            }
+    #[inline(always)] fn set_v_c(&mut self, vx: V, c: u8) -> Self::Result {
+        self.0.registers[vx] = c;
+        self.0.step()
+    }
 
-            #[inline(always)] fn get_delay_timer(&mut self, v: V) -> Self::Result {
-                self.0.registers[v] = self.0.registers.delay_timer;
-                self.0.step()
-            }
+    #[inline(always)] fn add_v_c(&mut self, vx: V, c: u8) -> Self::Result {
+        self.0.registers[vx] = self.0.registers[vx].wrapping_add(c);
+        self.0.step()
+    }
 
-            #[inline(always)] fn await_key(&mut self, v: V) -> Self::Result {
-                let Some(key) = self.0.syscalls.get_key() else { return false };
-                self.0.registers[v] = key;
-                self.0.step()
-            }
+    #[inline(always)] fn set_v_v(&mut self, vx: V, vy: V) -> Self::Result {
+        self.0.registers[vx] = self.0.registers[vy];
+        self.0.step()
+    }
 
-            #[inline(always)] fn set_delay_timer(&mut self, v: V) -> Self::Result {
-                self.0.registers.delay_timer = self.0.registers[v];
-                self.0.step()
-            }
+    #[inline(always)] fn bitor_v_v(&mut self, vx: V, vy: V) -> Self::Result {
+        self.0.registers[vx] |= self.0.registers[vy];
+        if self.0.quirks.vf_reset { self.0.registers[VF] = 0 }
+        self.0.step()
+    }
 
-            #[inline(always)] fn set_sound_timer(&mut self, v: V) -> Self::Result {
-                self.0.registers.sound_timer = self.0.registers[v];
-                self.0.step()
-            }
+    #[inline(always)] fn bitand_v_v(&mut self, vx: V, vy: V) -> Self::Result {
+        self.0.registers[vx] &= self.0.registers[vy];
+        if self.0.quirks.vf_reset { self.0.registers[VF] = 0 }
+        self.0.step()
+    }
 
-            #[inline(always)] fn add_i_v(&mut self, v: V) -> Self::Result {
-                self.0.registers.i.0 += u16::from(self.0.registers[v]);
-                self.0.step()
-            }
+    #[inline(always)] fn bitxor_v_v(&mut self, vx: V, vy: V) -> Self::Result {
+        self.0.registers[vx] ^= self.0.registers[vy];
+        if self.0.quirks.vf_reset { self.0.registers[VF] = 0 }
+        self.0.step()
+    }
 
-            #[inline(always)] fn set_i_sprite(&mut self, v: V) -> Self::Result {
-                self.0.registers.i.0 = Addr::TYPICAL_FONTS_START.0 + u16::from(self.0.registers[v]) * 5;
-                self.0.step()
-            }
+    // on Vx vs VF write order:
+    //
+    // > Note that all these instructions overwrite variable VF.
+    // > This is used to show the status of the carry bit, which is copied into the least significant bit of VF.
+    // > All other bits of VF will be set to 0.
+    // > This means, if you use VF as the VX argument, the result will be overwritten by the flag status.
+    //
+    // https://laurencescotford.com/chip-8-on-the-cosmac-vip-arithmetic-and-logic-instructions/
+
+    #[inline(always)] fn add_v_v(&mut self, vx: V, vy: V) -> Self::Result {
+        let (x, y) = (self.0.registers[vx], self.0.registers[vy]);
+        self.0.registers[vx] = x.wrapping_add(y);
+        self.0.registers[VF] = x.checked_add(y).is_none().into(); // carry
+        self.0.step()
+    }
 
-            #[inline(always)] fn set_i_bcd(&mut self, v: V) -> Self::Result {
-                self.0.memory.copy_from_slice(self.0.registers.i, &bcd(self.0.registers[v])).map_or(false, |_| self.0.step())
-            }
+    #[inline(always)] fn sub_v_v(&mut self, vx: V, vy: V) -> Self::Result {
+        let (x, y) = (self.0.registers[vx], self.0.registers[vy]);
+        self.0.registers[vx] = x.wrapping_sub(y);
+        self.0.registers[VF] = (x >= y).into(); // NOT borrow
+        self.0.step()
+    }
 
-            #[inline(always)] fn reg_dump(&mut self, v: V) -> Self::Result {
-                // XXX: behavior depends on machine!
-                // COSMAC VIP:  I is incremeneted during dump (current behavior)
-                // CHIP-48:     I remains original value
-                // SUPER-CHIP:  I remains original value
-                // https://tobiasvl.github.io/blog/write-a-chip-8-emulator/#fx55-and-fx65-store-and-load-memory
-
-                for v in V::iter().take(v.0.to_usize()+1) {
-                    self.0.memory.write(Addr(self.0.registers.i.0 + v.0.to_u16()), self.0.registers[v]);
-                }
-                self.0.registers.i.0 += v.0.to_u16()+1;
-                self.0.step()
-            }
+    #[inline(always)] fn shr1_v(&mut self, vx: V, vy: V) -> Self::Result {
+        let src = self.0.registers[if self.0.quirks.shift_vy { vy } else { vx }];
+        self.0.registers[vx] = src >> 1;
+        self.0.registers[VF] = src & 0x01; // discarded bit
+        self.0.step()
+    }
 
-            #[inline(always)] fn reg_load(&mut self, v: V) -> Self::Result {
-                // XXX: behavior depends on machine!
-                // COSMAC VIP:  I is incremeneted during dump (current behavior)
-                // CHIP-48:     I remains original value
-                // SUPER-CHIP:  I remains original value
-                // https://tobiasvl.github.io/blog/write-a-chip-8-emulator/#fx55-and-fx65-store-and-load-memory
-
-                for v in V::iter().take(v.0.to_usize()+1) {
-                    self.0.registers[v] = self.0.memory.read(Addr(self.0.registers.i.0 + v.0.to_u16()));
-                }
-                self.0.registers.i.0 += v.0.to_u16()+1;
-                self.0.step()
-            }
+    #[inline(always)] fn sub_v_v_alt(&mut self, vx: V, vy: V) -> Self::Result {
+        let (x, y) = (self.0.registers[vx], self.0.registers[vy]);
+        self.0.registers[vx] = y.wrapping_sub(x);
+        self.0.registers[VF] = (y >= x).into(); // NOT borrow
+        self.0.step()
+    }
+
+    #[inline(always)] fn shl1_v(&mut self, vx: V, vy: V) -> Self::Result {
+        let src = self.0.registers[if self.0.quirks.shift_vy { vy } else { vx }];
+        self.0.registers[vx] = src << 1;
+        self.0.registers[VF] = src >> 7; // discarded bit
+        self.0.step()
+    }
+
+    #[inline(always)] fn skip_if_v_ne_v(&mut self, vx: V, vy: V) -> Self::Result {
+        self.0.step_skip_if(self.0.registers[vx] != self.0.registers[vy])
+    }
+
+    #[inline(always)] fn set_i_c(&mut self, c: Addr) -> Self::Result {
+        self.0.registers.i = c;
+        self.0.step()
+    }
+
+    #[inline(always)] fn set_pc_v0_plus_c(&mut self, _v0: (), c: Addr) -> Self::Result {
+        let v = if self.0.quirks.jump_vx { V(Nibble::truncate16(c.0 >> 8)) } else { V0 }; // BXNN vs BNNN
+
+        // XXX: overflow?
+        self.0.registers.pc = Addr((u16::from(self.0.registers[v]) + c.0) & 0xFFF);
+        Ok(StepOutcome::Stepped)
+    }
+
+    #[inline(always)] fn set_v_rand_mask(&mut self, v: V, mask: u8) -> Self::Result {
+        let Context { rng, memory, syscalls, .. } = &mut *self.0;
+        self.0.registers[v] = rng.next(memory, syscalls) & mask;
+        self.0.step()
+    }
+
+    #[inline(always)] fn draw_x_y_h(&mut self, vx: V, vy: V, h: Nibble) -> Self::Result {
+        self.0.draw(vx, vy, 8, h.to_usize())
+    }
+
+    #[inline(always)] fn skip_if_pressed(&mut self, key: V) -> Self::Result {
+        self.0.step_skip_if(self.0.syscalls.is_pressed(self.0.registers[key]))
+    }
+
+    #[inline(always)] fn skip_unless_pressed(&mut self, key: V) -> Self::Result {
+        self.0.step_skip_if(!self.0.syscalls.is_pressed(self.0.registers[key]))
+    }
+
+    #[inline(always)] fn get_delay_timer(&mut self, v: V) -> Self::Result {
+        self.0.registers[v] = self.0.registers.delay_timer;
+        self.0.step()
+    }
+
+    #[inline(always)] fn await_key(&mut self, v: V) -> Self::Result {
+        let Some(key) = self.0.syscalls.get_key() else { return Ok(StepOutcome::AwaitingKey) };
+        self.0.registers[v] = key;
+        self.0.step()
+    }
+
+    #[inline(always)] fn set_delay_timer(&mut self, v: V) -> Self::Result {
+        self.0.registers.delay_timer = self.0.registers[v];
+        self.0.step()
+    }
+
+    #[inline(always)] fn set_sound_timer(&mut self, v: V) -> Self::Result {
+        self.0.registers.sound_timer = self.0.registers[v];
+        self.0.step()
+    }
+
+    #[inline(always)] fn add_i_v(&mut self, v: V) -> Self::Result {
+        self.0.registers.i.0 = self.0.registers.i.0.wrapping_add(self.0.registers[v].into());
+        self.0.step()
+    }
+
+    #[inline(always)] fn set_i_sprite(&mut self, v: V) -> Self::Result {
+        self.0.registers.i.0 = Addr::TYPICAL_FONTS_START.0 + u16::from(self.0.registers[v]) * 5;
+        self.0.step()
+    }
+
+    #[inline(always)] fn set_i_bcd(&mut self, v: V) -> Self::Result {
+        self.0.check_range(self.0.registers.i, 3)?;
+        self.0.memory.copy_from_slice(self.0.registers.i, &bcd(self.0.registers[v])).map_err(|()| self.0.fault(FaultKind::MemoryOutOfRange(self.0.registers.i)))?;
+        self.0.step()
+    }
+
+    #[inline(always)] fn reg_dump(&mut self, v: V) -> Self::Result {
+        self.0.check_range(self.0.registers.i, v.0.to_usize()+1)?;
+        for v in V::iter().take(v.0.to_usize()+1) {
+            self.0.memory.write(Addr(self.0.registers.i.0 + v.0.to_u16()), self.0.registers[v]);
         }
+        if self.0.quirks.increment_i { self.0.registers.i.0 = self.0.registers.i.0.wrapping_add(v.0.to_u16()+1) }
+        self.0.step()
     }
 
-    /// Try to run `steps` instructions.  Returns the number of instructions actually executed (may be 0).
-    pub fn try_step_many(&mut self, steps: usize) -> usize {
-        for step in 0 .. steps {
-            if !self.try_step_single() { return step }
+    #[inline(always)] fn reg_load(&mut self, v: V) -> Self::Result {
+        self.0.check_range(self.0.registers.i, v.0.to_usize()+1)?;
+        for v in V::iter().take(v.0.to_usize()+1) {
+            self.0.registers[v] = self.0.memory.read(Addr(self.0.registers.i.0 + v.0.to_u16()));
         }
-        steps
+        if self.0.quirks.increment_i { self.0.registers.i.0 = self.0.registers.i.0.wrapping_add(v.0.to_u16()+1) }
+        self.0.step()
     }
 
-    pub fn step_clocks(&mut self) {
-        self.registers.delay_timer = self.registers.delay_timer.saturating_sub(1);
-        self.registers.sound_timer = self.registers.sound_timer.saturating_sub(1);
+    #[inline(always)] fn scroll_down(&mut self, n: Nibble) -> Self::Result {
+        if !self.schip() { return self.call_mcs(Addr(0x00C0 | n.to_u16())) }
+        self.scroll(n.to_usize(), ScreenMonochrome128x64::scroll_down)
+    }
 
-        // N.B. by waiting until after the `saturating_sub`s to check timer state, sound will only play if sound_timer >= 2.
-        // This is intentional - as noted by https://github.com/mattmikolay/chip-8/wiki/Mastering-CHIP%E2%80%908#timers :
-        //
-        // "It should be noted that in the COSMAC VIP manual, it was made clear that the minimum value that the timer
-        // will respond to is 02. Thus, setting the timer to a value of 01 would have no audible effect."
-        //
-        // Other impls might have different behavior, but I've chosen to match the COSMAC VIP in this regard.
-        // https://en.wikipedia.org/wiki/COSMAC_VIP
-        //
-        let should_play = self.registers.sound_timer > 0;
-        match (self.registers.sound_playing, should_play) {
-            (true, false)   => self.syscalls.sound_stop(),
-            (false, true)   => self.syscalls.sound_play(),
-            _               => {},
+    #[inline(always)] fn scroll_right(&mut self) -> Self::Result {
+        if !self.schip() { return self.call_mcs(Addr(0x00FB)) }
+        self.scroll(4, ScreenMonochrome128x64::scroll_right)
+    }
+
+    #[inline(always)] fn scroll_left(&mut self) -> Self::Result {
+        if !self.schip() { return self.call_mcs(Addr(0x00FC)) }
+        self.scroll(4, ScreenMonochrome128x64::scroll_left)
+    }
+
+    #[inline(always)] fn exit(&mut self) -> Self::Result {
+        if !self.schip() { return self.call_mcs(Addr(0x00FD)) }
+        Ok(StepOutcome::Exited) // without advancing
+    }
+
+    #[inline(always)] fn lores(&mut self) -> Self::Result {
+        if !self.schip() { return self.call_mcs(Addr(0x00FE)) }
+        self.0.hires = false;
+        if self.xo() { self.0.screen_planes = ScreenBitplanes128x64::new() }
+        self.0.step()
+    }
+
+    #[inline(always)] fn hires(&mut self) -> Self::Result {
+        if !self.schip() { return self.call_mcs(Addr(0x00FF)) }
+        self.0.hires = true;
+        if self.xo() { self.0.screen_planes = ScreenBitplanes128x64::new() }
+        self.0.step()
+    }
+
+    #[inline(always)] fn draw_x_y_16x16(&mut self, vx: V, vy: V) -> Self::Result {
+        if !self.schip() { return self.draw_x_y_h(vx, vy, N0) }
+        self.0.draw(vx, vy, 16, 16)
+    }
+
+    #[inline(always)] fn set_i_sprite_large(&mut self, v: V) -> Self::Result {
+        if !self.schip() { return self.invalid(0xF030 | v.0.to_u16() << 8) }
+        self.0.registers.i.0 = Addr::TYPICAL_LARGE_FONTS_START.0 + u16::from(self.0.registers[v] & 0xF) * 10;
+        self.0.step()
+    }
+
+    #[inline(always)] fn rpl_dump(&mut self, v: V) -> Self::Result {
+        if !self.schip() { return self.invalid(0xF075 | v.0.to_u16() << 8) }
+        for v in V::iter().take(v.0.to_usize()+1) { self.0.rpl[v.0.to_usize()] = self.0.registers[v] }
+        self.0.step()
+    }
+
+    #[inline(always)] fn rpl_load(&mut self, v: V) -> Self::Result {
+        if !self.schip() { return self.invalid(0xF085 | v.0.to_u16() << 8) }
+        for v in V::iter().take(v.0.to_usize()+1) { self.0.registers[v] = self.0.rpl[v.0.to_usize()] }
+        self.0.step()
+    }
+
+    #[inline(always)] fn scroll_up(&mut self, n: Nibble) -> Self::Result {
+        if !self.xo() { return self.call_mcs(Addr(0x00D0 | n.to_u16())) }
+        self.scroll(n.to_usize(), ScreenMonochrome128x64::scroll_up)
+    }
+
+    #[inline(always)] fn reg_dump_range(&mut self, vx: V, vy: V) -> Self::Result {
+        if !self.xo() { return self.invalid(0x5002 | vx.0.to_u16() << 8 | vy.0.to_u16() << 4) }
+        self.0.check_range(self.0.registers.i, v_range(vx, vy).count())?;
+        for (offset, v) in v_range(vx, vy).enumerate() {
+            self.0.memory.write(Addr(self.0.registers.i.0.wrapping_add(offset as u16)), self.0.registers[v]);
         }
-        self.registers.sound_playing = should_play;
+        self.0.step()
+    }
+
+    #[inline(always)] fn reg_load_range(&mut self, vx: V, vy: V) -> Self::Result {
+        if !self.xo() { return self.invalid(0x5003 | vx.0.to_u16() << 8 | vy.0.to_u16() << 4) }
+        self.0.check_range(self.0.registers.i, v_range(vx, vy).count())?;
+        for (offset, v) in v_range(vx, vy).enumerate() {
+            self.0.registers[v] = self.0.memory.read(Addr(self.0.registers.i.0.wrapping_add(offset as u16)));
+        }
+        self.0.step()
+    }
+
+    #[inline(always)] fn set_i_long(&mut self) -> Self::Result {
+        if !self.xo() { return self.invalid(0xF000) }
+        self.0.registers.i = Addr(self.0.memory.read16(Addr(self.0.registers.pc.0.wrapping_add(2))));
+        self.0.advance(4)
+    }
+
+    #[inline(always)] fn select_planes(&mut self, n: Nibble) -> Self::Result {
+        if !self.xo() { return self.invalid(0xF001 | n.to_u16() << 8) }
+        self.0.planes = n.to_u8() & 0b11;
+        self.0.step()
+    }
 
-        let screen = self.screen().clone(); // XXX: extra 256-byte memcpy
-        self.syscalls.render(&screen);
+    #[inline(always)] fn audio_pattern(&mut self) -> Self::Result {
+        if !self.xo() { return self.invalid(0xF002) }
+        self.0.check_range(self.0.registers.i, 16)?;
+        for (offset, b) in self.0.audio_pattern.iter_mut().enumerate() {
+            *b = self.0.memory.read(Addr(self.0.registers.i.0.wrapping_add(offset as u16)));
+        }
+        self.0.syscalls.sound_pattern(&self.0.audio_pattern, self.0.pitch);
+        self.0.step()
     }
 
-    #[inline] fn advance(&mut self, n: u16) -> bool { self.registers.pc.0 += n; true }
-    fn step(&mut self) -> bool { self.advance(2) }
-    fn step_skip_if(&mut self, skip: bool) -> bool { self.advance(if skip { 4 } else { 2 }) }
+    #[inline(always)] fn set_pitch(&mut self, v: V) -> Self::Result {
+        if !self.xo() { return self.invalid(0xF03A | v.0.to_u16() << 8) }
+        self.0.pitch = self.0.registers[v];
+        self.0.syscalls.sound_pattern(&self.0.audio_pattern, self.0.pitch);
+        self.0.step()
+    }
+}
+
+/// [`Addr::SYSTEM_STACK_ETC_START`] ..= `0xEFF` holds 96 bytes = 48 16-bit return addresses.
+const STACK_MEMORY_ENTRIES : u8 = 48;
+
+/// `VX ..= VY`, or `VX` down to `VY` if `VY` < `VX` (XO-CHIP `5XY2` / `5XY3`)
+pub(crate) fn v_range(vx: V, vy: V) -> impl Iterator<Item = V> {
+    let (x, y) = (vx.0.to_u8(), vy.0.to_u8());
+    let forward = x <= y;
+    (0 ..= x.abs_diff(y)).map(move |o| V(Nibble::truncate8(if forward { x + o } else { x - o })))
 }
 
-fn bcd(b: u8) -> [u8; 3] { [b / 100, b/10%10, b%10] }
+pub(crate) fn bcd(b: u8) -> [u8; 3] { [b / 100, b/10%10, b%10] }
 #[test] fn test_bcd() { assert_eq!([1, 2, 3], bcd(123)) }
+
+#[test] fn test_sub_flags() {
+    for (op, x, y, expected) in [(0x8015, 5, 3, [2, 1]), (0x8015, 3, 5, [0xFE, 0]), (0x8015, 3, 3, [0, 1]), (0x8017, 3, 5, [2, 1]), (0x8017, 5, 3, [0xFE, 0])] {
+        let mut ctx = Context::<()>::default();
+        ctx.memory.copy_from_slice(Addr(0), &u16::to_be_bytes(op)).unwrap();
+        ctx.registers[V0] = x;
+        ctx.registers[V(N1)] = y;
+        assert_eq!(ctx.try_step_single(), Ok(StepOutcome::Stepped));
+        assert_eq!([ctx.registers[V0], ctx.registers[VF]], expected, "{:?} with V0 = {x}, V1 = {y}", Op(op)); // VF = NOT borrow
+    }
+}
+
+#[test] fn test_quirks_shift() {
+    for (quirks, expected) in [(Quirks::COSMAC_VIP, 0x02), (Quirks::CHIP_48, 0x20)] {
+        let mut ctx = Context::<()> { quirks, ..Context::default() };
+        ctx.memory.copy_from_slice(Addr(0), &[0x80, 0x16]).unwrap(); // V0 <- V1 >> 1
+        ctx.registers[V0] = 0x40;
+        ctx.registers[V(N1)] = 0x04;
+        assert_eq!(ctx.try_step_single(), Ok(StepOutcome::Stepped));
+        assert_eq!(ctx.registers[V0], expected);
+    }
+}
+
+#[test] fn test_quirks_vf_reset() {
+    for (quirks, expected) in [(Quirks::COSMAC_VIP, 0), (Quirks::CHIP_48, 5)] {
+        let mut ctx = Context::<()> { quirks, ..Context::default() };
+        ctx.memory.copy_from_slice(Addr(0), &[0x80, 0x11]).unwrap(); // V0 |= V1
+        ctx.registers[VF] = 5;
+        assert_eq!(ctx.try_step_single(), Ok(StepOutcome::Stepped));
+        assert_eq!(ctx.registers[VF], expected);
+    }
+}
+
+#[test] fn test_quirks_jump_vx() {
+    for (quirks, expected) in [(Quirks::COSMAC_VIP, 0x212), (Quirks::CHIP_48, 0x214)] {
+        let mut ctx = Context::<()> { quirks, ..Context::default() };
+        ctx.memory.copy_from_slice(Addr(0), &[0xB2, 0x10]).unwrap(); // pc <- V0 + 0x210 (or V2 + 0x210)
+        ctx.registers[V0] = 2;
+        ctx.registers[V(N2)] = 4;
+        assert_eq!(ctx.try_step_single(), Ok(StepOutcome::Stepped));
+        assert_eq!(ctx.registers.pc, Addr(expected));
+    }
+}
+
+#[test] fn test_quirks_increment_i() {
+    for (quirks, expected) in [(Quirks::COSMAC_VIP, 0x302), (Quirks::CHIP_48, 0x300)] {
+        let mut ctx = Context::<()> { quirks, ..Context::default() };
+        ctx.memory.copy_from_slice(Addr(0), &[0xF1, 0x55]).unwrap(); // save V0 ..= V1
+        ctx.registers.i = Addr(0x300);
+        assert_eq!(ctx.try_step_single(), Ok(StepOutcome::Stepped));
+        assert_eq!(ctx.registers.i, Addr(expected));
+    }
+}
+
+#[test] fn test_quirks_clip_sprites() {
+    for clip_sprites in [true, false] {
+        let quirks = Quirks { clip_sprites, display_wait: false, ..Quirks::COSMAC_VIP };
+        let mut ctx = Context::<()> { quirks, ..Context::default() };
+        ctx.memory.copy_from_slice(Addr(0), &[0xD0, 0x11, 0xFF]).unwrap(); // draw(V0, V1, 1) from I = 2
+        ctx.registers.i = Addr(2);
+        ctx.registers[V0] = 60;
+        assert_eq!(ctx.try_step_single(), Ok(StepOutcome::Stepped));
+        assert!(ctx.screen().get_pixel(63, 0));
+        assert_eq!(ctx.screen().get_pixel(0, 0), !clip_sprites);
+    }
+}
+
+#[test] fn test_quirks_display_wait() {
+    for (quirks, waits) in [(Quirks::COSMAC_VIP, true), (Quirks::CHIP_48, false), (Quirks::default(), false)] {
+        let mut ctx = Context::<()> { quirks, ..Context::default() };
+        ctx.memory.copy_from_slice(Addr(0), &[0xD0, 0x01, 0xD0, 0x01]).unwrap(); // draw(V0, V0, 1) twice
+        let blocked = if waits { StepOutcome::AwaitingVBlank } else { StepOutcome::Stepped };
+        assert_eq!(ctx.try_step_single(), Ok(blocked), "no vblank yet");
+        ctx.step_clocks();
+        ctx.registers.pc = Addr(0);
+        assert_eq!(ctx.try_step_single(), Ok(StepOutcome::Stepped));
+        assert_eq!(ctx.try_step_single(), Ok(blocked), "one draw per frame");
+    }
+}
+
+#[test] fn test_skip_key() {
+    struct Held(u8);
+    impl Syscalls for Held {
+        fn rand(&self) -> u8 { 0 }
+        fn get_key(&self) -> Option<u8> { Some(self.0) }
+        fn is_pressed(&self, key: u8) -> bool { key == self.0 }
+        fn sound_play(&self) {}
+        fn sound_stop(&self) {}
+        fn render(&self, _screen: &ScreenMonochrome64x32) {}
+    }
+
+    for (op, key, skips) in [(0xE09E, 5, true), (0xE09E, 6, false), (0xE0A1, 5, false), (0xE0A1, 6, true)] {
+        let mut ctx = Context::<Held>::with_syscalls(Held(key));
+        ctx.memory.copy_from_slice(Addr(0x200), &u16::to_be_bytes(op)).unwrap();
+        ctx.registers.pc = Addr(0x200);
+        ctx.registers[V0] = 5;
+        assert_eq!(ctx.try_step_single(), Ok(StepOutcome::Stepped));
+        assert_eq!(ctx.registers.pc, Addr(if skips { 0x204 } else { 0x202 }), "{op:04X} with key {key} held");
+    }
+}
+
+#[test] fn test_xo_long_skip() {
+    let mut ctx = Context::<(), Memory64K> { quirks: Quirks::XO_CHIP, ..Context::default() };
+    ctx.memory.copy_from_slice(Addr(0), &[
+        0x30, 0x00,             // skip_if V0 == 0
+        0xF0, 0x00, 0x12, 0x34, // i <- long 0x1234
+        0xF0, 0x00, 0xAB, 0xCD, // i <- long 0xABCD
+    ]).unwrap();
+    assert_eq!(ctx.try_step_single(), Ok(StepOutcome::Stepped));
+    assert_eq!(ctx.registers.pc, Addr(6));
+    assert_eq!(ctx.try_step_single(), Ok(StepOutcome::Stepped));
+    assert_eq!(ctx.registers.i, Addr(0xABCD));
+}
+
+#[test] fn test_faults() {
+    let mut ctx = Context::<()>::default();
+    ctx.memory.copy_from_slice(Addr(0x200), &[0x00, 0xEE, 0x01, 0x23, 0x5F, 0xF1]).unwrap();
+    for (pc, kind) in [(0x200, FaultKind::StackUnderflow), (0x202, FaultKind::MachineCodeCall), (0x204, FaultKind::InvalidOpcode)] {
+        ctx.registers.pc = Addr(pc);
+        assert_eq!(ctx.try_step_single().map_err(|f| (f.kind, f.pc)), Err((kind, Addr(pc))));
+    }
+
+    ctx.memory.copy_from_slice(Addr(0x200), &[0xF3, 0x55]).unwrap(); // i[0..=3] <- [V0..=V3]
+    ctx.registers.pc = Addr(0x200);
+    ctx.registers.i = Addr(0xFFE);
+    assert_eq!(ctx.try_step_single().map_err(|f| f.kind), Err(FaultKind::MemoryOutOfRange(Addr(0xFFE))));
+}
+
+#[test] fn test_stack_in_memory() {
+    let mut ctx = Context::<()> { quirks: Quirks::COSMAC_VIP, ..Context::default() }; // 12 levels, in memory
+    for i in 0 .. 13 { ctx.memory.copy_from_slice(Addr(0x200 + 2*i), &(0x2202 + 2*i).to_be_bytes()).unwrap() } // call next instruction
+    ctx.registers.pc = Addr(0x200);
+    assert_eq!(ctx.try_step_many(12), Ok(12));
+    assert_eq!(ctx.memory.read16(Addr::SYSTEM_STACK_ETC_START), 0x202);
+    assert_eq!(ctx.call_stack().last(), Some(Addr(0x218)));
+    assert_eq!(ctx.try_step_single().map_err(|f| f.kind), Err(FaultKind::StackOverflow));
+}
@@ -0,0 +1,163 @@
+//! Code coverage: which addresses were executed as instructions, read as data, and written.
+//!
+//! A [`Coverage`] is an [`Observer`], so collect coverage by running a ROM in a <code>[Context]&lt;S, M, [Coverage]&gt;</code>,
+//! then write an [LCOV](https://github.com/linux-test-project/lcov) tracefile keyed to source lines with
+//! [`write_lcov`](Coverage::write_lcov), or an annotated listing of the ROM itself with [`write_listing`](Coverage::write_listing).
+
+use crate::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+
+
+
+/// Addresses executed, read, and written.
+#[derive(Clone, Debug, Default)] pub struct Coverage {
+    /// Executed instructions, by address, with how many times they were executed.
+    /// An instruction is counted once the next one is fetched, or the clocks [step](Context::step_clocks), since until then it might still block.
+    pub executed:   BTreeMap<Addr, u64>,
+    /// Bytes read as data: sprites, `FX65`, in-memory call stacks...
+    pub read:       BTreeSet<Addr>,
+    /// Bytes written: `FX55`, `FX33`, in-memory call stacks, the COSMAC VIP screen...
+    pub written:    BTreeSet<Addr>,
+
+    pending:        Option<Addr>, // fetched, but not known to have executed yet
+}
+
+impl Coverage {
+    pub fn new() -> Self { Self::default() }
+
+    /// Write an LCOV tracefile: a record per file of `source_map`, with a `DA:LINE,HITS` entry per line of instructions.
+    ///
+    /// A line assembled to several instructions (macros, `:unpack`, ...) counts the most executed one.
+    /// Data isn't in [`SourceMap`]s, so only [`write_listing`](Self::write_listing) shows data coverage.
+    pub fn write_lcov(&self, mut w: impl Write, source_map: &SourceMap) -> io::Result<()> {
+        let mut files = BTreeMap::<&str, BTreeMap<u32, u64>>::new();
+        for loc in source_map.entries() {
+            let hits = files.entry(&loc.file).or_default().entry(loc.line).or_default();
+            *hits = (*hits).max(self.executed.get(&loc.addr).copied().unwrap_or(0));
+        }
+        for (file, lines) in files {
+            writeln!(w, "SF:{file}")?;
+            for (line, hits) in lines.iter() { writeln!(w, "DA:{line},{hits}")? }
+            writeln!(w, "LF:{}", lines.len())?;
+            writeln!(w, "LH:{}", lines.values().filter(|&&hits| hits > 0).count())?;
+            writeln!(w, "end_of_record")?;
+        }
+        Ok(())
+    }
+
+    /// Write `rom` (loaded at [`Addr::PROGRAM_START_TYPICAL`]) annotated with coverage, à la `gcov`.
+    ///
+    /// Instructions are found by [`disasm::disassemble`] (plus anything executed), and show how many times they were
+    /// executed, or `#####` if never.  Other bytes are data, and show `R` if read and `W` if written.  Runs of untouched
+    /// data are collapsed.
+    pub fn write_listing(&self, mut w: impl Write, rom: &[u8], instruction_set: InstructionSet) -> io::Result<()> {
+        let disassembly = disasm::disassemble(rom, instruction_set);
+        let start = Addr::PROGRAM_START_TYPICAL.to_usize();
+        let end = start + rom.len();
+        let byte = |addr: usize| rom[addr - start];
+
+        let reached = disassembly.code.keys().filter(|addr| self.executed.contains_key(addr)).count();
+        let data = (start .. end).filter(|&a| !disassembly.is_code(Addr(a as u16)) && !self.executed.contains_key(&Addr(a as u16)));
+        let (data, read, written) = data.fold((0, 0, 0), |(n, r, w), a| (n + 1, r + usize::from(self.read.contains(&Addr(a as u16))), w + usize::from(self.written.contains(&Addr(a as u16)))));
+        writeln!(w, "instructions executed  {reached} of {}", disassembly.code.len())?;
+        writeln!(w, "data bytes read        {read} of {data}")?;
+        writeln!(w, "data bytes written     {written} of {data}")?;
+        writeln!(w)?;
+
+        let mut addr = start;
+        while addr < end {
+            let a = Addr(addr as u16);
+            let len = disassembly.code.get(&a).copied().map(usize::from).or_else(|| self.executed.contains_key(&a).then_some(2));
+            if let Some(len) = len {
+                let len = len.min(end - addr);
+                let bytes = (addr .. addr + len).map(|a| format!("{:02X}", byte(a))).collect::<String>();
+                let hits = self.executed.get(&a).map_or_else(|| "#####".into(), |hits| hits.to_string());
+                let op = if len >= 2 { format!("{:?}", Op(u16::from_be_bytes([byte(addr), byte(addr + 1)]))) } else { String::new() };
+                writeln!(w, "{hits:>12}  {:04X}       {bytes:<8}  {op}", addr)?;
+                addr += len;
+            } else if !self.read.contains(&a) && !self.written.contains(&a) {
+                let run = (addr .. end).take_while(|&a| {
+                    let a = Addr(a as u16);
+                    !disassembly.code.contains_key(&a) && !self.executed.contains_key(&a) && !self.read.contains(&a) && !self.written.contains(&a)
+                }).count();
+                writeln!(w, "{:>12}  {:04X}-{:04X}  {run} bytes untouched", "-", addr, addr + run - 1)?;
+                addr += run;
+            } else {
+                let flags = format!("{}{}", if self.read.contains(&a) { "R" } else { " " }, if self.written.contains(&a) { "W" } else { " " });
+                writeln!(w, "{flags:>12}  {:04X}       {:02X}", addr, byte(addr))?;
+                addr += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn insert(set: &mut BTreeSet<Addr>, range: MemoryRange) {
+        set.extend((0 .. range.len).map(|offset| Addr(range.start.0.wrapping_add(offset as u16))));
+    }
+
+    /// Count the pending instruction, now that it's known not to have blocked.
+    fn commit(&mut self) {
+        let Some(pc) = self.pending.take() else { return };
+        *self.executed.entry(pc).or_default() += 1;
+    }
+}
+
+impl Observer for Coverage {
+    fn fetch(&mut self, registers: &Registers, _op: Op) {
+        self.commit();
+        self.pending = Some(registers.pc);
+    }
+
+    fn memory_read (&mut self, range: MemoryRange) { Self::insert(&mut self.read,    range) }
+    fn memory_write(&mut self, range: MemoryRange) { Self::insert(&mut self.written, range) }
+
+    fn timer_tick(&mut self, _delay: u8, _sound: u8) { self.commit() }
+
+    fn fault(&mut self, _fault: Fault) { self.pending = None }
+    fn key_wait(&mut self, _v: V)   { self.pending = None }
+    fn vblank_wait(&mut self)       { self.pending = None }
+}
+
+#[test] fn test_coverage() {
+    let source = "
+: main
+    i := sprite
+    sprite v0 v0 1
+    if v0 == 1 then jump unused
+    save v0
+    loop again
+
+: unused
+    v1 := key
+
+: sprite
+    0xFF 0x81
+";
+    let asm = asm::assemble(source, "game.8o").unwrap();
+    let mut ctx = Context::<(), Memory4K, Coverage>::default();
+    ctx.load_rom(&asm.rom, Quirks::CHIP_48).unwrap();
+    assert_eq!(ctx.try_step_many(5), Ok(5));
+    assert!(!ctx.observer.executed.contains_key(&Addr(0x20A)), "loop again is pending until the next fetch or tick");
+    ctx.step_clocks();
+
+    let c = &ctx.observer;
+    let sprite = asm.labels["sprite"];
+    assert_eq!(c.executed[&Addr(0x200)], 1);
+    assert_eq!(c.executed[&Addr(0x20A)], 1); // loop again
+    assert!(!c.executed.contains_key(&Addr(0x206))); // jump unused
+    assert!(c.read.contains(&sprite) && !c.read.contains(&Addr(sprite.0 + 1)));
+    assert!(c.written.contains(&sprite), "save v0 overwrote the sprite");
+
+    let mut lcov = Vec::new();
+    c.write_lcov(&mut lcov, &asm.source_map).unwrap();
+    let lcov = String::from_utf8(lcov).unwrap();
+    assert_eq!(lcov, "SF:game.8o\nDA:3,1\nDA:4,1\nDA:5,1\nDA:6,1\nDA:7,1\nDA:10,0\nLF:6\nLH:5\nend_of_record\n");
+
+    let mut listing = Vec::new();
+    c.write_listing(&mut listing, &asm.rom, InstructionSet::Chip8).unwrap();
+    let listing = String::from_utf8(listing).unwrap();
+    assert!(listing.starts_with("instructions executed  5 of 7\ndata bytes read        1 of 2\ndata bytes written     1 of 2\n"), "{listing}");
+    assert!(listing.contains(&format!("       #####  {:04X}       F10A", asm.labels["unused"].0)), "{listing}");
+    assert!(listing.contains(&format!("          RW  {:04X}       FF\n", sprite.0)), "{listing}");
+}
@@ -0,0 +1,176 @@
+use crate::*;
+use std::collections::BTreeSet;
+
+
+
+/// What a [`Watchpoint`] watches.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)] pub enum Watch {
+    Memory(MemoryRange),
+    I,
+    V(V),
+}
+
+/// Stop before an instruction that reads and/or writes a [`Watch`]ed location.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)] pub struct Watchpoint {
+    pub watch:  Watch,
+    pub read:   bool,
+    pub write:  bool,
+}
+
+impl Watchpoint {
+    pub const fn read      (watch: Watch) -> Self { Self { watch, read: true,  write: false } }
+    pub const fn write     (watch: Watch) -> Self { Self { watch, read: false, write: true  } }
+    pub const fn read_write(watch: Watch) -> Self { Self { watch, read: true,  write: true  } }
+
+    fn hit(&self, accesses: &Accesses) -> Option<bool> {
+        let (read, write) = match self.watch {
+            Watch::Memory(range)    => (accesses.reads_memory(&range), accesses.writes_memory(&range)),
+            Watch::I                => (accesses.i_read, accesses.i_write),
+            Watch::V(v)             => (accesses.reads_v(v), accesses.writes_v(v)),
+        };
+        if self.write && write { Some(true) } else if self.read && read { Some(false) } else { None }
+    }
+}
+
+/// Why a [`Debugger`] returned control.  Unless noted otherwise, the program counter points at the *next* instruction to execute.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)] pub enum StopReason {
+    /// The requested step, step over, or step out completed.
+    Stepped,
+
+    /// The program counter reached a breakpoint.  The instruction there hasn't been executed.
+    Breakpoint(Addr),
+
+    /// The instruction at the program counter would access a watched location (and hasn't been executed.)
+    Watchpoint { watchpoint: Watchpoint, write: bool },
+
+    /// Execution is blocked ([`StepOutcome::AwaitingKey`] or [`StepOutcome::AwaitingVBlank`]) until input or [`Context::step_clocks`].
+    Blocked(StepOutcome),
+
+    /// `00FD` (SUPER-CHIP) exited the program.
+    Exited,
+
+    /// The instruction at the program counter [`Fault`]ed.
+    Fault(Fault),
+
+    /// `max_steps` instructions ran without stopping.
+    StepLimit,
+}
+
+/// Breakpoints, watchpoints, and stepping around [`Context::try_step_single`].
+///
+/// Resuming never stops on the instruction it resumes from, so a stopped breakpoint or watchpoint can simply be continued.
+#[derive(Clone, Debug, Default)] pub struct Debugger {
+    breakpoints: BTreeSet<Addr>,
+    watchpoints: Vec<Watchpoint>,
+}
+
+impl Debugger {
+    pub fn new() -> Self { Self::default() }
+
+    /// Returns `false` if there was already a breakpoint at `addr`.
+    pub fn add_breakpoint(&mut self, addr: Addr) -> bool { self.breakpoints.insert(addr) }
+
+    /// Returns `false` if there wasn't a breakpoint at `addr`.
+    pub fn remove_breakpoint(&mut self, addr: Addr) -> bool { self.breakpoints.remove(&addr) }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = Addr> + '_ { self.breakpoints.iter().copied() }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) { if !self.watchpoints.contains(&watchpoint) { self.watchpoints.push(watchpoint) } }
+
+    /// Returns `false` if there wasn't an identical watchpoint.
+    pub fn remove_watchpoint(&mut self, watchpoint: &Watchpoint) -> bool {
+        let len = self.watchpoints.len();
+        self.watchpoints.retain(|w| w != watchpoint);
+        self.watchpoints.len() != len
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] { &self.watchpoints }
+
+    /// Execute instructions until a breakpoint, watchpoint, or one of the other [`StopReason`]s.
+    pub fn run<S: Syscalls, M: Memory, O: Observer>(&self, ctx: &mut Context<S, M, O>, max_steps: usize) -> StopReason {
+        self.run_until(ctx, max_steps, |_| false)
+    }
+
+    /// Execute a single instruction.
+    pub fn step_into<S: Syscalls, M: Memory, O: Observer>(&self, ctx: &mut Context<S, M, O>) -> StopReason {
+        self.run_until(ctx, 1, |_| true)
+    }
+
+    /// Execute a single instruction, or if it's a `2NNN` call, run until that call returns.
+    pub fn step_over<S: Syscalls, M: Memory, O: Observer>(&self, ctx: &mut Context<S, M, O>, max_steps: usize) -> StopReason {
+        let pc = ctx.registers.pc;
+        if ctx.memory.read16(pc) & 0xF000 != 0x2000 { return self.step_into(ctx) }
+        let (ret, depth) = (Addr(pc.0.wrapping_add(2)), ctx.registers.sp);
+        self.run_until(ctx, max_steps, |ctx| ctx.registers.sp <= depth && ctx.registers.pc == ret)
+    }
+
+    /// Run until the current subroutine returns (`00EE`) to its caller.  Outside of any subroutine, this is the same as [`run`](Self::run).
+    pub fn step_out<S: Syscalls, M: Memory, O: Observer>(&self, ctx: &mut Context<S, M, O>, max_steps: usize) -> StopReason {
+        let depth = ctx.registers.sp;
+        self.run_until(ctx, max_steps, |ctx| ctx.registers.sp < depth)
+    }
+
+    /// Why execution would stop before the instruction at the program counter, if it would.
+    pub fn check<S: Syscalls, M: Memory, O: Observer>(&self, ctx: &Context<S, M, O>) -> Option<StopReason> {
+        let pc = ctx.registers.pc;
+        if self.breakpoints.contains(&pc) { return Some(StopReason::Breakpoint(pc)) }
+        if self.watchpoints.is_empty() { return None }
+        let accesses = ctx.accesses();
+        self.watchpoints.iter().find_map(|&watchpoint| watchpoint.hit(&accesses).map(|write| StopReason::Watchpoint { watchpoint, write }))
+    }
+
+    /// Execute instructions until `done` returns `true` after an instruction ([`StopReason::Stepped`]), or another [`StopReason`].
+    pub fn run_until<S: Syscalls, M: Memory, O: Observer>(&self, ctx: &mut Context<S, M, O>, max_steps: usize, mut done: impl FnMut(&Context<S, M, O>) -> bool) -> StopReason {
+        for step in 0 .. max_steps {
+            if step > 0 { if let Some(stop) = self.check(ctx) { return stop } }
+            match ctx.try_step_single() {
+                Ok(StepOutcome::Stepped)    => {},
+                Ok(StepOutcome::Exited)     => return StopReason::Exited,
+                Ok(blocked)                 => return StopReason::Blocked(blocked),
+                Err(fault)                  => return StopReason::Fault(fault),
+            }
+            if done(ctx) { return StopReason::Stepped }
+        }
+        StopReason::StepLimit
+    }
+}
+
+#[test] fn test_debugger() {
+    let mut ctx = Context::<()> { quirks: Quirks::CHIP_48, ..Context::default() };
+    ctx.memory.copy_from_slice(Addr(0x200), &[
+        0x22, 0x08, // 200: call 208
+        0x70, 0x01, // 202: V0 += 1
+        0x12, 0x00, // 204: goto 200
+        0x00, 0x00, // 206
+        0x61, 0x05, // 208: V1 = 5
+        0xA3, 0x00, // 20A: I = 300
+        0xF1, 0x55, // 20C: i[..] <- [V0..=V1]
+        0x00, 0xEE, // 20E: return
+    ]).unwrap();
+    ctx.registers.pc = Addr(0x200);
+
+    let mut dbg = Debugger::new();
+    assert_eq!(dbg.step_over(&mut ctx, 100), StopReason::Stepped);
+    assert_eq!(ctx.registers.pc, Addr(0x202));
+    assert_eq!(ctx.registers.v[1], 5);
+
+    dbg.add_breakpoint(Addr(0x20A));
+    assert_eq!(dbg.run(&mut ctx, 100), StopReason::Breakpoint(Addr(0x20A)));
+    assert_eq!(ctx.call_stack().collect::<Vec<_>>(), [Addr(0x202)]);
+    assert_eq!(dbg.step_out(&mut ctx, 100), StopReason::Stepped);
+    assert_eq!(ctx.registers.pc, Addr(0x202));
+    assert!(dbg.remove_breakpoint(Addr(0x20A)));
+
+    let watch = Watchpoint::write(Watch::Memory(MemoryRange::new(Addr(0x301), 1)));
+    dbg.add_watchpoint(watch);
+    dbg.add_watchpoint(Watchpoint::read(Watch::V(V(N1))));
+    assert_eq!(dbg.run(&mut ctx, 100), StopReason::Watchpoint { watchpoint: watch, write: true });
+    assert_eq!(ctx.registers.pc, Addr(0x20C));
+    assert_eq!(dbg.step_into(&mut ctx), StopReason::Stepped);
+    assert_eq!(ctx.memory.read(Addr(0x301)), 5);
+
+    dbg.remove_watchpoint(&watch);
+    assert_eq!(dbg.run(&mut ctx, 100), StopReason::Watchpoint { watchpoint: Watchpoint::read(Watch::V(V(N1))), write: false });
+    assert_eq!(ctx.registers.pc, Addr(0x20C));
+    assert_eq!(Debugger::new().run(&mut ctx, 3), StopReason::StepLimit);
+}
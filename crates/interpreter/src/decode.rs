@@ -44,6 +44,32 @@ pub trait Decode {
     #[doc = "`FX33`"] fn set_i_bcd              (&mut self, v: V)                       -> Self::Result;
     #[doc = "`FX55`"] fn reg_dump               (&mut self, v: V)                       -> Self::Result;
     #[doc = "`FX65`"] fn reg_load               (&mut self, v: V)                       -> Self::Result;
+
+    // SUPER-CHIP 1.1
+    // http://devernay.free.fr/hacks/chip8/schip.txt
+
+    #[doc = "`00CN`"] fn scroll_down            (&mut self, n: Nibble)                  -> Self::Result { self.call_mcs(Addr(0x00C0 | n.to_u16())) }
+    #[doc = "`00FB`"] fn scroll_right           (&mut self)                             -> Self::Result { self.call_mcs(Addr(0x00FB)) }
+    #[doc = "`00FC`"] fn scroll_left            (&mut self)                             -> Self::Result { self.call_mcs(Addr(0x00FC)) }
+    #[doc = "`00FD`"] fn exit                   (&mut self)                             -> Self::Result { self.call_mcs(Addr(0x00FD)) }
+    #[doc = "`00FE`"] fn lores                  (&mut self)                             -> Self::Result { self.call_mcs(Addr(0x00FE)) }
+    #[doc = "`00FF`"] fn hires                  (&mut self)                             -> Self::Result { self.call_mcs(Addr(0x00FF)) }
+    #[doc = "`DXY0`"] fn draw_x_y_16x16         (&mut self, vx: V, vy: V)               -> Self::Result { self.draw_x_y_h(vx, vy, N0) }
+    #[doc = "`FX30`"] fn set_i_sprite_large     (&mut self, v: V)                       -> Self::Result { self.invalid(0xF030 | v.0.to_u16() << 8) }
+    #[doc = "`FX75`"] fn rpl_dump               (&mut self, v: V)                       -> Self::Result { self.invalid(0xF075 | v.0.to_u16() << 8) }
+    #[doc = "`FX85`"] fn rpl_load               (&mut self, v: V)                       -> Self::Result { self.invalid(0xF085 | v.0.to_u16() << 8) }
+
+    // XO-CHIP
+    // https://johnearnest.github.io/Octo/docs/XO-ChipSpecification.html
+
+    #[doc = "`00DN`"] fn scroll_up              (&mut self, n: Nibble)                  -> Self::Result { self.call_mcs(Addr(0x00D0 | n.to_u16())) }
+    #[doc = "`5XY2`"] fn reg_dump_range         (&mut self, vx: V, vy: V)               -> Self::Result { self.invalid(0x5002 | vx.0.to_u16() << 8 | vy.0.to_u16() << 4) }
+    #[doc = "`5XY3`"] fn reg_load_range         (&mut self, vx: V, vy: V)               -> Self::Result { self.invalid(0x5003 | vx.0.to_u16() << 8 | vy.0.to_u16() << 4) }
+    /// `F000 NNNN` — the only 4-byte instruction: `NNNN` is the *following* word.
+    fn set_i_long                               (&mut self)                             -> Self::Result { self.invalid(0xF000) }
+    #[doc = "`FN01`"] fn select_planes          (&mut self, n: Nibble)                  -> Self::Result { self.invalid(0xF001 | n.to_u16() << 8) }
+    #[doc = "`F002`"] fn audio_pattern          (&mut self)                             -> Self::Result { self.invalid(0xF002) }
+    #[doc = "`FX3A`"] fn set_pitch              (&mut self, v: V)                       -> Self::Result { self.invalid(0xF03A | v.0.to_u16() << 8) }
 }
 
 impl Op {
@@ -60,6 +86,13 @@ impl Op {
             N0 => match op {
                 0x00E0  => decode.display_clear(),
                 0x00EE  => decode.flow_return(),
+                0x00C0 ..= 0x00CF => decode.scroll_down(n(op>>0)),
+                0x00D0 ..= 0x00DF => decode.scroll_up(n(op>>0)),
+                0x00FB  => decode.scroll_right(),
+                0x00FC  => decode.scroll_left(),
+                0x00FD  => decode.exit(),
+                0x00FE  => decode.lores(),
+                0x00FF  => decode.hires(),
                 other   => decode.call_mcs(Addr(other)),
             },
             N1 => decode.flow_goto(addr3(op)),
@@ -67,6 +100,8 @@ impl Op {
             N3 => decode.skip_if_v_eq_c(v(op>>8), b(op>>0)),
             N4 => decode.skip_if_v_ne_c(v(op>>8), b(op>>0)),
             N5 if n(op>>0) == N0 => decode.skip_if_v_eq_v(v(op>>8), v(op>>4)),
+            N5 if n(op>>0) == N2 => decode.reg_dump_range(v(op>>8), v(op>>4)),
+            N5 if n(op>>0) == N3 => decode.reg_load_range(v(op>>8), v(op>>4)),
             N5 => decode.invalid(op),
             N6 => decode.set_v_c(v(op>>8), b(op>>0)),
             N7 => decode.add_v_c(v(op>>8), b(op>>0)),
@@ -98,6 +133,7 @@ impl Op {
             NA => decode.set_i_c(addr3(op)),
             NB => decode.set_pc_v0_plus_c((), addr3(op>>0)),
             NC => decode.set_v_rand_mask(v(op>>8), b(op>>0)),
+            ND if n(op>>0) == N0 => decode.draw_x_y_16x16(v(op>>8), v(op>>4)),
             ND => decode.draw_x_y_h(v(op>>8), v(op>>4), n(op>>0)),
             NE => {
                 let vx = v(op>>8);
@@ -112,15 +148,22 @@ impl Op {
                 let vx = v(op>>8);
                 let c  = b(op>>0);
                 match c {
+                    0x00 if vx == V0 => decode.set_i_long(),
+                    0x01 => decode.select_planes    (vx.0),
+                    0x02 if vx == V0 => decode.audio_pattern(),
                     0x07 => decode.get_delay_timer  (vx),
                     0x0A => decode.await_key        (vx),
                     0x15 => decode.set_delay_timer  (vx),
                     0x18 => decode.set_sound_timer  (vx),
                     0x1E => decode.add_i_v          (vx),
                     0x29 => decode.set_i_sprite     (vx),
+                    0x30 => decode.set_i_sprite_large(vx),
+                    0x3A => decode.set_pitch        (vx),
                     0x33 => decode.set_i_bcd        (vx),
                     0x55 => decode.reg_dump         (vx),
                     0x65 => decode.reg_load         (vx),
+                    0x75 => decode.rpl_dump         (vx),
+                    0x85 => decode.rpl_load         (vx),
                     _    => decode.invalid(op),
                 }
             },
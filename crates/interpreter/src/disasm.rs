@@ -0,0 +1,264 @@
+//! Disassemble ROMs into [Octo](https://github.com/JohnEarnest/Octo) source that [`asm::assemble`](crate::asm::assemble)s back into the same bytes.
+//!
+//! Control flow is followed from [`Addr::PROGRAM_START_TYPICAL`] through jumps, calls, and skips.
+//! Anything never reached (sprites, tables, code only reachable through `jump0`'s `V0` offset...) is treated as data.
+
+use crate::*;
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Write};
+
+
+
+/// The result of [`disassemble`].
+#[derive(Clone, Debug, Default)] pub struct Disassembly {
+    /// The disassembled ROM, loaded at [`Addr::PROGRAM_START_TYPICAL`].
+    pub rom:                Vec<u8>,
+    pub instruction_set:    InstructionSet,
+    /// Reachable instructions, and their length in bytes (2, or 4 for XO-CHIP's `F000 NNNN`.)
+    pub code:               BTreeMap<Addr, u8>,
+    /// Auto-generated labels: `main`, `sub_XXX` (call targets), `label_XXX` (jump targets), and `data_XXX` (`i := ...` targets.)
+    pub labels:             BTreeMap<Addr, String>,
+}
+
+/// Disassemble `rom` (loaded at [`Addr::PROGRAM_START_TYPICAL`]) by following control flow from its entry point.
+pub fn disassemble(rom: &[u8], instruction_set: InstructionSet) -> Disassembly {
+    let mut d = Disassembly { rom: rom.into(), instruction_set, code: BTreeMap::new(), labels: BTreeMap::new() };
+    let mut owned = vec![false; rom.len()];
+    let mut refs = BTreeMap::<Addr, Ref>::new();
+    let mut pending = vec![START];
+    refs.insert(START, Ref::Main);
+
+    while let Some(mut pc) = pending.pop() {
+        loop {
+            if d.code.contains_key(&pc) { break }
+            let Some(op) = d.word(pc) else { break };
+            let Some((_, next)) = d.inst(pc, op, &BTreeMap::new()) else { break };
+            let len = if matches!(next, Next::Long(_)) { 4 } else { 2 };
+            let Some(bytes) = owned.get_mut(d.index(pc) .. d.index(pc) + len).filter(|b| b.iter().all(|o| !o)) else { break };
+            bytes.fill(true);
+            d.code.insert(pc, len as u8);
+            let after = Addr(pc.0.wrapping_add(len as u16));
+
+            let mut reference = |addr: Addr, r: Ref| { let e = refs.entry(addr).or_insert(r); *e = (*e).min(r) };
+            match next {
+                Next::Continue          => {},
+                Next::Return            => break,
+                Next::Exit              => break,
+                Next::Goto(addr)        => { reference(addr, Ref::Label); pending.push(addr); break },
+                Next::Jump0(addr)       => { reference(addr, Ref::Label); pending.push(addr); break },
+                Next::Call(addr)        => { reference(addr, Ref::Sub); pending.push(addr) },
+                Next::Data(addr)        => reference(addr, Ref::Data),
+                Next::Long(addr)        => reference(addr, Ref::Data),
+                Next::Skip              => pending.push(d.skip_target(after)),
+            }
+            pc = after;
+        }
+    }
+
+    for (addr, r) in refs {
+        // only label the start of instructions, or data
+        let labelable = addr >= START && d.index(addr) < rom.len() && (d.code.contains_key(&addr) || !owned[d.index(addr)]);
+        if !labelable { continue }
+        let name = match r {
+            Ref::Main   => "main".into(),
+            Ref::Sub    => format!("sub_{:03x}", addr.0),
+            Ref::Label  => format!("label_{:03x}", addr.0),
+            Ref::Data   => format!("data_{:03x}", addr.0),
+        };
+        d.labels.insert(addr, name);
+    }
+    d
+}
+
+impl Disassembly {
+    pub fn is_code(&self, addr: Addr) -> bool { self.code.range(..=addr).next_back().is_some_and(|(start, len)| addr.0 - start.0 < u16::from(*len)) }
+
+    /// Write Octo source.
+    pub fn write_octo(&self, mut w: impl Write) -> io::Result<()> { write!(w, "{self}") }
+
+    /// The instruction at `addr` as Octo source, and where control flows after it.
+    pub(crate) fn flow(&self, addr: Addr) -> Option<(String, Next)> { self.inst(addr, self.word(addr)?, &self.labels) }
+
+    /// Where a skip lands, given the address `after` the skip instruction.
+    pub(crate) fn skip_target(&self, after: Addr) -> Addr {
+        let long = self.instruction_set >= InstructionSet::XoChip && self.word(after) == Some(0xF000);
+        Addr(after.0.wrapping_add(if long { 4 } else { 2 }))
+    }
+
+    fn index(&self, addr: Addr) -> usize { addr.to_usize().wrapping_sub(START.to_usize()) }
+
+    fn word(&self, addr: Addr) -> Option<u16> {
+        let i = self.index(addr);
+        Some(u16::from_be_bytes([*self.rom.get(i)?, *self.rom.get(i.checked_add(1)?)?]))
+    }
+
+    fn inst(&self, pc: Addr, op: u16, labels: &BTreeMap<Addr, String>) -> Option<(String, Next)> {
+        let long = if op == 0xF000 { Some(Addr(self.word(Addr(pc.0.wrapping_add(2)))?)) } else { None };
+        Op(op).decode(&mut Octo { isa: self.instruction_set, labels, long })
+    }
+}
+
+impl Display for Disassembly {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        let end = START.to_usize() + self.rom.len();
+        let mut addr = START.to_usize();
+        while addr < end {
+            let a = Addr(addr as u16);
+            if let Some(label) = self.labels.get(&a) { writeln!(fmt, ": {label}")? }
+
+            if let Some(&len) = self.code.get(&a) {
+                let (text, _) = self.inst(a, self.word(a).unwrap(), &self.labels).unwrap();
+                writeln!(fmt, "\t{text}")?;
+                addr += usize::from(len);
+            } else {
+                write!(fmt, "\t")?;
+                let mut n = 0;
+                loop {
+                    write!(fmt, "{}0x{:02X}", if n > 0 { " " } else { "" }, self.rom[addr - START.to_usize()])?;
+                    addr += 1;
+                    n += 1;
+                    let a = Addr(addr as u16);
+                    if n == 8 || addr >= end || self.labels.contains_key(&a) || self.code.contains_key(&a) { break }
+                }
+                writeln!(fmt)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+
+
+const START : Addr = Addr::PROGRAM_START_TYPICAL;
+
+/// Why an address was referenced, in label naming priority order.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)] enum Ref { Main, Sub, Label, Data }
+
+pub(crate) enum Next { Continue, Return, Exit, Skip, Goto(Addr), Jump0(Addr), Call(Addr), Data(Addr), Long(Addr) }
+
+struct Octo<'a> { isa: InstructionSet, labels: &'a BTreeMap<Addr, String>, long: Option<Addr> }
+
+impl Octo<'_> {
+    fn schip(&self) -> bool { self.isa >= InstructionSet::SuperChip }
+    fn xo(&self) -> bool { self.isa >= InstructionSet::XoChip }
+    fn addr(&self, addr: Addr) -> String { self.labels.get(&addr).cloned().unwrap_or_else(|| format!("0x{:03X}", addr.0)) }
+    fn ok(text: impl Into<String>) -> Option<(String, Next)> { Some((text.into(), Next::Continue)) }
+}
+
+fn v(v: V) -> String { format!("v{:x}", v.0) }
+
+impl Decode for Octo<'_> {
+    type Result = Option<(String, Next)>;
+
+    fn invalid              (&mut self, _op: u16)                   -> Self::Result { None }
+    fn call_mcs             (&mut self, addr: Addr)                 -> Self::Result { Self::ok(format!("native 0x{:03X}", addr.0)) }
+    fn display_clear        (&mut self)                             -> Self::Result { Self::ok("clear") }
+    fn flow_return          (&mut self)                             -> Self::Result { Some(("return".into(), Next::Return)) }
+    fn flow_goto            (&mut self, addr: Addr)                 -> Self::Result { Some((format!("jump {}", self.addr(addr)), Next::Goto(addr))) }
+    fn flow_call            (&mut self, addr: Addr)                 -> Self::Result {
+        let text = match self.labels.get(&addr) { Some(label) => label.clone(), None => format!(":call 0x{:03X}", addr.0) };
+        Some((text, Next::Call(addr)))
+    }
+    fn skip_if_v_eq_c       (&mut self, vx: V, c: u8)               -> Self::Result { Some((format!("if {} != 0x{c:02X} then", v(vx)), Next::Skip)) }
+    fn skip_if_v_ne_c       (&mut self, vx: V, c: u8)               -> Self::Result { Some((format!("if {} == 0x{c:02X} then", v(vx)), Next::Skip)) }
+    fn skip_if_v_eq_v       (&mut self, vx: V, vy: V)               -> Self::Result { Some((format!("if {} != {} then", v(vx), v(vy)), Next::Skip)) }
+    fn set_v_c              (&mut self, vx: V, c: u8)               -> Self::Result { Self::ok(format!("{} := 0x{c:02X}", v(vx))) }
+    fn add_v_c              (&mut self, vx: V, c: u8)               -> Self::Result { Self::ok(format!("{} += 0x{c:02X}", v(vx))) }
+    fn set_v_v              (&mut self, vx: V, vy: V)               -> Self::Result { Self::ok(format!("{} := {}", v(vx), v(vy))) }
+    fn bitor_v_v            (&mut self, vx: V, vy: V)               -> Self::Result { Self::ok(format!("{} |= {}", v(vx), v(vy))) }
+    fn bitand_v_v           (&mut self, vx: V, vy: V)               -> Self::Result { Self::ok(format!("{} &= {}", v(vx), v(vy))) }
+    fn bitxor_v_v           (&mut self, vx: V, vy: V)               -> Self::Result { Self::ok(format!("{} ^= {}", v(vx), v(vy))) }
+    fn add_v_v              (&mut self, vx: V, vy: V)               -> Self::Result { Self::ok(format!("{} += {}", v(vx), v(vy))) }
+    fn sub_v_v              (&mut self, vx: V, vy: V)               -> Self::Result { Self::ok(format!("{} -= {}", v(vx), v(vy))) }
+    fn shr1_v               (&mut self, vx: V, vy: V)               -> Self::Result { Self::ok(format!("{} >>= {}", v(vx), v(vy))) }
+    fn sub_v_v_alt          (&mut self, vx: V, vy: V)               -> Self::Result { Self::ok(format!("{} =- {}", v(vx), v(vy))) }
+    fn shl1_v               (&mut self, vx: V, vy: V)               -> Self::Result { Self::ok(format!("{} <<= {}", v(vx), v(vy))) }
+    fn skip_if_v_ne_v       (&mut self, vx: V, vy: V)               -> Self::Result { Some((format!("if {} == {} then", v(vx), v(vy)), Next::Skip)) }
+    fn set_i_c              (&mut self, c: Addr)                    -> Self::Result { Some((format!("i := {}", self.addr(c)), Next::Data(c))) }
+    fn set_pc_v0_plus_c     (&mut self, _v0: (), c: Addr)           -> Self::Result { Some((format!("jump0 {}", self.addr(c)), Next::Jump0(c))) }
+    fn set_v_rand_mask      (&mut self, vx: V, mask: u8)            -> Self::Result { Self::ok(format!("{} := random 0x{mask:02X}", v(vx))) }
+    fn draw_x_y_h           (&mut self, vx: V, vy: V, h: Nibble)    -> Self::Result { Self::ok(format!("sprite {} {} {h}", v(vx), v(vy))) }
+    fn skip_if_pressed      (&mut self, key: V)                     -> Self::Result { Some((format!("if {} -key then", v(key)), Next::Skip)) }
+    fn skip_unless_pressed  (&mut self, key: V)                     -> Self::Result { Some((format!("if {} key then", v(key)), Next::Skip)) }
+    fn get_delay_timer      (&mut self, vx: V)                      -> Self::Result { Self::ok(format!("{} := delay", v(vx))) }
+    fn await_key            (&mut self, vx: V)                      -> Self::Result { Self::ok(format!("{} := key", v(vx))) }
+    fn set_delay_timer      (&mut self, vx: V)                      -> Self::Result { Self::ok(format!("delay := {}", v(vx))) }
+    fn set_sound_timer      (&mut self, vx: V)                      -> Self::Result { Self::ok(format!("buzzer := {}", v(vx))) }
+    fn add_i_v              (&mut self, vx: V)                      -> Self::Result { Self::ok(format!("i += {}", v(vx))) }
+    fn set_i_sprite         (&mut self, vx: V)                      -> Self::Result { Self::ok(format!("i := hex {}", v(vx))) }
+    fn set_i_bcd            (&mut self, vx: V)                      -> Self::Result { Self::ok(format!("bcd {}", v(vx))) }
+    fn reg_dump             (&mut self, vx: V)                      -> Self::Result { Self::ok(format!("save {}", v(vx))) }
+    fn reg_load             (&mut self, vx: V)                      -> Self::Result { Self::ok(format!("load {}", v(vx))) }
+
+    fn scroll_down          (&mut self, n: Nibble)                  -> Self::Result { Self::ok(format!("scroll-down {n}")) }
+    fn scroll_right         (&mut self)                             -> Self::Result { Self::ok("scroll-right") }
+    fn scroll_left          (&mut self)                             -> Self::Result { Self::ok("scroll-left") }
+    fn exit                 (&mut self)                             -> Self::Result { Some(("exit".into(), Next::Exit)) }
+    fn lores                (&mut self)                             -> Self::Result { Self::ok("lores") }
+    fn hires                (&mut self)                             -> Self::Result { Self::ok("hires") }
+    fn draw_x_y_16x16       (&mut self, vx: V, vy: V)               -> Self::Result { Self::ok(format!("sprite {} {} 0", v(vx), v(vy))) }
+    fn set_i_sprite_large   (&mut self, vx: V)                      -> Self::Result { self.schip().then(|| (format!("i := bighex {}", v(vx)), Next::Continue)) }
+    fn rpl_dump             (&mut self, vx: V)                      -> Self::Result { self.schip().then(|| (format!("saveflags {}", v(vx)), Next::Continue)) }
+    fn rpl_load             (&mut self, vx: V)                      -> Self::Result { self.schip().then(|| (format!("loadflags {}", v(vx)), Next::Continue)) }
+
+    fn scroll_up            (&mut self, n: Nibble)                  -> Self::Result { self.xo().then(|| (format!("scroll-up {n}"), Next::Continue)) }
+    fn reg_dump_range       (&mut self, vx: V, vy: V)               -> Self::Result { self.xo().then(|| (format!("save {} - {}", v(vx), v(vy)), Next::Continue)) }
+    fn reg_load_range       (&mut self, vx: V, vy: V)               -> Self::Result { self.xo().then(|| (format!("load {} - {}", v(vx), v(vy)), Next::Continue)) }
+    fn set_i_long           (&mut self)                             -> Self::Result {
+        let addr = self.long.filter(|_| self.xo())?;
+        let text = match self.labels.get(&addr) { Some(label) => format!("i := long {label}"), None => format!("i := long 0x{:04X}", addr.0) };
+        Some((text, Next::Long(addr)))
+    }
+    fn select_planes        (&mut self, n: Nibble)                  -> Self::Result { self.xo().then(|| (format!("plane {n}"), Next::Continue)) }
+    fn audio_pattern        (&mut self)                             -> Self::Result { self.xo().then(|| ("audio".into(), Next::Continue)) }
+    fn set_pitch            (&mut self, vx: V)                      -> Self::Result { self.xo().then(|| (format!("pitch := {}", v(vx)), Next::Continue)) }
+}
+
+#[test] fn test_disassemble() {
+    let rom = [
+        0x22, 0x0A,             // 200: call 20A
+        0xA2, 0x12,             // 202: i := 212
+        0x3F, 0x01,             // 204: if vf != 1 then
+        0x61, 0x02,             // 206:     v1 := 2
+        0x12, 0x02,             // 208: jump 202
+        0xD0, 0x15,             // 20A: sprite v0 v1 5
+        0xF0, 0x00, 0x02, 0x13, // 20C: i := long 213
+        0x00, 0xEE,             // 210: return
+        0x00, 0x00, 0xEE,       // 212: data that looks like code
+    ];
+    let d = disassemble(&rom, InstructionSet::XoChip);
+    assert_eq!(d.code.len(), 8);
+    assert!(!d.is_code(Addr(0x212)));
+    assert!(d.is_code(Addr(0x20F)));
+    assert_eq!(d.to_string(), "\
+: main
+\tsub_20a
+: label_202
+\ti := data_212
+\tif vf != 0x01 then
+\tv1 := 0x02
+\tjump label_202
+: sub_20a
+\tsprite v0 v1 5
+\ti := long data_213
+\treturn
+: data_212
+\t0x00
+: data_213
+\t0x00 0xEE
+");
+    assert_eq!(asm::assemble(&d.to_string(), "").unwrap().rom, rom);
+
+    // without XO-CHIP, `F000` is invalid, and `sub_20a` runs into data
+    let d = disassemble(&rom, InstructionSet::Chip8);
+    assert_eq!(d.code.len(), 6);
+    assert!(!d.is_code(Addr(0x20C)));
+    assert_eq!(asm::assemble(&d.to_string(), "").unwrap().rom, rom);
+}
+
+#[test] fn test_disassemble_sierpinski() {
+    let rom = include_bytes!("../../../examples/sierpinski.ch8");
+    let d = disassemble(rom, InstructionSet::SuperChip);
+    assert_eq!(asm::assemble(&d.to_string(), "sierpinski.8o").unwrap().rom, rom);
+}
@@ -0,0 +1,59 @@
+use crate::*;
+use core::fmt::{self, Display, Formatter};
+
+
+
+/// An instruction that [`Context::try_step_single`] refused to execute.  The program counter is left pointing at [`op`](Self::op).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)] pub struct Fault {
+    pub kind:   FaultKind,
+    pub pc:     Addr,
+    pub op:     Op,
+}
+
+/// What kind of [`Fault`] occured.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)] pub enum FaultKind {
+    /// The instruction isn't part of <code>[Quirks]::instruction_set</code>.
+    InvalidOpcode,
+
+    /// `0NNN`: calling native COSMAC VIP (CDP1802) machine code without <code>[Quirks]::machine_code</code>, or machine code that didn't return.
+    MachineCodeCall,
+
+    /// `00EE` without a matching `2NNN`.
+    StackUnderflow,
+
+    /// `2NNN` with a full call stack.
+    StackOverflow,
+
+    /// The instruction tried to access memory starting at this address, but would've run past the end of memory.
+    MemoryOutOfRange(Addr),
+}
+
+/// What happened when [`Context::try_step_single`] didn't [`Fault`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)] pub enum StepOutcome {
+    /// An instruction was executed.
+    Stepped,
+
+    /// `FX0A` is waiting for a key press.  No instruction was executed.
+    AwaitingKey,
+
+    /// A sprite draw is waiting for the next [`Context::step_clocks`] (see [`Quirks::display_wait`].)  No instruction was executed.
+    AwaitingVBlank,
+
+    /// `00FD` (SUPER-CHIP) exited the program.  Further steps will exit again.
+    Exited,
+}
+
+impl Display for Fault {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        let Self { kind, pc, op } = *self;
+        match kind {
+            FaultKind::InvalidOpcode            => write!(fmt, "invalid instruction 0x{:04X} @ {pc}", op.0),
+            FaultKind::MachineCodeCall          => write!(fmt, "unsupported or unreturning machine code call 0x{:04X} @ {pc}", op.0),
+            FaultKind::StackUnderflow           => write!(fmt, "return without any stack (0x{:04X} @ {pc})", op.0),
+            FaultKind::StackOverflow            => write!(fmt, "call stack overflow (0x{:04X} @ {pc})", op.0),
+            FaultKind::MemoryOutOfRange(addr)   => write!(fmt, "memory access @ {addr} out of range (0x{:04X} @ {pc})", op.0),
+        }
+    }
+}
+
+impl std::error::Error for Fault {}
@@ -26,7 +26,7 @@ const XX_X : u8 = 0b1101_0000;
 const XXX_ : u8 = 0b1110_0000;
 const XXXX : u8 = 0b1111_0000;
 
-pub const DEFAULT : &'static [[u8; 5]] = &[
+pub const DEFAULT : &[[u8; 5]] = &[
     [
         _XX_,
         X__X,
@@ -140,3 +140,23 @@ pub const DEFAULT : &'static [[u8; 5]] = &[
         X___,
     ],
 ];
+
+/// SUPER-CHIP's 8x10 hexadecimal digits (`FX30`).  SUPER-CHIP 1.1 itself only shipped `0` ..= `9`.
+pub const LARGE : &[[u8; 10]] = &[
+    [0b0011_1100, 0b0111_1110, 0b1110_0111, 0b1100_0011, 0b1100_0011, 0b1100_0011, 0b1100_0011, 0b1110_0111, 0b0111_1110, 0b0011_1100], // 0
+    [0b0001_1000, 0b0011_1000, 0b0101_1000, 0b0001_1000, 0b0001_1000, 0b0001_1000, 0b0001_1000, 0b0001_1000, 0b0001_1000, 0b0011_1100], // 1
+    [0b0011_1110, 0b0111_1111, 0b1100_0011, 0b0000_0110, 0b0000_1100, 0b0001_1000, 0b0011_0000, 0b0110_0000, 0b1111_1111, 0b1111_1111], // 2
+    [0b0011_1100, 0b0111_1110, 0b1100_0011, 0b0000_0011, 0b0000_1110, 0b0000_1110, 0b0000_0011, 0b1100_0011, 0b0111_1110, 0b0011_1100], // 3
+    [0b0000_0110, 0b0000_1110, 0b0001_1110, 0b0011_0110, 0b0110_0110, 0b1100_0110, 0b1111_1111, 0b1111_1111, 0b0000_0110, 0b0000_0110], // 4
+    [0b1111_1111, 0b1111_1111, 0b1100_0000, 0b1100_0000, 0b1111_1100, 0b1111_1110, 0b0000_0011, 0b1100_0011, 0b0111_1110, 0b0011_1100], // 5
+    [0b0011_1110, 0b0111_1100, 0b1110_0000, 0b1100_0000, 0b1111_1100, 0b1111_1110, 0b1100_0011, 0b1100_0011, 0b0111_1110, 0b0011_1100], // 6
+    [0b1111_1111, 0b1111_1111, 0b0000_0011, 0b0000_0110, 0b0000_1100, 0b0001_1000, 0b0011_0000, 0b0110_0000, 0b0110_0000, 0b0110_0000], // 7
+    [0b0011_1100, 0b0111_1110, 0b1100_0011, 0b1100_0011, 0b0111_1110, 0b0111_1110, 0b1100_0011, 0b1100_0011, 0b0111_1110, 0b0011_1100], // 8
+    [0b0011_1100, 0b0111_1110, 0b1100_0011, 0b1100_0011, 0b0111_1111, 0b0011_1111, 0b0000_0011, 0b0000_0011, 0b0011_1110, 0b0111_1100], // 9
+    [0b0011_1100, 0b0111_1110, 0b1100_0011, 0b1100_0011, 0b1111_1111, 0b1111_1111, 0b1100_0011, 0b1100_0011, 0b1100_0011, 0b1100_0011], // A
+    [0b1111_1100, 0b1111_1110, 0b1100_0011, 0b1100_0011, 0b1111_1110, 0b1111_1110, 0b1100_0011, 0b1100_0011, 0b1111_1110, 0b1111_1100], // B
+    [0b0011_1100, 0b0111_1110, 0b1100_0011, 0b1100_0000, 0b1100_0000, 0b1100_0000, 0b1100_0000, 0b1100_0011, 0b0111_1110, 0b0011_1100], // C
+    [0b1111_1100, 0b1111_1110, 0b1100_0011, 0b1100_0011, 0b1100_0011, 0b1100_0011, 0b1100_0011, 0b1100_0011, 0b1111_1110, 0b1111_1100], // D
+    [0b1111_1111, 0b1111_1111, 0b1100_0000, 0b1100_0000, 0b1111_1100, 0b1111_1100, 0b1100_0000, 0b1100_0000, 0b1111_1111, 0b1111_1111], // E
+    [0b1111_1111, 0b1111_1111, 0b1100_0000, 0b1100_0000, 0b1111_1100, 0b1111_1100, 0b1100_0000, 0b1100_0000, 0b1100_0000, 0b1100_0000], // F
+];
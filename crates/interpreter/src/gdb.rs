@@ -0,0 +1,377 @@
+//! GDB [Remote Serial Protocol](https://sourceware.org/gdb/current/onlinedocs/gdb.html/Remote-Protocol.html) stub.
+//!
+//! ```no_run
+//! # use maulingmonkey_chip8_interpreter::*;
+//! let mut ctx = Context::<()>::default();
+//! // ...load a ROM...
+//! gdb::serve_tcp(&mut ctx, "127.0.0.1:1234").unwrap(); // then: (gdb) target remote :1234
+//! ```
+//!
+//! # Registers
+//!
+//! | Number    | Name      | Bytes | Register                      |
+//! | --------- | --------- | ----- | ----------------------------- |
+//! | 0 ..= 15  | `v0`..    | 1     | `V0` ..= `VF`                 |
+//! | 16        | `i`       | 2     | `I` (little endian)           |
+//! | 17        | `pc`      | 2     | `PC` (little endian)          |
+//! | 18        | `sp`      | 1     | Call stack depth              |
+//! | 19        | `dt`      | 1     | Delay timer                   |
+//! | 20        | `st`      | 1     | Sound timer                   |
+//!
+//! A target description (`target.xml`) with these registers is served via `qXfer:features:read`.
+//!
+//! # Execution
+//!
+//! Continuing runs [`GdbServer::steps_per_frame`] instructions per [`Context::step_clocks`] until a breakpoint
+//! (`Z0`/`Z1`), watchpoint (`Z2`/`Z3`/`Z4`, on memory), fault, exit, or interrupt (`^C`).
+
+use crate::*;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+
+
+const SIGINT  : u8 = 2;
+const SIGILL  : u8 = 4;
+const SIGTRAP : u8 = 5;
+const SIGSEGV : u8 = 11;
+const REGISTERS : usize = 21;
+
+/// A bidirectional byte stream a [`GdbServer`] can be served over.
+pub trait Connection: Read + Write {
+    /// Check, without blocking, whether the client sent an interrupt (`0x03`) while the target was running.
+    fn interrupted(&mut self) -> io::Result<bool>;
+}
+
+impl Connection for TcpStream {
+    fn interrupted(&mut self) -> io::Result<bool> {
+        self.set_nonblocking(true)?;
+        let mut b = [0u8];
+        let peek = self.peek(&mut b);
+        self.set_nonblocking(false)?;
+        match peek {
+            Ok(1) if b[0] == 0x03 => { self.read_exact(&mut b)?; Ok(true) },
+            Ok(_) => Ok(false),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(unix)] impl Connection for std::os::unix::net::UnixStream {
+    fn interrupted(&mut self) -> io::Result<bool> {
+        self.set_nonblocking(true)?;
+        let mut b = [0u8];
+        let read = self.read(&mut b); // UnixStream::peek is unstable: any other byte is a protocol violation mid-run anyways
+        self.set_nonblocking(false)?;
+        match read {
+            Ok(1) => Ok(b[0] == 0x03),
+            Ok(_) => Ok(false),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// Accept a single GDB connection on `addr` and [serve](GdbServer::serve) `ctx` until it detaches or disconnects.
+pub fn serve_tcp<S: Syscalls, M: Memory>(ctx: &mut Context<S, M>, addr: impl ToSocketAddrs) -> io::Result<()> {
+    let (mut stream, _) = TcpListener::bind(addr)?.accept()?;
+    stream.set_nodelay(true)?;
+    GdbServer::new(ctx).serve(&mut stream)
+}
+
+/// Serves a [`Context`] to a GDB (or other RSP speaking) client, using a [`Debugger`] for breakpoints and stepping.
+pub struct GdbServer<'c, S: Syscalls, M: Memory> {
+    ctx:                    &'c mut Context<S, M>,
+    pub debugger:           Debugger,
+
+    /// Instructions to execute between [`Context::step_clocks`] while continuing (default: 8, ≈ 500 Hz at 60 Hz.)
+    pub steps_per_frame:    usize,
+
+    no_ack:                 bool,
+}
+
+impl<'c, S: Syscalls, M: Memory> GdbServer<'c, S, M> {
+    pub fn new(ctx: &'c mut Context<S, M>) -> Self { Self { ctx, debugger: Debugger::new(), steps_per_frame: 8, no_ack: false } }
+
+    /// Handle packets until the client detaches (`D`), kills (`k`), or disconnects.
+    pub fn serve(&mut self, conn: &mut impl Connection) -> io::Result<()> {
+        while let Some(packet) = self.read_packet(conn)? {
+            let reply = match packet.as_str() {
+                "\x03"                  => stop(SIGINT),
+                "D"                     => { self.write_packet(conn, "OK")?; return Ok(()) },
+                "k"                     => return Ok(()),
+                _                       => self.handle(conn, &packet)?,
+            };
+            self.write_packet(conn, &reply)?;
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, conn: &mut impl Connection, packet: &str) -> io::Result<String> {
+        let Some((cmd, args)) = packet.split_at_checked(1) else { return Ok(String::new()) }; // empty, or non-ASCII: unsupported
+        Ok(match cmd {
+            "?"                         => stop(SIGTRAP),
+            "g"                         => (0 .. REGISTERS).map(|r| self.read_register(r)).collect(),
+            "G"                         => self.write_registers(args).map_or_else(|| error(1), |()| ok()),
+            "p"                         => parse_hex(args).and_then(|r| (r < REGISTERS).then(|| self.read_register(r))).unwrap_or_else(|| error(1)),
+            "P"                         => args.split_once('=').and_then(|(r, v)| self.write_register(parse_hex(r)?, &decode_hex(v)?)).map_or_else(|| error(1), |()| ok()),
+            "m"                         => self.read_memory(args).unwrap_or_else(|| error(1)),
+            "M"                         => self.write_memory(args).map_or_else(|| error(1), |()| ok()),
+            "Z" | "z"                   => self.breakpoint(cmd == "Z", args).unwrap_or_else(|| error(1)),
+            "s"                         => self.step(),
+            "c"                         => self.cont(conn)?,
+            "H"                         => ok(),
+            "q" | "Q"                   => self.query(packet),
+            _                           => String::new(), // unsupported
+        })
+    }
+
+    fn query(&mut self, packet: &str) -> String {
+        match packet {
+            "QStartNoAckMode"           => { self.no_ack = true; ok() },
+            "qAttached"                 => "1".into(),
+            "qC"                        => "QC1".into(),
+            "qfThreadInfo"              => "m1".into(),
+            "qsThreadInfo"              => "l".into(),
+            _ if packet.starts_with("qSupported") => "PacketSize=1000;QStartNoAckMode+;qXfer:features:read+;swbreak+;hwbreak+".into(),
+            _ => match packet.strip_prefix("qXfer:features:read:target.xml:").and_then(|range| range.split_once(',')) {
+                Some((offset, len)) => {
+                    let (Some(offset), Some(len)) = (parse_hex(offset), parse_hex(len)) else { return error(1) };
+                    let xml = target_xml();
+                    let chunk = xml.get(offset.min(xml.len()) ..).unwrap_or("");
+                    if chunk.len() > len { format!("m{}", &chunk[..len]) } else { format!("l{chunk}") }
+                },
+                None => String::new(),
+            },
+        }
+    }
+
+    fn read_register(&self, r: usize) -> String {
+        let regs = &self.ctx.registers;
+        match r {
+            0 ..= 15    => encode_hex(&[regs.v[r]]),
+            16          => encode_hex(&regs.i.0.to_le_bytes()),
+            17          => encode_hex(&regs.pc.0.to_le_bytes()),
+            18          => encode_hex(&[regs.sp]),
+            19          => encode_hex(&[regs.delay_timer]),
+            _           => encode_hex(&[regs.sound_timer]),
+        }
+    }
+
+    fn write_register(&mut self, r: usize, value: &[u8]) -> Option<()> {
+        let regs = &mut self.ctx.registers;
+        match (r, value) {
+            (0 ..= 15, &[v])    => regs.v[r] = v,
+            (16, &[l, h])       => regs.i = Addr(u16::from_le_bytes([l, h])),
+            (17, &[l, h])       => regs.pc = Addr(u16::from_le_bytes([l, h])),
+            (18, &[sp])         => { if sp > self.ctx.stack_capacity() { return None } self.ctx.registers.sp = sp },
+            (19, &[dt])         => regs.delay_timer = dt,
+            (20, &[st])         => regs.sound_timer = st,
+            _                   => return None,
+        }
+        Some(())
+    }
+
+    fn write_registers(&mut self, hex: &str) -> Option<()> {
+        let bytes = decode_hex(hex)?;
+        if bytes.len() != 16 + 2 + 2 + 3 { return None }
+        let (v, rest) = bytes.split_at(16);
+        if rest[4] > self.ctx.stack_capacity() { return None }
+        for (r, &b) in v.iter().enumerate() { self.write_register(r, &[b])?; }
+        self.write_register(16, &rest[0..2])?;
+        self.write_register(17, &rest[2..4])?;
+        self.write_register(18, &rest[4..5])?;
+        self.write_register(19, &rest[5..6])?;
+        self.write_register(20, &rest[6..7])
+    }
+
+    fn read_memory(&self, args: &str) -> Option<String> {
+        let range = parse_range(args)?;
+        let mem = self.ctx.memory.as_slice_ref();
+        Some(encode_hex(mem.get(range.start.to_usize() ..)?.get(.. range.len as usize)?))
+    }
+
+    fn write_memory(&mut self, args: &str) -> Option<()> {
+        let (range, data) = args.split_once(':')?;
+        let range = parse_range(range)?;
+        let data = decode_hex(data)?;
+        if data.len() != range.len as usize { return None }
+        self.ctx.memory.copy_from_slice(range.start, &data).ok()
+    }
+
+    fn breakpoint(&mut self, insert: bool, args: &str) -> Option<String> {
+        let mut args = args.split(',');
+        let kind = args.next()?;
+        let range = MemoryRange::new(Addr(u16::try_from(parse_hex(args.next()?)?).ok()?), u32::try_from(parse_hex(args.next()?)?).ok()?.max(1));
+        let watchpoint = |read, write| Watchpoint { watch: Watch::Memory(range), read, write };
+        match (kind, insert) {
+            ("0" | "1", true)   => { self.debugger.add_breakpoint(range.start); },
+            ("0" | "1", false)  => { self.debugger.remove_breakpoint(range.start); },
+            ("2", _)            => self.watchpoint(insert, watchpoint(false, true)),
+            ("3", _)            => self.watchpoint(insert, watchpoint(true, false)),
+            ("4", _)            => self.watchpoint(insert, watchpoint(true, true)),
+            _                   => return Some(String::new()), // unsupported
+        }
+        Some(ok())
+    }
+
+    fn watchpoint(&mut self, insert: bool, watchpoint: Watchpoint) {
+        if insert { self.debugger.add_watchpoint(watchpoint) } else { self.debugger.remove_watchpoint(&watchpoint); }
+    }
+
+    fn step(&mut self) -> String {
+        let mut stop = self.debugger.step_into(self.ctx);
+        if stop == StopReason::Blocked(StepOutcome::AwaitingVBlank) {
+            self.ctx.step_clocks();
+            stop = self.debugger.step_into(self.ctx);
+        }
+        self.stop_reply(stop)
+    }
+
+    fn cont(&mut self, conn: &mut impl Connection) -> io::Result<String> {
+        let mut check = false; // resuming: don't re-stop on the current instruction
+        loop {
+            if check { if let Some(stop) = self.debugger.check(self.ctx) { return Ok(self.stop_reply(stop)) } }
+            match self.debugger.run(self.ctx, self.steps_per_frame.max(1)) {
+                StopReason::StepLimit       => check = true,
+                StopReason::Blocked(_)      => check = false, // the blocked instruction was already checked
+                stop                        => return Ok(self.stop_reply(stop)),
+            }
+            self.ctx.step_clocks();
+            if conn.interrupted()? { return Ok(stop(SIGINT)) }
+        }
+    }
+
+    fn stop_reply(&self, reason: StopReason) -> String {
+        match reason {
+            StopReason::Stepped | StopReason::StepLimit | StopReason::Blocked(_) => stop(SIGTRAP),
+            StopReason::Breakpoint(_)   => format!("T{SIGTRAP:02x}swbreak:;"),
+            StopReason::Watchpoint { watchpoint, write } => {
+                let Watch::Memory(range) = watchpoint.watch else { return stop(SIGTRAP) };
+                let kind = match (watchpoint.read, watchpoint.write, write) { (true, true, _) => "awatch", (_, _, true) => "watch", _ => "rwatch" };
+                format!("T{SIGTRAP:02x}{kind}:{:x};", range.start.0)
+            },
+            StopReason::Exited          => "W00".into(),
+            StopReason::Fault(fault)    => stop(match fault.kind {
+                FaultKind::InvalidOpcode | FaultKind::MachineCodeCall => SIGILL,
+                FaultKind::StackUnderflow | FaultKind::StackOverflow | FaultKind::MemoryOutOfRange(_) => SIGSEGV,
+            }),
+        }
+    }
+
+    /// Read the next packet's payload, acknowledging it.  Returns `"\x03"` for an interrupt, or `None` on disconnect.
+    fn read_packet(&mut self, conn: &mut impl Connection) -> io::Result<Option<String>> {
+        loop {
+            let Some(b) = read_byte(conn)? else { return Ok(None) };
+            match b {
+                0x03    => return Ok(Some("\x03".into())),
+                b'$'    => {},
+                _       => continue, // acks ('+' / '-') and noise
+            }
+            let mut payload = Vec::new();
+            loop {
+                match read_byte(conn)? {
+                    None        => return Ok(None),
+                    Some(b'#')  => break,
+                    Some(b)     => payload.push(b),
+                }
+            }
+            let mut checksum = [0u8; 2];
+            conn.read_exact(&mut checksum)?;
+            let valid = std::str::from_utf8(&checksum).ok().and_then(|c| u8::from_str_radix(c, 16).ok()) == Some(sum(&payload));
+            if !self.no_ack { conn.write_all(if valid { b"+" } else { b"-" })?; }
+            if valid { return Ok(Some(String::from_utf8_lossy(&payload).into_owned())) }
+        }
+    }
+
+    fn write_packet(&mut self, conn: &mut impl Connection, payload: &str) -> io::Result<()> {
+        write!(conn, "${payload}#{:02x}", sum(payload.as_bytes()))?;
+        conn.flush()
+    }
+}
+
+fn read_byte(conn: &mut impl Read) -> io::Result<Option<u8>> {
+    let mut b = [0u8];
+    match conn.read(&mut b)? { 0 => Ok(None), _ => Ok(Some(b[0])) }
+}
+
+fn sum(data: &[u8]) -> u8 { data.iter().fold(0u8, |s, &b| s.wrapping_add(b)) }
+fn ok() -> String { "OK".into() }
+fn error(e: u8) -> String { format!("E{e:02x}") }
+fn stop(signal: u8) -> String { format!("S{signal:02x}") }
+
+fn encode_hex(bytes: &[u8]) -> String { bytes.iter().map(|b| format!("{b:02x}")).collect() }
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) { return None }
+    (0 .. hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i .. i+2)?, 16).ok()).collect()
+}
+fn parse_hex(hex: &str) -> Option<usize> { usize::from_str_radix(hex, 16).ok() }
+fn parse_range(args: &str) -> Option<MemoryRange> {
+    let (addr, len) = args.split_once(',')?;
+    Some(MemoryRange::new(Addr(u16::try_from(parse_hex(addr)?).ok()?), u32::try_from(parse_hex(len)?).ok()?))
+}
+
+fn target_xml() -> String {
+    let mut xml = String::from(r#"<?xml version="1.0"?><!DOCTYPE target SYSTEM "gdb-target.dtd"><target version="1.0"><feature name="org.maulingmonkey.chip8">"#);
+    for v in 0 .. 16 { xml += &format!(r#"<reg name="v{v:x}" bitsize="8" type="uint8" regnum="{v}"/>"#) }
+    xml += r#"<reg name="i" bitsize="16" type="data_ptr"/><reg name="pc" bitsize="16" type="code_ptr"/>"#;
+    xml += r#"<reg name="sp" bitsize="8" type="uint8"/><reg name="dt" bitsize="8" type="uint8"/><reg name="st" bitsize="8" type="uint8"/>"#;
+    xml += "</feature></target>";
+    xml
+}
+
+#[test] fn test_loopback() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || {
+        let mut ctx = Context::<()> { quirks: Quirks::CHIP_48, ..Context::default() };
+        ctx.memory.copy_from_slice(Addr(0x200), &[0x60, 0x2A, 0x70, 0x01, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x02]).unwrap(); // V0 = 42; loop { V0 += 1; I = 300; i[..] <- [V0] }
+        ctx.registers.pc = Addr(0x200);
+        let (mut stream, _) = listener.accept().unwrap();
+        stream.set_nodelay(true).unwrap();
+        GdbServer::new(&mut ctx).serve(&mut stream).unwrap();
+        ctx.registers
+    });
+
+    let mut client = TcpStream::connect(addr).unwrap();
+    client.set_nodelay(true).unwrap();
+    let mut request = |payload: &str| -> String {
+        write!(client, "${payload}#{:02x}", sum(payload.as_bytes())).unwrap();
+        let mut reply = Vec::new();
+        loop {
+            let b = read_byte(&mut client).unwrap().unwrap();
+            if b == b'+' && reply.is_empty() { continue }
+            if b == b'#' { let mut c = [0u8; 2]; client.read_exact(&mut c).unwrap(); break }
+            reply.push(b);
+        }
+        client.write_all(b"+").unwrap();
+        String::from_utf8(reply).unwrap().trim_start_matches('$').into()
+    };
+
+    assert!(request("qSupported:swbreak+").contains("swbreak+"));
+    assert_eq!(request("?"), "S05");
+    assert_eq!(request("p11"), "0002");                 // pc = 0x200
+    assert_eq!(request("s"), "S05");
+    assert_eq!(request("p0"), "2a");
+    assert_eq!(request("Z0,206,2"), "OK");
+    assert_eq!(request("c"), "T05swbreak:;");
+    assert_eq!(request("p11"), "0602");
+    assert_eq!(request("z0,206,2"), "OK");
+    assert_eq!(request("Z2,300,1"), "OK");
+    assert_eq!(request("c"), "T05watch:300;");
+    assert_eq!(request("s"), "S05");
+    assert_eq!(request("m300,2"), "2c00");
+    assert_eq!(request("M301,1:ff"), "OK");
+    assert_eq!(request("m300,2"), "2cff");
+    assert_eq!(request("P0=07"), "OK");
+    assert_eq!(request("g")[..4], *"0700");
+    assert!(request("qXfer:features:read:target.xml:0,1000").starts_with("l<?xml"));
+    assert_eq!(request("m1000,1"), "E01");
+    assert_eq!(request(""), "");
+    assert_eq!(request("\u{FFFD}?"), "");
+    assert_eq!(request("D"), "OK");
+
+    let registers = server.join().unwrap();
+    assert_eq!(registers.v[0], 7);
+}
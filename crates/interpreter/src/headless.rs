@@ -0,0 +1,49 @@
+//! Run ROMs headless, for reproducible tools like `chip8-trace`, `chip8-profile`, and `chip8-coverage`.
+//!
+//! ```no_run
+//! # use maulingmonkey_chip8_interpreter::*;
+//! let rom = std::fs::read("game.ch8").unwrap();
+//! let (coverage, fault) = headless::run(&rom, Quirks::CHIP_48, FrameTiming::default(), 600, coverage::Coverage::new()).unwrap();
+//! ```
+
+use crate::*;
+use std::io;
+
+
+
+/// Run `rom` for `frames` frames, or until it exits, watched by `observer`.
+/// No keys are ever pressed, and [`Context::rng`] is [seeded](Rng::seeded) with 0, so runs are reproducible.
+///
+/// XO-CHIP `quirks` run with a [`Memory64K`], others with a [`Memory4K`].  Returns `observer`, and the [`Fault`] that
+/// ended the run early, if any.  Fails with [`io::ErrorKind::InvalidData`] if `rom` doesn't fit in memory.
+pub fn run<O: Observer>(rom: &[u8], quirks: Quirks, timing: FrameTiming, frames: usize, observer: O) -> io::Result<(O, Option<Fault>)> {
+    if quirks.instruction_set >= InstructionSet::XoChip {
+        run_in::<Memory64K, O>(rom, quirks, timing, frames, observer)
+    } else {
+        run_in::<Memory4K, O>(rom, quirks, timing, frames, observer)
+    }
+}
+
+fn run_in<M: Memory, O: Observer>(rom: &[u8], quirks: Quirks, mut timing: FrameTiming, frames: usize, observer: O) -> io::Result<(O, Option<Fault>)> {
+    let mut ctx = Context::<(), M, O>::with_observer((), observer);
+    ctx.rng = Rng::seeded(0);
+    ctx.load_rom(rom, quirks)?;
+    for _ in 0 .. frames {
+        if ctx.has_exited() { break }
+        if let Err(fault) = ctx.try_step_frame(&mut timing) { return Ok((ctx.observer, Some(fault))) }
+    }
+    Ok((ctx.observer, None))
+}
+
+#[test] fn test_run() {
+    let exits = [0x60, 0x01, 0x00, 0xFD]; // V0 <- 1, exit
+    let (profile, fault) = run(&exits, Quirks::SUPER_CHIP, FrameTiming::default(), 10, profile::Profiler::new()).unwrap();
+    assert_eq!((profile.frames, profile.instructions, fault), (1, 2, None), "stopped at the exit");
+
+    let (_, fault) = run(&[0x00, 0xEE], Quirks::CHIP_48, FrameTiming::default(), 10, ()).unwrap(); // return
+    assert_eq!(fault.map(|f| f.kind), Some(FaultKind::StackUnderflow));
+
+    let large = [&exits[..], &[0; 0x1000]].concat();
+    assert_eq!(run(&large, Quirks::SUPER_CHIP, FrameTiming::default(), 1, ()).err().map(|err| err.kind()), Some(io::ErrorKind::InvalidData));
+    assert_eq!(run(&large, Quirks::XO_CHIP,    FrameTiming::default(), 1, ()).unwrap().1, None);
+}
@@ -0,0 +1,576 @@
+//! [Cranelift](https://cranelift.dev/) JIT (requires the `jit` feature.)
+//!
+//! ```no_run
+//! # use maulingmonkey_chip8_interpreter::*;
+//! let mut ctx = Context::<()>::default();
+//! // ...load a ROM...
+//! let mut jit = jit::Jit::new().unwrap();
+//! loop {
+//!     ctx.try_step_many_jit(&mut jit, 1000).unwrap();
+//!     ctx.step_clocks();
+//! }
+//! ```
+//!
+//! # What's compiled
+//!
+//! Straight runs of register-only instructions (`6XNN`, `7XNN`, `8XY_`, `ANNN`, `FX1E`, `FX29`), optionally ending in a
+//! `1NNN`, `BNNN`, or `3XNN`/`4XNN`/`5XY0`/`9XY0` skip, are compiled into native basic blocks.  Everything else — anything
+//! touching [`Syscalls`], memory, the call stack, the screen, or timers — is run by [`Context::try_step_single`], so compiled
+//! and interpreted code share one [`Context`].
+//!
+//! # Self modifying code
+//!
+//! Instructions that overwrite compiled code evict the blocks they overlap, and the overwritten bytes are left to the
+//! interpreter from then on.  Compiled bytes are compared before and after each instruction that may write memory, so
+//! rewriting code with the same bytes, or `0NNN` machine code ([`Quirks::machine_code`]) that might write anywhere but
+//! doesn't touch compiled code, evicts nothing.  Writes to <code>[Context]::memory</code> made outside of instructions must be followed by
+//! [`Jit::invalidate`] or [`Jit::clear`].
+//!
+//! # Differential testing
+//!
+//! [`Jit::differential`] compiles every instruction into its own block, and checks each one against
+//! [`Context::try_step_single`] before it's committed, panicing on the first mismatch.
+
+use crate::*;
+use cranelift_codegen::ir::{types, AbiParam, InstBuilder, MemFlags, Value};
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::Module;
+use std::collections::HashMap;
+
+
+
+const MAX_BLOCK_STEPS : usize = 32;
+
+/// Native code compiled from CHIP-8 code, for [`Context::try_step_many_jit`].
+pub struct Jit {
+    module:         Option<JITModule>, // None only while being replaced by clear
+    ctx:            cranelift_codegen::Context,
+    blocks:         HashMap<Addr, Entry>,
+    code:           Vec<bool>, // by address: covered by a Entry::Native block
+    bytes:          Vec<u8>, // by address: the memory compiled, where code is true
+    modified:       Vec<bool>, // by address: overwritten while compiled, left to the interpreter
+    quirks:         Option<Quirks>, // blocks were compiled for
+    differential:   bool,
+}
+
+#[derive(Clone, Copy)] enum Entry {
+    Interpret, // the instruction at this address isn't compiled
+    Native(Block),
+}
+
+#[derive(Clone, Copy)] struct Block {
+    f:      unsafe extern "C" fn(*mut State) -> u32, // returns the new PC
+    end:    usize, // exclusive end of the bytes compiled (including the op after a skip)
+    steps:  usize,
+}
+
+#[repr(C)] struct State {
+    v:  [u8; 16],
+    i:  u16,
+}
+
+const STATE_V : i32 = 0;
+const STATE_I : i32 = 16;
+
+impl core::fmt::Debug for Jit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { write!(f, "Jit {{ ... }}") }
+}
+
+impl Drop for Jit {
+    fn drop(&mut self) {
+        if let Some(module) = self.module.take() { unsafe { module.free_memory() } } // dropping a JITModule leaks its code
+    }
+}
+
+impl Jit {
+    /// Create a JIT for the host, or an error if Cranelift doesn't support the host.
+    pub fn new() -> Result<Self, String> {
+        let module = new_module()?;
+        Ok(Self {
+            ctx:            module.make_context(),
+            module:         Some(module),
+            blocks:         Default::default(),
+            code:           Vec::new(),
+            bytes:          Vec::new(),
+            modified:       Vec::new(),
+            quirks:         None,
+            differential:   false,
+        })
+    }
+
+    /// Create a JIT that compiles each instruction separately, and checks each against the interpreter (see [module docs](self).)
+    pub fn differential() -> Result<Self, String> {
+        let mut jit = Self::new()?;
+        jit.differential = true;
+        Ok(jit)
+    }
+
+    /// Forget all compiled code (and which code was self modifying), freeing the native code.
+    pub fn clear(&mut self) {
+        self.blocks.clear();
+        self.code.fill(false);
+        self.modified.fill(false);
+        if let Some(module) = self.module.take() { unsafe { module.free_memory() } } // no more Blocks reference it
+        self.module = Some(new_module().expect("Cranelift supported the host earlier"));
+    }
+
+    /// Forget compiled code overlapping `range`.  Native code is only freed by [`clear`](Self::clear), or dropping the [`Jit`].
+    pub fn invalidate(&mut self, range: MemoryRange) {
+        let (start, end) = (range.start.to_usize(), range.start.to_usize() + range.len as usize);
+        self.blocks.retain(|addr, entry| {
+            let block_end = match entry { Entry::Interpret => addr.to_usize() + 2, Entry::Native(b) => b.end };
+            block_end <= start || end <= addr.to_usize()
+        });
+        self.code.fill(false);
+        for (addr, entry) in self.blocks.iter() {
+            if let Entry::Native(b) = entry { self.code[addr.to_usize() .. b.end].fill(true) }
+        }
+    }
+
+    /// An instruction may have written to `range`: mark any compiled code it changed as self modifying.
+    fn written<M: Memory>(&mut self, memory: &M, range: MemoryRange) {
+        let start = range.start.to_usize().min(self.code.len());
+        let end = (start + range.len as usize).min(self.code.len());
+        let memory = memory.as_slice_ref();
+        let mut changed = (start .. end).filter(|&addr| self.code[addr] && memory[addr] != self.bytes[addr]).peekable();
+        let Some(&first) = changed.peek() else { return };
+        let mut last = first;
+        for addr in changed {
+            self.modified[addr] = true;
+            last = addr;
+        }
+        self.invalidate(MemoryRange::new(Addr(first as u16), (last + 1 - first) as u32));
+    }
+
+    fn fit<M: Memory>(&mut self, quirks: Quirks) {
+        if self.code.len() != M::SIZE || self.quirks != Some(quirks) {
+            self.clear();
+            self.code = vec![false; M::SIZE];
+            self.bytes = vec![0; M::SIZE];
+            self.modified = vec![false; M::SIZE];
+            self.quirks = Some(quirks);
+        }
+    }
+
+    /// The compiled block at `pc`, if there is one and it runs at most `steps` instructions.
+    fn block<M: Memory>(&mut self, memory: &M, pc: Addr, steps: usize) -> Option<Block> {
+        let entry = match self.blocks.get(&pc) {
+            Some(entry) => *entry,
+            None => {
+                let entry = self.compile(memory, pc);
+                if let Entry::Native(b) = entry {
+                    let compiled = pc.to_usize() .. b.end;
+                    self.code[compiled.clone()].fill(true);
+                    self.bytes[compiled.clone()].copy_from_slice(&memory.as_slice_ref()[compiled]);
+                }
+                self.blocks.insert(pc, entry);
+                entry
+            },
+        };
+        match entry {
+            Entry::Native(block) if block.steps <= steps => Some(block),
+            _ => None,
+        }
+    }
+
+    fn compile<M: Memory>(&mut self, memory: &M, start: Addr) -> Entry {
+        let quirks = self.quirks.expect("fit before compile");
+        let module = self.module.as_mut().expect("module");
+        let ptr = module.target_config().pointer_type();
+        self.ctx.func.signature.params.push(AbiParam::new(ptr));
+        self.ctx.func.signature.returns.push(AbiParam::new(types::I32));
+
+        let mut fbc = FunctionBuilderContext::new();
+        let mut b = FunctionBuilder::new(&mut self.ctx.func, &mut fbc);
+        let block = b.create_block();
+        b.append_block_params_for_function_params(block);
+        b.switch_to_block(block);
+        b.seal_block(block);
+        let state = b.block_params(block)[0];
+        let mut emit = Emit { b, state, quirks, pc: start, skip: 4, v: [None; 16], v_dirty: [false; 16], i: None, i_dirty: false };
+
+        let max_steps = if self.differential { 1 } else { MAX_BLOCK_STEPS };
+        let (mut steps, mut end, mut exit) = (0, start.to_usize(), None);
+        while steps < max_steps && exit.is_none() {
+            let pc = emit.pc.to_usize();
+            if pc + 2 > M::SIZE || self.modified[pc] || self.modified[pc+1] { break }
+            let xo_long = quirks.instruction_set >= InstructionSet::XoChip && pc + 4 <= M::SIZE && memory.read16(Addr(emit.pc.0 + 2)) == 0xF000;
+            emit.skip = if xo_long { 6 } else { 4 };
+            match Op(memory.read16(emit.pc)).decode(&mut emit) {
+                Emitted::Unsupported    => break,
+                Emitted::Next           => {},
+                Emitted::Exit(pc)       => exit = Some(pc),
+            }
+            end = if exit.is_some() { (pc + 4).min(M::SIZE) } else { pc + 2 }; // skips read the next op
+            emit.pc.0 += 2;
+            steps += 1;
+        }
+
+        if steps == 0 {
+            self.ctx.clear();
+            return Entry::Interpret;
+        }
+
+        let exit = exit.unwrap_or_else(|| emit.b.ins().iconst(types::I32, i64::from(emit.pc.0)));
+        let Emit { mut b, state, v, v_dirty, i, i_dirty, .. } = emit;
+        for (n, (v, dirty)) in v.into_iter().zip(v_dirty).enumerate() {
+            if let (Some(v), true) = (v, dirty) { b.ins().store(MemFlags::trusted(), v, state, STATE_V + n as i32); }
+        }
+        if let (Some(i), true) = (i, i_dirty) { b.ins().store(MemFlags::trusted(), i, state, STATE_I); }
+        b.ins().return_(&[exit]);
+        b.finalize();
+
+        let f = (|| {
+            let id = module.declare_anonymous_function(&self.ctx.func.signature).map_err(drop)?;
+            module.define_function(id, &mut self.ctx).map_err(drop)?;
+            module.finalize_definitions().map_err(drop)?;
+            Ok::<_, ()>(module.get_finalized_function(id))
+        })();
+        module.clear_context(&mut self.ctx);
+        match f {
+            Ok(f)   => Entry::Native(Block { f: unsafe { core::mem::transmute::<*const u8, unsafe extern "C" fn(*mut State) -> u32>(f) }, end, steps }),
+            Err(()) => Entry::Interpret, // shouldn't happen, but the interpreter can always take over
+        }
+    }
+}
+
+impl<S: Syscalls, M: Memory, O: Observer> Context<S, M, O> {
+    /// [`try_step_many`](Self::try_step_many), but running native code compiled by `jit` where possible.
+    ///
+    /// Compiled blocks that would run more than the remaining `steps` are interpreted instead, so this runs exactly `steps`
+    /// instructions unless execution blocks, exits, or [`Fault`]s.  Only interpreted instructions are reported to the
+    /// [`observer`](Self::observer).
+    ///
+    /// # Panics
+    ///
+    /// With a [`Jit::differential`] `jit`, panics if compiled code diverges from [`try_step_single`](Self::try_step_single).
+    pub fn try_step_many_jit(&mut self, jit: &mut Jit, steps: usize) -> Result<usize, Fault> {
+        jit.fit::<M>(self.quirks);
+        let mut step = 0;
+        while step < steps {
+            if let Some(block) = jit.block(&self.memory, self.registers.pc, steps - step) {
+                let mut state = State { v: self.registers.v, i: self.registers.i.0 };
+                let pc = Addr(unsafe { (block.f)(&mut state) } as u16);
+                if jit.differential {
+                    let (start, op) = (self.registers.pc, Op(self.memory.read16(self.registers.pc)));
+                    assert_eq!(self.try_step_single(), Ok(StepOutcome::Stepped), "interpreter didn't step {op:?} at {start:?}");
+                    assert_eq!((state.v, Addr(state.i), pc), (self.registers.v, self.registers.i, self.registers.pc), "JIT (left) diverged from interpreter (right) after {op:?} at {start:?}");
+                } else {
+                    self.registers.v = state.v;
+                    self.registers.i = Addr(state.i);
+                    self.registers.pc = pc;
+                }
+                step += block.steps;
+                continue;
+            }
+
+            let write = self.accesses().memory_write;
+            if self.try_step_single()? != StepOutcome::Stepped { return Ok(step) }
+            if let Some(write) = write { jit.written(&self.memory, write) }
+            step += 1;
+        }
+        Ok(step)
+    }
+}
+
+fn new_module() -> Result<JITModule, String> {
+    let mut flags = settings::builder();
+    flags.set("opt_level", "speed").map_err(|err| err.to_string())?;
+    let isa = cranelift_native::builder()?.finish(settings::Flags::new(flags)).map_err(|err| err.to_string())?;
+    Ok(JITModule::new(JITBuilder::with_isa(isa, cranelift_module::default_libcall_names())))
+}
+
+
+
+enum Emitted {
+    Unsupported,    // nothing emitted, end the block before this op
+    Next,           // continue with the next op
+    Exit(Value),    // I32 PC to return
+}
+
+/// Emits Cranelift IR for an [`Op`], caching registers in SSA values until the block returns.
+struct Emit<'f> {
+    b:          FunctionBuilder<'f>,
+    state:      Value, // *mut State
+    quirks:     Quirks,
+    pc:         Addr,
+    skip:       u16, // bytes to advance if skipping
+    v:          [Option<Value>; 16],
+    v_dirty:    [bool; 16],
+    i:          Option<Value>,
+    i_dirty:    bool,
+}
+
+impl Emit<'_> {
+    fn v(&mut self, v: V) -> Value {
+        let n = v.0.to_usize();
+        if let Some(value) = self.v[n] { return value }
+        let value = self.b.ins().load(types::I8, MemFlags::trusted(), self.state, STATE_V + n as i32);
+        self.v[n] = Some(value);
+        value
+    }
+
+    fn set_v(&mut self, v: V, value: Value) -> Emitted {
+        let n = v.0.to_usize();
+        self.v[n] = Some(value);
+        self.v_dirty[n] = true;
+        Emitted::Next
+    }
+
+    fn i(&mut self) -> Value {
+        if let Some(value) = self.i { return value }
+        let value = self.b.ins().load(types::I16, MemFlags::trusted(), self.state, STATE_I);
+        self.i = Some(value);
+        value
+    }
+
+    fn set_i(&mut self, value: Value) -> Emitted {
+        self.i = Some(value);
+        self.i_dirty = true;
+        Emitted::Next
+    }
+
+    fn vf_reset(&mut self) -> Emitted {
+        if !self.quirks.vf_reset { return Emitted::Next }
+        let zero = self.b.ins().iconst(types::I8, 0);
+        self.set_v(VF, zero)
+    }
+
+    /// `cond` is an `I8` boolean (`icmp` result.)
+    fn skip_if(&mut self, cond: Value) -> Emitted {
+        let skip = self.b.ins().iconst(types::I32, i64::from(self.pc.0.wrapping_add(self.skip)));
+        let next = self.b.ins().iconst(types::I32, i64::from(self.pc.0.wrapping_add(2)));
+        Emitted::Exit(self.b.ins().select(cond, skip, next))
+    }
+}
+
+impl Decode for Emit<'_> {
+    type Result = Emitted;
+
+    fn invalid              (&mut self, _op: u16)                   -> Emitted { Emitted::Unsupported }
+    fn call_mcs             (&mut self, _addr: Addr)                -> Emitted { Emitted::Unsupported }
+    fn flow_call            (&mut self, _addr: Addr)                -> Emitted { Emitted::Unsupported }
+    fn set_v_rand_mask      (&mut self, _v: V, _mask: u8)           -> Emitted { Emitted::Unsupported }
+    fn draw_x_y_h           (&mut self, _: V, _: V, _: Nibble)      -> Emitted { Emitted::Unsupported }
+    fn skip_if_pressed      (&mut self, _key: V)                    -> Emitted { Emitted::Unsupported }
+    fn skip_unless_pressed  (&mut self, _key: V)                    -> Emitted { Emitted::Unsupported }
+    fn get_delay_timer      (&mut self, _v: V)                      -> Emitted { Emitted::Unsupported }
+    fn await_key            (&mut self, _v: V)                      -> Emitted { Emitted::Unsupported }
+    fn set_delay_timer      (&mut self, _v: V)                      -> Emitted { Emitted::Unsupported }
+    fn set_sound_timer      (&mut self, _v: V)                      -> Emitted { Emitted::Unsupported }
+    fn set_i_bcd            (&mut self, _v: V)                      -> Emitted { Emitted::Unsupported }
+    fn reg_dump             (&mut self, _v: V)                      -> Emitted { Emitted::Unsupported }
+    fn reg_load             (&mut self, _v: V)                      -> Emitted { Emitted::Unsupported }
+
+    fn flow_goto(&mut self, addr: Addr) -> Emitted {
+        Emitted::Exit(self.b.ins().iconst(types::I32, i64::from(addr.0)))
+    }
+
+    fn skip_if_v_eq_c(&mut self, v: V, c: u8) -> Emitted {
+        let v = self.v(v);
+        let cond = self.b.ins().icmp_imm(IntCC::Equal, v, i64::from(c));
+        self.skip_if(cond)
+    }
+
+    fn skip_if_v_ne_c(&mut self, v: V, c: u8) -> Emitted {
+        let v = self.v(v);
+        let cond = self.b.ins().icmp_imm(IntCC::NotEqual, v, i64::from(c));
+        self.skip_if(cond)
+    }
+
+    fn skip_if_v_eq_v(&mut self, vx: V, vy: V) -> Emitted {
+        let (x, y) = (self.v(vx), self.v(vy));
+        let cond = self.b.ins().icmp(IntCC::Equal, x, y);
+        self.skip_if(cond)
+    }
+
+    fn set_v_c(&mut self, vx: V, c: u8) -> Emitted {
+        let c = self.b.ins().iconst(types::I8, i64::from(c));
+        self.set_v(vx, c)
+    }
+
+    fn add_v_c(&mut self, vx: V, c: u8) -> Emitted {
+        let x = self.v(vx);
+        let sum = self.b.ins().iadd_imm(x, i64::from(c));
+        self.set_v(vx, sum)
+    }
+
+    fn set_v_v(&mut self, vx: V, vy: V) -> Emitted {
+        let y = self.v(vy);
+        self.set_v(vx, y)
+    }
+
+    fn bitor_v_v(&mut self, vx: V, vy: V) -> Emitted {
+        let (x, y) = (self.v(vx), self.v(vy));
+        let r = self.b.ins().bor(x, y);
+        self.set_v(vx, r);
+        self.vf_reset()
+    }
+
+    fn bitand_v_v(&mut self, vx: V, vy: V) -> Emitted {
+        let (x, y) = (self.v(vx), self.v(vy));
+        let r = self.b.ins().band(x, y);
+        self.set_v(vx, r);
+        self.vf_reset()
+    }
+
+    fn bitxor_v_v(&mut self, vx: V, vy: V) -> Emitted {
+        let (x, y) = (self.v(vx), self.v(vy));
+        let r = self.b.ins().bxor(x, y);
+        self.set_v(vx, r);
+        self.vf_reset()
+    }
+
+    fn add_v_v(&mut self, vx: V, vy: V) -> Emitted {
+        let (x, y) = (self.v(vx), self.v(vy));
+        let sum = self.b.ins().iadd(x, y);
+        let carry = self.b.ins().icmp(IntCC::UnsignedLessThan, sum, x);
+        self.set_v(vx, sum);
+        self.set_v(VF, carry)
+    }
+
+    fn sub_v_v(&mut self, vx: V, vy: V) -> Emitted {
+        let (x, y) = (self.v(vx), self.v(vy));
+        let diff = self.b.ins().isub(x, y);
+        let no_borrow = self.b.ins().icmp(IntCC::UnsignedGreaterThanOrEqual, x, y);
+        self.set_v(vx, diff);
+        self.set_v(VF, no_borrow)
+    }
+
+    fn shr1_v(&mut self, vx: V, vy: V) -> Emitted {
+        let src = self.v(if self.quirks.shift_vy { vy } else { vx });
+        let r = self.b.ins().ushr_imm(src, 1);
+        let bit = self.b.ins().band_imm(src, 1);
+        self.set_v(vx, r);
+        self.set_v(VF, bit)
+    }
+
+    fn sub_v_v_alt(&mut self, vx: V, vy: V) -> Emitted {
+        let (x, y) = (self.v(vx), self.v(vy));
+        let diff = self.b.ins().isub(y, x);
+        let no_borrow = self.b.ins().icmp(IntCC::UnsignedGreaterThanOrEqual, y, x);
+        self.set_v(vx, diff);
+        self.set_v(VF, no_borrow)
+    }
+
+    fn shl1_v(&mut self, vx: V, vy: V) -> Emitted {
+        let src = self.v(if self.quirks.shift_vy { vy } else { vx });
+        let r = self.b.ins().ishl_imm(src, 1);
+        let bit = self.b.ins().ushr_imm(src, 7);
+        self.set_v(vx, r);
+        self.set_v(VF, bit)
+    }
+
+    fn skip_if_v_ne_v(&mut self, vx: V, vy: V) -> Emitted {
+        let (x, y) = (self.v(vx), self.v(vy));
+        let cond = self.b.ins().icmp(IntCC::NotEqual, x, y);
+        self.skip_if(cond)
+    }
+
+    fn set_i_c(&mut self, c: Addr) -> Emitted {
+        let c = self.b.ins().iconst(types::I16, i64::from(c.0));
+        self.set_i(c)
+    }
+
+    fn set_pc_v0_plus_c(&mut self, _v0: (), c: Addr) -> Emitted {
+        let v = self.v(if self.quirks.jump_vx { V(Nibble::truncate16(c.0 >> 8)) } else { V0 }); // BXNN vs BNNN
+        let v = self.b.ins().uextend(types::I32, v);
+        let pc = self.b.ins().iadd_imm(v, i64::from(c.0));
+        Emitted::Exit(self.b.ins().band_imm(pc, 0xFFF))
+    }
+
+    fn add_i_v(&mut self, v: V) -> Emitted {
+        let (i, v) = (self.i(), self.v(v));
+        let v = self.b.ins().uextend(types::I16, v);
+        let i = self.b.ins().iadd(i, v);
+        self.set_i(i)
+    }
+
+    fn set_i_sprite(&mut self, v: V) -> Emitted {
+        let v = self.v(v);
+        let v = self.b.ins().uextend(types::I16, v);
+        let offset = self.b.ins().imul_imm(v, 5);
+        let i = self.b.ins().iadd_imm(offset, i64::from(Addr::TYPICAL_FONTS_START.0));
+        self.set_i(i)
+    }
+}
+
+#[cfg(test)] fn test_program<M: Memory>(quirks: Quirks, program: &[u8], jit: &mut Jit, steps: usize) {
+    let mut expected = Context::<(), M> { quirks, ..Context::default() };
+    expected.memory.copy_from_slice(Addr(0x200), program).unwrap();
+    expected.registers.pc = Addr(0x200);
+    let mut actual = Context::<(), M> { quirks, ..Context::default() };
+    actual.memory.copy_from_slice(Addr(0x200), program).unwrap();
+    actual.registers.pc = Addr(0x200);
+
+    for _ in 0 .. steps / 10 {
+        assert_eq!(actual.try_step_many_jit(jit, 10), expected.try_step_many(10));
+        assert_eq!(actual.registers, expected.registers);
+    }
+    assert_eq!(actual.memory.as_slice_ref(), expected.memory.as_slice_ref());
+}
+
+#[test] fn test_jit() {
+    let program = [
+        0x60, 0xFE,             // 200: V0 <- 0xFE
+        0x61, 0x03,             // 202: V1 <- 0x03
+        0x62, 0x81,             // 204: V2 <- 0x81
+        0x80, 0x14,             // 206: V0 += V1        (carry)
+        0x83, 0x05,             // 208: V3 -= V0
+        0x84, 0x27,             // 20A: V4 = V2 - V4
+        0x85, 0x26,             // 20C: V5 = V2 >> 1
+        0x86, 0x2E,             // 20E: V6 = V2 << 1
+        0x87, 0x11,             // 210: V7 |= V1
+        0x88, 0x22,             // 212: V8 &= V2
+        0x89, 0x13,             // 214: V9 ^= V1
+        0xA3, 0x00,             // 216: I <- 300
+        0xF1, 0x1E,             // 218: I += V1
+        0xF1, 0x29,             // 21A: I <- sprite(V1)
+        0x8F, 0x14,             // 21C: VF += V1        (VF as destination)
+        0xA2, 0x32,             // 21E: I <- 232
+        0x60, 0x12,             // 220: V0 <- 0x12
+        0x61, 0x38,             // 222: V1 <- 0x38
+        0x7A, 0x01,             // 224: VA += 1
+        0x3A, 0x08,             // 226: skip if VA == 8
+        0x12, 0x30,             // 228: goto 230
+        0x62, 0x10,             // 22A: V2 <- 0x10
+        0xB2, 0x30,             // 22C: goto V0 + 230 = 242 (BNNN) or V2 + 230 = 240 (BXNN)
+        0x00, 0x00,             // 22E
+        0x12, 0x32,             // 230: goto 232
+        0x12, 0x34,             // 232: goto 234                    (first pass)
+        0xF1, 0x55,             // 234: [232..=233] <- [V0, V1]     rewrites 232 as `goto 238`
+        0x12, 0x00,             // 236: goto 200
+        0x12, 0x36,             // 238: goto 236
+        0x00, 0x00,             // 23A
+        0x00, 0x00,             // 23C
+        0x00, 0x00,             // 23E
+        0x12, 0x40,             // 240: spin
+        0x12, 0x42,             // 242: spin
+    ];
+
+    for quirks in [Quirks::COSMAC_VIP, Quirks::CHIP_48, Quirks::SUPER_CHIP] {
+        test_program::<Memory4K>(quirks, &program, &mut Jit::new().unwrap(), 1000);
+        test_program::<Memory4K>(quirks, &program, &mut Jit::differential().unwrap(), 1000);
+    }
+    test_program::<Memory64K>(Quirks::XO_CHIP, &program, &mut Jit::new().unwrap(), 1000);
+    test_program::<Memory64K>(Quirks::XO_CHIP, &program, &mut Jit::differential().unwrap(), 1000);
+}
+
+#[test] fn test_jit_machine_code() {
+    let mut program = vec![0; 0x101];
+    program[.. 8].copy_from_slice(&[
+        0x60, 0x01,             // 200: V0 <- 1
+        0x70, 0x01,             // 202: V0 += 1
+        0x03, 0x00,             // 204: call machine code at 300
+        0x12, 0x02,             // 206: goto 202
+    ]);
+    program[0x100] = 0xD4;      // 300: return                              SEP R4
+
+    let mut jit = Jit::new().unwrap();
+    test_program::<Memory4K>(Quirks { machine_code: true, ..Quirks::COSMAC_VIP }, &program, &mut jit, 100);
+    assert!(!jit.modified.contains(&true), "machine code that doesn't touch compiled code shouldn't disable the JIT");
+    assert!(jit.blocks.values().any(|entry| matches!(entry, Entry::Native(_))));
+}
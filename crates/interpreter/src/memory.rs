@@ -3,30 +3,38 @@ use std::io;
 
 
 
-/// 4 KiB of (wrapping 12-bit addressed) memory
-#[derive(Clone)] pub struct Memory4K([u64; 1<<9]);
-impl Default for Memory4K { fn default() -> Self { Self::new() } }
+/// Byte addressable memory for a [`Context`].  Addresses wrap around at the end of memory.
+pub trait Memory: Clone + Default {
+    /// Size of memory in bytes (a power of 2.)
+    const SIZE : usize;
 
-impl Memory4K {
-    pub const fn new() -> Self { Self([0; 1<<9]) }
+    fn as_slice_ref(&    self) -> &    [u8];
+    fn as_slice_mut(&mut self) -> &mut [u8];
+
+    /// The COSMAC VIP's display buffer, which lives at <code>[Addr::SYSTEM_DISPLAY_START]</code> ..= `0xFFF`.
+    fn screen_monochrome_64x32_mut(&mut self) -> &mut ScreenMonochrome64x32 {
+        let screen = &mut self.as_slice_mut()[Addr::SYSTEM_DISPLAY_START.to_usize()..][..256];
+        bytemuck::from_bytes_mut(screen)
+    }
 
-    pub fn read(&self, addr: Addr) -> u8 { self.as_bytes_ref().get(addr.to_usize() & 0xFFF).copied().unwrap_or(0) }
-    pub fn read16(&self, addr: Addr) -> u16 { u16::from_be_bytes([self.read(addr), self.read(Addr(addr.0+1))]) }
-    pub fn write(&mut self, addr: Addr, value: u8) { self.as_bytes_mut().get_mut(addr.to_usize() & 0xFFF).map(|b| *b = value); }
+    fn read(&self, addr: Addr) -> u8 { self.as_slice_ref().get(addr.to_usize() & (Self::SIZE-1)).copied().unwrap_or(0) }
+    fn read16(&self, addr: Addr) -> u16 { u16::from_be_bytes([self.read(addr), self.read(Addr(addr.0.wrapping_add(1)))]) }
+    fn write(&mut self, addr: Addr, value: u8) { if let Some(b) = self.as_slice_mut().get_mut(addr.to_usize() & (Self::SIZE-1)) { *b = value } }
 
-    pub fn clear(&mut self) { self.0.fill(0) }
+    fn clear(&mut self) { self.as_slice_mut().fill(0) }
 
-    pub fn copy_from_slice(&mut self, addr: Addr, src: &[u8]) -> Result<(), ()> {
-        let dst = self.as_bytes_mut().get_mut(addr.to_usize()..).ok_or(())?;
+    fn copy_from_slice(&mut self, addr: Addr, src: &[u8]) -> Result<(), ()> {
+        let dst = self.as_slice_mut().get_mut(addr.to_usize()..).ok_or(())?;
         let dst = dst.get_mut(0..src.len()).ok_or(())?;
-        Ok(dst.copy_from_slice(src))
+        dst.copy_from_slice(src);
+        Ok(())
     }
 
-    pub fn copy_from_io(&mut self, addr: Addr, mut src: impl io::Read) -> io::Result<()> {
+    fn copy_from_io(&mut self, addr: Addr, mut src: impl io::Read) -> io::Result<()> {
         const FILE_TOO_LARGE : io::ErrorKind = io::ErrorKind::InvalidData; // FileTooLarge is unstable
         let mut addr = addr.to_usize();
         loop {
-            let dst = self.as_bytes_mut().get_mut(addr..).ok_or(FILE_TOO_LARGE)?;
+            let dst = self.as_slice_mut().get_mut(addr..).ok_or(FILE_TOO_LARGE)?;
             if dst.is_empty() {
                 match src.read(&mut [0u8])? {
                     0 => return Ok(()),
@@ -40,6 +48,22 @@ impl Memory4K {
             }
         }
     }
+}
+
+
+
+/// 4 KiB of (wrapping 12-bit addressed) memory
+#[derive(Clone)] pub struct Memory4K([u64; 1<<9]);
+impl Default for Memory4K { fn default() -> Self { Self::new() } }
+
+impl Memory for Memory4K {
+    const SIZE : usize = 1<<12;
+    fn as_slice_ref(&    self) -> &    [u8] { self.as_bytes_ref() }
+    fn as_slice_mut(&mut self) -> &mut [u8] { self.as_bytes_mut() }
+}
+
+impl Memory4K {
+    pub const fn new() -> Self { Self([0; 1<<9]) }
 
     pub fn as_bytes_ref(&    self) -> &    [u8; 1<<12] { bytemuck::cast_ref(&    self.0) }
     pub fn as_bytes_mut(&mut self) -> &mut [u8; 1<<12] { bytemuck::cast_mut(&mut self.0) }
@@ -49,11 +73,29 @@ impl Memory4K {
 
     pub fn as_qwords_ref(&    self) -> &    [u64; 1<<9] { &    self.0 }
     pub fn as_qwords_mut(&mut self) -> &mut [u64; 1<<9] { &mut self.0 }
+}
 
-    pub fn screen_monochrome_64x32_mut(&mut self) -> &mut ScreenMonochrome64x32 {
-        let screen = &mut self.as_qwords_mut()[(1<<9)-32..];
-        let screen = bytemuck::cast_slice_mut(screen);
-        let screen = bytemuck::from_bytes_mut(screen);
-        screen
-    }
+
+
+/// 64 KiB of (wrapping 16-bit addressed) memory, for XO-CHIP
+#[derive(Clone)] pub struct Memory64K(Box<[u64; 1<<13]>);
+impl Default for Memory64K { fn default() -> Self { Self::new() } }
+
+impl Memory for Memory64K {
+    const SIZE : usize = 1<<16;
+    fn as_slice_ref(&    self) -> &    [u8] { self.as_bytes_ref() }
+    fn as_slice_mut(&mut self) -> &mut [u8] { self.as_bytes_mut() }
+}
+
+impl Memory64K {
+    pub fn new() -> Self { Self(vec![0; 1<<13].into_boxed_slice().try_into().unwrap()) } // avoid a 64 KiB stack temporary
+
+    pub fn as_bytes_ref(&    self) -> &    [u8; 1<<16] { bytemuck::cast_ref(&*    self.0) }
+    pub fn as_bytes_mut(&mut self) -> &mut [u8; 1<<16] { bytemuck::cast_mut(&mut *self.0) }
+
+    pub fn as_words_ref(&    self) -> &    [u16; 1<<15] { bytemuck::cast_ref(&*    self.0) }
+    pub fn as_words_mut(&mut self) -> &mut [u16; 1<<15] { bytemuck::cast_mut(&mut *self.0) }
+
+    pub fn as_qwords_ref(&    self) -> &    [u64; 1<<13] { &    self.0 }
+    pub fn as_qwords_mut(&mut self) -> &mut [u64; 1<<13] { &mut self.0 }
 }
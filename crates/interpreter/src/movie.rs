@@ -0,0 +1,324 @@
+//! Movies: deterministic input recording ([`Recorder`]) and playback ([`Player`]).
+//!
+//! A [`Recorder`] wraps the live [`Syscalls`] and logs every input the [`Context`] observes.  A [`Player`] answers
+//! the same queries from that log, so replaying a [`Movie`] against the same ROM and [`Quirks`] is bit-exact.
+//!
+//! Frames end whenever the context renders (once per [`Context::step_clocks`].)  Within a frame:
+//! *   The keypad is sampled once, on the first `EX9E`/`EXA1`, so every `is_pressed` in that frame agrees.
+//! *   Every `get_key` (`FX0A`) and `rand` (`CXNN`) result is logged in order.
+//!
+//! An owned [`Context::rng`] needs no logging: its starting state is stored in the [`MovieHeader`] instead.
+//!
+//! # Format
+//!
+//! All integers are little endian.
+//!
+//! | Bytes     | Field                                                                 |
+//! | --------- | --------------------------------------------------------------------- |
+//! | 8         | Magic: `b"CHIP8MOV"`                                                  |
+//! | 2         | Version ([`VERSION`] when written by this crate)                      |
+//! | 4         | ROM length in bytes                                                   |
+//! | 4         | CRC-32 (IEEE) of the ROM                                              |
+//! | 3         | [`Quirks`], encoded as in [save states](crate::savestate)             |
+//! | 9         | [`Rng`], encoded as in [save states](crate::savestate) (version 2+)   |
+//! | 4         | Frame count                                                           |
+//! | *varies*  | Frames                                                                |
+//! | 4         | CRC-32 (IEEE) of everything after the version                         |
+//!
+//! ### Frame
+//!
+//! | Bytes     | Field                                                                 |
+//! | --------- | --------------------------------------------------------------------- |
+//! | 2         | Keypad: bit N set if key N was held                                   |
+//! | 4         | `get_key` result count                                                |
+//! | *count*   | `get_key` results: `0x0` ..= `0xF`, or `0xFF` for `None`              |
+//! | 4         | `rand` result count                                                   |
+//! | *count*   | `rand` results                                                        |
+
+use crate::*;
+use crate::savestate::{crc32, quirks_from_bytes, quirks_to_bytes, rng_from_bytes, rng_to_bytes};
+use std::cell::{Cell, RefCell};
+use std::io::{self, Read, Write};
+
+
+
+/// Magic bytes at the start of every movie.
+pub const MAGIC : [u8; 8] = *b"CHIP8MOV";
+
+/// The version written by [`Movie::write`].
+pub const VERSION : u16 = 2;
+
+/// Identifies what a [`Movie`] was recorded against.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)] pub struct MovieHeader {
+    pub rom_len:    u32,
+    pub rom_crc32:  u32,
+    pub quirks:     Quirks,
+
+    /// [`Context::rng`] when recording started.  Restore it before playback.
+    pub rng:        Rng,
+}
+
+/// The inputs observed during one frame.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)] pub struct MovieFrame {
+    /// Bit N is set if key N was held (only meaningful if the frame queried the keypad.)
+    pub keys:       u16,
+    pub get_key:    Vec<Option<u8>>,
+    pub rand:       Vec<u8>,
+}
+
+/// A [`MovieHeader`] and the [`MovieFrame`]s recorded after it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)] pub struct Movie {
+    pub header: MovieHeader,
+    pub frames: Vec<MovieFrame>,
+}
+
+impl MovieHeader {
+    /// A header for recording `rom` with `quirks`.  Set [`rng`](Self::rng) too if the context doesn't use [`Rng::Syscalls`].
+    pub fn new(rom: &[u8], quirks: Quirks) -> Self {
+        Self { rom_len: rom.len() as u32, rom_crc32: crc32(rom), quirks, rng: Rng::Syscalls }
+    }
+
+    /// Check that a movie with this header can be replayed against `rom` with `quirks`.
+    pub fn verify(&self, rom: &[u8], quirks: Quirks) -> io::Result<()> {
+        let rom = Self::new(rom, quirks);
+        if (rom.rom_len, rom.rom_crc32) != (self.rom_len, self.rom_crc32) { return Err(invalid("movie was recorded against a different ROM")) }
+        if quirks != self.quirks { return Err(invalid(format!("movie was recorded with different quirks: {:?}", self.quirks))) }
+        Ok(())
+    }
+}
+
+impl Movie {
+    pub fn new(header: MovieHeader) -> Self { Self { header, frames: Vec::new() } }
+
+    pub fn write(&self, mut w: impl Write) -> io::Result<()> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&self.header.rom_len.to_le_bytes());
+        body.extend_from_slice(&self.header.rom_crc32.to_le_bytes());
+        body.extend_from_slice(&quirks_to_bytes(&self.header.quirks));
+        body.extend_from_slice(&rng_to_bytes(&self.header.rng));
+        body.extend_from_slice(&len32(self.frames.len())?.to_le_bytes());
+        for frame in self.frames.iter() {
+            body.extend_from_slice(&frame.keys.to_le_bytes());
+            body.extend_from_slice(&len32(frame.get_key.len())?.to_le_bytes());
+            body.extend(frame.get_key.iter().map(|key| key.unwrap_or(0xFF)));
+            body.extend_from_slice(&len32(frame.rand.len())?.to_le_bytes());
+            body.extend_from_slice(&frame.rand);
+        }
+
+        w.write_all(&MAGIC)?;
+        w.write_all(&VERSION.to_le_bytes())?;
+        w.write_all(&body)?;
+        w.write_all(&crc32(&body).to_le_bytes())?;
+        Ok(())
+    }
+
+    /// Read a movie written by [`write`](Self::write).  Corrupt or truncated movies are rejected with [`io::ErrorKind::InvalidData`].
+    pub fn read(mut r: impl Read) -> io::Result<Self> {
+        let mut all = Vec::new();
+        r.read_to_end(&mut all)?;
+        if all.len() < 10 + 4 || all[..8] != MAGIC { return Err(invalid("not a CHIP-8 movie (bad magic)")) }
+        let version = u16::from_le_bytes([all[8], all[9]]);
+        if version == 0 || version > VERSION { return Err(invalid(format!("unsupported movie version {version} (expected 1 ..= {VERSION})"))) }
+        let (body, crc) = all[10..].split_at(all.len() - 10 - 4);
+        if crc32(body) != u32::from_le_bytes(crc.try_into().unwrap()) { return Err(invalid("corrupt movie (checksum mismatch)")) }
+
+        let mut body = body;
+        let mut take = |n: usize| -> io::Result<&[u8]> {
+            if n > body.len() { return Err(invalid("corrupt movie (truncated)")) }
+            let (head, tail) = body.split_at(n);
+            body = tail;
+            Ok(head)
+        };
+        let u16 = |b: &[u8]| u16::from_le_bytes(b.try_into().unwrap());
+        let u32 = |b: &[u8]| u32::from_le_bytes(b.try_into().unwrap());
+
+        let rom_len     = u32(take(4)?);
+        let rom_crc32   = u32(take(4)?);
+        let quirks      = quirks_from_bytes(take(3)?.try_into().unwrap())?;
+        let rng         = if version < 2 { Rng::Syscalls } else { rng_from_bytes(take(9)?.try_into().unwrap())? };
+        let mut movie   = Movie::new(MovieHeader { rom_len, rom_crc32, quirks, rng });
+        for _ in 0 .. u32(take(4)?) {
+            let keys = u16(take(2)?);
+            let n = u32(take(4)?) as usize;
+            let get_key = take(n)?.iter().map(|&key| match key {
+                0x0 ..= 0xF => Ok(Some(key)),
+                0xFF        => Ok(None),
+                _           => Err(invalid(format!("corrupt movie (get_key result 0x{key:02X})"))),
+            }).collect::<io::Result<_>>()?;
+            let n = u32(take(4)?) as usize;
+            let rand = take(n)?.to_vec();
+            movie.frames.push(MovieFrame { keys, get_key, rand });
+        }
+        if !body.is_empty() { return Err(invalid("corrupt movie (trailing bytes)")) }
+        Ok(movie)
+    }
+}
+
+
+
+/// Forwards to `S`, recording every input into a [`Movie`].
+pub struct Recorder<S: Syscalls> {
+    inner:      S,
+    movie:      RefCell<Movie>,
+    frame:      RefCell<MovieFrame>,
+    sampled:    Cell<bool>, // was frame.keys sampled yet?
+}
+
+impl<S: Syscalls> Recorder<S> {
+    pub fn new(inner: S, header: MovieHeader) -> Self {
+        Self { inner, movie: RefCell::new(Movie::new(header)), frame: Default::default(), sampled: Cell::new(false) }
+    }
+
+    pub fn inner(&self) -> &S { &self.inner }
+
+    /// The movie recorded so far, including the current (incomplete) frame.
+    pub fn movie(&self) -> Movie {
+        let mut movie = self.movie.borrow().clone();
+        if self.sampled.get() || *self.frame.borrow() != MovieFrame::default() { movie.frames.push(self.frame.borrow().clone()) }
+        movie
+    }
+
+    pub fn into_movie(self) -> Movie { self.movie() }
+
+    fn end_frame(&self) {
+        self.movie.borrow_mut().frames.push(self.frame.take());
+        self.sampled.set(false);
+    }
+}
+
+impl<S: Syscalls> Syscalls for Recorder<S> {
+    fn rand(&self) -> u8 {
+        let value = self.inner.rand();
+        self.frame.borrow_mut().rand.push(value);
+        value
+    }
+
+    fn get_key(&self) -> Option<u8> {
+        let key = self.inner.get_key().filter(|&key| key < 16);
+        self.frame.borrow_mut().get_key.push(key);
+        key
+    }
+
+    fn is_pressed(&self, key: u8) -> bool {
+        if !self.sampled.replace(true) {
+            self.frame.borrow_mut().keys = (0 .. 16).filter(|&k| self.inner.is_pressed(k)).fold(0, |keys, k| keys | 1 << k);
+        }
+        key < 16 && self.frame.borrow().keys & (1 << key) != 0
+    }
+
+    fn sound_play(&self) { self.inner.sound_play() }
+    fn sound_stop(&self) { self.inner.sound_stop() }
+    fn sound_pattern(&self, pattern: &[u8; 16], pitch: u8) { self.inner.sound_pattern(pattern, pitch) }
+    fn render(&self, screen: &ScreenMonochrome64x32) { self.inner.render(screen); self.end_frame() }
+    fn render_128x64(&self, screen: &ScreenMonochrome128x64) { self.inner.render_128x64(screen); self.end_frame() }
+    fn render_bitplanes(&self, screen: &ScreenBitplanes128x64) { self.inner.render_bitplanes(screen); self.end_frame() }
+}
+
+
+
+/// Replays a [`Movie`]'s inputs, forwarding output (rendering and sound) to `S`.
+///
+/// Once the movie runs out, or if the context asks for more inputs in a frame than were recorded (a desync), the keypad
+/// reads as released, `get_key` as `None`, and `rand` as 0.
+pub struct Player<S: Syscalls> {
+    inner:      S,
+    frames:     Vec<MovieFrame>,
+    frame:      Cell<usize>,
+    get_key:    Cell<usize>,
+    rand:       Cell<usize>,
+    desync:     Cell<Option<usize>>,
+}
+
+impl<S: Syscalls> Player<S> {
+    pub fn new(inner: S, movie: Movie) -> Self {
+        Self { inner, frames: movie.frames, frame: Cell::new(0), get_key: Cell::new(0), rand: Cell::new(0), desync: Cell::new(None) }
+    }
+
+    pub fn inner(&self) -> &S { &self.inner }
+
+    /// The index of the frame currently being replayed.
+    pub fn current_frame(&self) -> usize { self.frame.get() }
+
+    /// `true` once every recorded frame has been replayed.
+    pub fn is_finished(&self) -> bool { self.frame.get() >= self.frames.len() }
+
+    /// The first frame that asked for an input that wasn't recorded, if any.
+    pub fn desync(&self) -> Option<usize> { self.desync.get() }
+
+    fn next<T: Copy>(&self, pos: &Cell<usize>, log: impl FnOnce(&MovieFrame) -> &[T]) -> Option<T> {
+        let frame = self.frame.get();
+        let value = self.frames.get(frame).and_then(|f| log(f).get(pos.get()).copied());
+        if value.is_some() { pos.set(pos.get() + 1) } else if frame < self.frames.len() && self.desync.get().is_none() { self.desync.set(Some(frame)) }
+        value
+    }
+
+    fn end_frame(&self) {
+        let frame = self.frame.get();
+        if let Some(f) = self.frames.get(frame) {
+            if self.desync.get().is_none() && (self.get_key.get() != f.get_key.len() || self.rand.get() != f.rand.len()) { self.desync.set(Some(frame)) }
+        }
+        self.frame.set(frame + 1);
+        self.get_key.set(0);
+        self.rand.set(0);
+    }
+}
+
+impl<S: Syscalls> Syscalls for Player<S> {
+    fn rand(&self) -> u8 { self.next(&self.rand, |f| &f.rand).unwrap_or(0) }
+    fn get_key(&self) -> Option<u8> { self.next(&self.get_key, |f| &f.get_key).flatten() }
+    fn is_pressed(&self, key: u8) -> bool { key < 16 && self.frames.get(self.frame.get()).is_some_and(|f| f.keys & (1 << key) != 0) }
+    fn sound_play(&self) { self.inner.sound_play() }
+    fn sound_stop(&self) { self.inner.sound_stop() }
+    fn sound_pattern(&self, pattern: &[u8; 16], pitch: u8) { self.inner.sound_pattern(pattern, pitch) }
+    fn render(&self, screen: &ScreenMonochrome64x32) { self.inner.render(screen); self.end_frame() }
+    fn render_128x64(&self, screen: &ScreenMonochrome128x64) { self.inner.render_128x64(screen); self.end_frame() }
+    fn render_bitplanes(&self, screen: &ScreenBitplanes128x64) { self.inner.render_bitplanes(screen); self.end_frame() }
+}
+
+fn invalid(msg: impl Into<String>) -> io::Error { io::Error::new(io::ErrorKind::InvalidData, msg.into()) }
+fn len32(len: usize) -> io::Result<u32> { u32::try_from(len).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "movie frame too large")) }
+
+#[test] fn test_record_playback() {
+    #[derive(Default)] struct Live(Cell<u32>);
+    impl Live { fn tick(&self) -> u32 { let n = self.0.get() + 1; self.0.set(n); n } }
+    impl Syscalls for Live {
+        fn rand(&self) -> u8 { (self.tick() * 37) as u8 }
+        fn get_key(&self) -> Option<u8> { (self.tick() > 5).then_some(9) }
+        fn is_pressed(&self, _key: u8) -> bool { (self.tick() / 7) & 1 == 0 }
+        fn sound_play(&self) {}
+        fn sound_stop(&self) {}
+        fn render(&self, _screen: &ScreenMonochrome64x32) {}
+    }
+
+    // V3 = key; loop { V0 = rand; V4 += V0; if !pressed(V1) { V5 += 1 } }
+    let rom = [0xF3, 0x0A, 0xC0, 0xFF, 0x84, 0x04, 0xE1, 0xA1, 0x75, 0x01, 0x12, 0x02];
+    fn run<S: Syscalls>(syscalls: S, rom: &[u8]) -> Context<S> {
+        let mut ctx = Context::<S> { quirks: Quirks::CHIP_48, ..Context::with_syscalls(syscalls) };
+        ctx.memory.copy_from_slice(Addr::PROGRAM_START_TYPICAL, rom).unwrap();
+        ctx.registers.pc = Addr::PROGRAM_START_TYPICAL;
+        for _ in 0 .. 20 {
+            ctx.try_step_many(10).unwrap();
+            ctx.step_clocks();
+        }
+        ctx
+    }
+
+    let recorded = run(Recorder::new(Live::default(), MovieHeader::new(&rom, Quirks::CHIP_48)), &rom);
+    let mut file = Vec::new();
+    recorded.syscalls.movie().write(&mut file).unwrap();
+    let movie = Movie::read(&file[..]).unwrap();
+    assert_eq!(movie, recorded.syscalls.movie());
+    assert_eq!(movie.frames.len(), 20);
+    movie.header.verify(&rom, Quirks::CHIP_48).unwrap();
+    assert!(movie.header.verify(&rom[1..], Quirks::CHIP_48).is_err());
+    assert!(movie.header.verify(&rom, Quirks::COSMAC_VIP).is_err());
+
+    let replayed = run(Player::new((), movie), &rom);
+    assert_eq!(replayed.syscalls.desync(), None);
+    assert!(replayed.syscalls.is_finished());
+    assert_eq!(replayed.registers, recorded.registers);
+    assert_eq!(replayed.registers.v[3], 9);
+
+    file[20] ^= 1;
+    assert_eq!(Movie::read(&file[..]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+}
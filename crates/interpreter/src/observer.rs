@@ -0,0 +1,112 @@
+use crate::*;
+
+
+
+/// Watches a [`Context`] execute, for debuggers, profilers, coverage tools, etc.
+///
+/// Every fn defaults to doing nothing, so implement only what you need.  The [`()`](unit) observer, which
+/// <code>[Context]&lt;S, M&gt;</code> defaults to, does nothing at all, at no cost.
+///
+/// Events are reported by [`Context::try_step_single`] (and friends) and [`Context::try_step_cached`] (and friends).
+/// JIT and [`aot`] compiled code doesn't report events.
+pub trait Observer {
+    /// Whether [`memory_read`](Self::memory_read), [`memory_write`](Self::memory_write), [`v_write`](Self::v_write), and
+    /// [`i_write`](Self::i_write) should be reported.  These require analyzing each instruction before it executes
+    /// ([`Context::accesses`]), so observers that don't need them can opt out with `false`.
+    const ACCESSES : bool = true;
+
+    /// The instruction `op` at <code>registers.pc</code> is about to execute.  It might still [`fault`](Self::fault) or block.
+    fn fetch(&mut self, _registers: &Registers, _op: Op) {}
+
+    /// The instruction just fetched [`Fault`]ed instead of executing.
+    fn fault(&mut self, _fault: Fault) {}
+
+    /// The instruction just executed read `range` (sprites, `FX65`, in-memory call stacks...)
+    fn memory_read(&mut self, _range: MemoryRange) {}
+
+    /// The instruction just executed wrote `range` (`FX55`, `FX33`, in-memory call stacks...)
+    fn memory_write(&mut self, _range: MemoryRange) {}
+
+    /// The instruction just executed wrote `value` to `v` (including `VF` flags.)
+    fn v_write(&mut self, _v: V, _value: u8) {}
+
+    /// The instruction just executed wrote `value` to `I`.
+    fn i_write(&mut self, _value: Addr) {}
+
+    /// A `width` x `height` sprite was drawn at (`x`, `y`), in screen pixels before wrapping.
+    fn draw(&mut self, _x: u8, _y: u8, _width: usize, _height: usize, _collision: bool) {}
+
+    /// The 60 Hz timers ticked ([`Context::step_clocks`]) to `delay` and `sound`.
+    fn timer_tick(&mut self, _delay: u8, _sound: u8) {}
+
+    /// Sound started playing when the timers ticked, and [`Syscalls::sound_play`] was called.
+    fn sound_start(&mut self) {}
+
+    /// Sound stopped playing when the timers ticked, and [`Syscalls::sound_stop`] was called.
+    fn sound_stop(&mut self) {}
+
+    /// `FX0A` is blocked waiting for a key to store in `v`.  Reported each time it's retried.
+    fn key_wait(&mut self, _v: V) {}
+
+    /// `DXYN` is blocked waiting for vblank ([`Quirks::display_wait`].)  Reported each time it's retried.
+    fn vblank_wait(&mut self) {}
+}
+
+impl Observer for () {
+    const ACCESSES : bool = false;
+}
+
+
+
+#[test] fn test_observer() {
+    #[derive(Default)] struct Log(Vec<String>);
+    impl Observer for Log {
+        fn fetch       (&mut self, r: &Registers, op: Op) { self.0.push(format!("fetch {:03X} {:04X}", r.pc.0, op.0)) }
+        fn memory_read (&mut self, r: MemoryRange)      { self.0.push(format!("read {:03X}+{}", r.start.0, r.len)) }
+        fn memory_write(&mut self, r: MemoryRange)      { self.0.push(format!("write {:03X}+{}", r.start.0, r.len)) }
+        fn v_write     (&mut self, v: V, value: u8)     { self.0.push(format!("{v:?} = {value}")) }
+        fn i_write     (&mut self, value: Addr)         { self.0.push(format!("I = {:03X}", value.0)) }
+        fn draw(&mut self, x: u8, y: u8, w: usize, h: usize, c: bool) { self.0.push(format!("draw {x},{y} {w}x{h} {c}")) }
+        fn timer_tick  (&mut self, dt: u8, st: u8)      { self.0.push(format!("tick {dt} {st}")) }
+        fn sound_start (&mut self)                      { self.0.push("sound start".into()) }
+        fn sound_stop  (&mut self)                      { self.0.push("sound stop".into()) }
+        fn key_wait    (&mut self, v: V)                { self.0.push(format!("key wait {v:?}")) }
+        fn vblank_wait (&mut self)                      { self.0.push("vblank wait".into()) }
+        fn fault       (&mut self, f: Fault)            { self.0.push(format!("fault {:?}", f.kind)) }
+    }
+
+    let program = [
+        0x61, 0x03,             // 200: V1 <- 3
+        0xA3, 0x00,             // 202: I <- 0x300
+        0xD1, 0x11,             // 204: draw(V1, V1, 1)
+        0xF1, 0x18,             // 206: ST <- V1
+        0xF0, 0x55,             // 208: save V0
+        0xF2, 0x0A,             // 20A: V2 <- key
+        0x00, 0xEE,             // 20C: return
+    ];
+    let mut ctx = Context::<(), Memory4K, Log> { quirks: Quirks::COSMAC_VIP, ..Context::default() };
+    ctx.memory.copy_from_slice(Addr(0x200), &program).unwrap();
+    ctx.registers.pc = Addr(0x200);
+    assert_eq!(ctx.try_step_many(6), Ok(2));
+    ctx.step_clocks();
+    assert_eq!(ctx.try_step_many(6), Ok(3));
+    for _ in 0 .. 3 { ctx.step_clocks() }
+    ctx.registers.pc = Addr(0x20C);
+    assert!(ctx.try_step_single().is_err());
+
+    let log = ctx.observer.0.iter().map(String::as_str).collect::<Vec<_>>();
+    assert_eq!(log, [
+        "fetch 200 6103", "V1 = 3",
+        "fetch 202 A300", "I = 300",
+        "fetch 204 D111", "vblank wait",
+        "tick 0 0",
+        "fetch 204 D111", "draw 3,3 8x1 false", "read 300+1", "write F00+256", "VF = 0", // COSMAC VIP screen memory
+        "fetch 206 F118",
+        "fetch 208 F055", "write 300+1", "I = 301",
+        "fetch 20A F20A", "key wait V2",
+        "tick 0 2", "sound start",
+        "tick 0 1",
+        "tick 0 0", "sound stop",
+        "fetch 20C 00EE", "fault StackUnderflow",
+    ]);
+}
@@ -29,9 +29,9 @@ impl Debug for Op {
             fn bitand_v_v           (&mut self, vx: V, vy: V)               -> Self::Result { write!(self.0, "{vx} <- {vx} & {vy}") }
             fn bitxor_v_v           (&mut self, vx: V, vy: V)               -> Self::Result { write!(self.0, "{vx} <- {vx} ^ {vy}") }
             fn add_v_v              (&mut self, vx: V, vy: V)               -> Self::Result { write!(self.0, "({vx}, VF) <- ({vx} + {vy}, carry)") }
-            fn sub_v_v              (&mut self, vx: V, vy: V)               -> Self::Result { write!(self.0, "({vx}, VF) <- ({vx} - {vy}, borrow)") }
+            fn sub_v_v              (&mut self, vx: V, vy: V)               -> Self::Result { write!(self.0, "({vx}, VF) <- ({vx} - {vy}, !borrow)") }
             fn shr1_v               (&mut self, vx: V, vy: V)               -> Self::Result { write!(self.0, "({vx}, VF) <- ({vy} >> 1, {vy} & 0x01 != 0)") }
-            fn sub_v_v_alt          (&mut self, vx: V, vy: V)               -> Self::Result { write!(self.0, "({vx}, VF) <- ({vy} - {vx}, borrow)") }
+            fn sub_v_v_alt          (&mut self, vx: V, vy: V)               -> Self::Result { write!(self.0, "({vx}, VF) <- ({vy} - {vx}, !borrow)") }
             fn shl1_v               (&mut self, vx: V, vy: V)               -> Self::Result { write!(self.0, "({vx}, VF) <- ({vy} << 1, {vy} & 0x80 != 0)") }
             fn skip_if_v_ne_v       (&mut self, vx: V, vy: V)               -> Self::Result { write!(self.0, "skip_if {vx} != {vy}") }
             fn set_i_c              (&mut self, c: Addr)                    -> Self::Result { write!(self.0, "i <- {c}") }
@@ -49,6 +49,25 @@ impl Debug for Op {
             fn set_i_bcd            (&mut self, v: V)                       -> Self::Result { write!(self.0, "i[0..3] <- bcd({v})") }
             fn reg_dump             (&mut self, v: V)                       -> Self::Result { write!(self.0, "i[0..={n}] <- [V0..={v}]", n = v.0.to_u8()) }
             fn reg_load             (&mut self, v: V)                       -> Self::Result { write!(self.0, "[V0..={v}] <- i[0..={n}]", n = v.0.to_u8()) }
+
+            fn scroll_down          (&mut self, n: Nibble)                  -> Self::Result { write!(self.0, "scroll_down {n}") }
+            fn scroll_right         (&mut self)                             -> Self::Result { write!(self.0, "scroll_right 4") }
+            fn scroll_left          (&mut self)                             -> Self::Result { write!(self.0, "scroll_left 4") }
+            fn exit                 (&mut self)                             -> Self::Result { write!(self.0, "exit") }
+            fn lores                (&mut self)                             -> Self::Result { write!(self.0, "lores") }
+            fn hires                (&mut self)                             -> Self::Result { write!(self.0, "hires") }
+            fn draw_x_y_16x16       (&mut self, vx: V, vy: V)               -> Self::Result { write!(self.0, "draw_sprite(x={vx}, y={vy}, w=16, h=16, sprite=i)") }
+            fn set_i_sprite_large   (&mut self, v: V)                       -> Self::Result { write!(self.0, "i <- large_sprites[{v}]") }
+            fn rpl_dump             (&mut self, v: V)                       -> Self::Result { write!(self.0, "rpl[0..={n}] <- [V0..={v}]", n = v.0.to_u8()) }
+            fn rpl_load             (&mut self, v: V)                       -> Self::Result { write!(self.0, "[V0..={v}] <- rpl[0..={n}]", n = v.0.to_u8()) }
+
+            fn scroll_up            (&mut self, n: Nibble)                  -> Self::Result { write!(self.0, "scroll_up {n}") }
+            fn reg_dump_range       (&mut self, vx: V, vy: V)               -> Self::Result { write!(self.0, "i[..] <- [{vx}..={vy}]") }
+            fn reg_load_range       (&mut self, vx: V, vy: V)               -> Self::Result { write!(self.0, "[{vx}..={vy}] <- i[..]") }
+            fn set_i_long           (&mut self)                             -> Self::Result { write!(self.0, "i <- long") }
+            fn select_planes        (&mut self, n: Nibble)                  -> Self::Result { write!(self.0, "planes <- {n}") }
+            fn audio_pattern        (&mut self)                             -> Self::Result { write!(self.0, "audio_pattern <- i[0..16]") }
+            fn set_pitch            (&mut self, v: V)                       -> Self::Result { write!(self.0, "pitch <- {v}") }
         }
     }
 }
@@ -75,9 +94,9 @@ impl Debug for Op {
     assert_eq!(format!("{:?}", Op(0x8432)), "V4 <- V4 & V3");
     assert_eq!(format!("{:?}", Op(0x8543)), "V5 <- V5 ^ V4");
     assert_eq!(format!("{:?}", Op(0x8654)), "(V6, VF) <- (V6 + V5, carry)");
-    assert_eq!(format!("{:?}", Op(0x8765)), "(V7, VF) <- (V7 - V6, borrow)");
+    assert_eq!(format!("{:?}", Op(0x8765)), "(V7, VF) <- (V7 - V6, !borrow)");
     assert_eq!(format!("{:?}", Op(0x8876)), "(V8, VF) <- (V7 >> 1, V7 & 0x01 != 0)");
-    assert_eq!(format!("{:?}", Op(0x8987)), "(V9, VF) <- (V8 - V9, borrow)");
+    assert_eq!(format!("{:?}", Op(0x8987)), "(V9, VF) <- (V8 - V9, !borrow)");
     assert_eq!(format!("{:?}", Op(0x8678)), "invalid ; 0x8678");
     assert_eq!(format!("{:?}", Op(0x8789)), "invalid ; 0x8789");
     assert_eq!(format!("{:?}", Op(0x889A)), "invalid ; 0x889A");
@@ -109,4 +128,26 @@ impl Debug for Op {
     assert_eq!(format!("{:?}", Op(0xFC33)), "i[0..3] <- bcd(VC)");
     assert_eq!(format!("{:?}", Op(0xFD55)), "i[0..=13] <- [V0..=VD]");
     assert_eq!(format!("{:?}", Op(0xFE65)), "[V0..=VE] <- i[0..=14]");
+
+    // SUPER-CHIP
+    assert_eq!(format!("{:?}", Op(0x00C5)), "scroll_down 5");
+    assert_eq!(format!("{:?}", Op(0x00FB)), "scroll_right 4");
+    assert_eq!(format!("{:?}", Op(0x00FC)), "scroll_left 4");
+    assert_eq!(format!("{:?}", Op(0x00FD)), "exit");
+    assert_eq!(format!("{:?}", Op(0x00FE)), "lores");
+    assert_eq!(format!("{:?}", Op(0x00FF)), "hires");
+    assert_eq!(format!("{:?}", Op(0xD120)), "draw_sprite(x=V1, y=V2, w=16, h=16, sprite=i)");
+    assert_eq!(format!("{:?}", Op(0xF330)), "i <- large_sprites[V3]");
+    assert_eq!(format!("{:?}", Op(0xF475)), "rpl[0..=4] <- [V0..=V4]");
+    assert_eq!(format!("{:?}", Op(0xF585)), "[V0..=V5] <- rpl[0..=5]");
+
+    // XO-CHIP
+    assert_eq!(format!("{:?}", Op(0x00D3)), "scroll_up 3");
+    assert_eq!(format!("{:?}", Op(0x5122)), "i[..] <- [V1..=V2]");
+    assert_eq!(format!("{:?}", Op(0x5313)), "[V3..=V1] <- i[..]");
+    assert_eq!(format!("{:?}", Op(0xF000)), "i <- long");
+    assert_eq!(format!("{:?}", Op(0xF201)), "planes <- 2");
+    assert_eq!(format!("{:?}", Op(0xF002)), "audio_pattern <- i[0..16]");
+    assert_eq!(format!("{:?}", Op(0xF43A)), "pitch <- V4");
+    assert_eq!(format!("{:?}", Op(0xF100)), "invalid ; 0xF100");
 }
@@ -0,0 +1,307 @@
+//! Profile ROMs: where instructions, frames, and draws go.
+//!
+//! A [`Profiler`] is an [`Observer`], so profile a ROM by running it in a <code>[Context]&lt;S, M, [Profiler]&gt;</code>,
+//! then [`write_report`](Profiler::write_report).  Run it with [`Context::try_step_frame_vip`] to see how a ROM spends the
+//! COSMAC VIP's per-frame instruction budget.
+
+use crate::*;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+
+
+/// Rows per section of [`Profiler::write_report`].
+pub const REPORT_ROWS : usize = 20;
+
+/// The instruction last executed at an address, and how many instructions were executed there.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)] pub struct Instruction {
+    pub op:     Op,
+    pub count:  u64,
+}
+
+/// A `2NNN` call target.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)] pub struct Subroutine {
+    pub calls:      u64,
+    /// Instructions executed by the subroutine itself, including its `2NNN` and `00EE` instructions.
+    pub self_instructions: u64,
+    /// Instructions executed by the subroutine and the subroutines it calls.
+    pub total_instructions: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)] enum Wait { Key, VBlank }
+
+/// Collects execution counts by address and opcode class, draw counts, and time spent blocked.
+///
+/// Counts are in instructions and frames (60 Hz [`Context::step_clocks`] timer ticks.)
+#[derive(Clone, Debug, Default)] pub struct Profiler {
+    /// Executed instructions, by address.
+    pub executed:       BTreeMap<Addr, Instruction>,
+    /// Executed instructions, by opcode pattern (`"8XY4"`, `"DXYN"`, ...)
+    pub classes:        BTreeMap<&'static str, u64>,
+    /// Backwards jumps (and branches) from `.1` to `.0`, with how many times they were taken.  Calls and returns excluded.
+    pub loops:          BTreeMap<(Addr, Addr), u64>,
+    pub subroutines:    BTreeMap<Addr, Subroutine>,
+
+    pub instructions:   u64,
+    pub frames:         u64,
+    /// The most instructions executed in a single frame.
+    pub max_frame_instructions: u64,
+
+    pub draws:          u64,
+    /// Sprite rows drawn.
+    pub draw_rows:      u64,
+    pub collisions:     u64,
+
+    /// Frames spent blocked on `FX0A`.
+    pub key_wait_frames:    u64,
+    /// Frames spent blocked on `DXYN` ([`Quirks::display_wait`].)
+    pub vblank_wait_frames: u64,
+
+    pending:            Option<(Addr, Op)>, // fetched, but not known to have executed yet
+    last:               Option<(Addr, Op)>,
+    stack:              Vec<Addr>,
+    frame_instructions: u64,
+    waiting:            Option<Wait>,
+}
+
+impl Profiler {
+    pub fn new() -> Self { Self::default() }
+
+    /// Write a plain text report of the hottest instructions, loops, and subroutines, labeled with `symbols` (see [`asm::read_symbols`].)
+    pub fn write_report(&self, mut w: impl Write, symbols: &BTreeMap<Addr, String>) -> io::Result<()> {
+        let label = |addr: Addr| match symbols.range(..=addr).next_back() {
+            Some((&a, name)) if a == addr   => name.clone(),
+            Some((&a, name))                => format!("{name}+{}", addr.0 - a.0),
+            None                            => String::new(),
+        };
+        let percent = |n: u64| 100.0 * n as f64 / self.instructions.max(1) as f64;
+
+        writeln!(w, "frames              {}", self.frames)?;
+        writeln!(w, "instructions        {} ({:.1} per frame on average, {} at most)", self.instructions, self.instructions as f64 / self.frames.max(1) as f64, self.max_frame_instructions)?;
+        writeln!(w, "draws               {} ({} rows, {} collisions)", self.draws, self.draw_rows, self.collisions)?;
+        writeln!(w, "awaiting keys       {} frames", self.key_wait_frames)?;
+        writeln!(w, "awaiting vblank     {} frames", self.vblank_wait_frames)?;
+
+        writeln!(w)?;
+        writeln!(w, "opcode classes")?;
+        writeln!(w, "       count       %  class")?;
+        let mut classes = self.classes.iter().collect::<Vec<_>>();
+        classes.sort_by_key(|(_, &count)| std::cmp::Reverse(count));
+        for (class, &count) in classes.into_iter().take(REPORT_ROWS) {
+            writeln!(w, "{count:>12}  {:>5.1}%  {class}", percent(count))?;
+        }
+
+        writeln!(w)?;
+        writeln!(w, "hot instructions")?;
+        writeln!(w, "       count       %  addr  {:<24}  instruction", "label")?;
+        let mut executed = self.executed.iter().collect::<Vec<_>>();
+        executed.sort_by_key(|(&addr, inst)| (std::cmp::Reverse(inst.count), addr));
+        for (&addr, inst) in executed.into_iter().take(REPORT_ROWS) {
+            writeln!(w, "{:>12}  {:>5.1}%  {:04X}  {:<24}  {:?}", inst.count, percent(inst.count), addr.0, label(addr), inst.op)?;
+        }
+
+        writeln!(w)?;
+        writeln!(w, "hot loops")?;
+        writeln!(w, "  iterations    instructions       %  range      label")?;
+        let mut loops = self.loops.iter().map(|(&(start, end), &iterations)| {
+            let instructions = self.executed.range(start ..= end).map(|(_, inst)| inst.count).sum::<u64>();
+            (start, end, iterations, instructions)
+        }).collect::<Vec<_>>();
+        loops.sort_by_key(|&(start, end, _, instructions)| (std::cmp::Reverse(instructions), start, end));
+        for (start, end, iterations, instructions) in loops.into_iter().take(REPORT_ROWS) {
+            writeln!(w, "{iterations:>12}  {instructions:>14}  {:>5.1}%  {:04X}-{:04X}  {}", percent(instructions), start.0, end.0, label(start))?;
+        }
+
+        writeln!(w)?;
+        writeln!(w, "subroutines")?;
+        writeln!(w, "       calls           total       %            self  addr  label")?;
+        let mut subroutines = self.subroutines.iter().collect::<Vec<_>>();
+        subroutines.sort_by_key(|(&addr, sub)| (std::cmp::Reverse(sub.total_instructions), addr));
+        for (&addr, sub) in subroutines.into_iter().take(REPORT_ROWS) {
+            writeln!(w, "{:>12}  {:>14}  {:>5.1}%  {:>14}  {:04X}  {}", sub.calls, sub.total_instructions, percent(sub.total_instructions), sub.self_instructions, addr.0, label(addr))?;
+        }
+        Ok(())
+    }
+
+    /// Count the pending instruction, now that it's known not to have blocked.
+    fn commit(&mut self) {
+        let Some((pc, op)) = self.pending.take() else { return };
+        self.waiting = None;
+        if let Some((prev, prev_op)) = self.last {
+            if pc <= prev && !matches!(op_class(prev_op), "2NNN" | "00EE") { *self.loops.entry((pc, prev)).or_default() += 1 }
+        }
+        self.last = Some((pc, op));
+
+        self.instructions += 1;
+        self.frame_instructions += 1;
+        self.executed.entry(pc).and_modify(|i| *i = Instruction { op, count: i.count + 1 }).or_insert(Instruction { op, count: 1 });
+        *self.classes.entry(op_class(op)).or_default() += 1;
+
+        if let Some(&top) = self.stack.last() { self.subroutines.entry(top).or_default().self_instructions += 1 }
+        for (i, &sub) in self.stack.iter().enumerate() {
+            if self.stack[..i].contains(&sub) { continue } // recursion
+            self.subroutines.entry(sub).or_default().total_instructions += 1;
+        }
+        match op_class(op) {
+            "2NNN" => {
+                let target = Addr(op.0 & 0xFFF);
+                self.subroutines.entry(target).or_default().calls += 1;
+                self.stack.push(target);
+            },
+            "00EE" => { self.stack.pop(); },
+            _ => {},
+        }
+    }
+}
+
+impl Observer for Profiler {
+    const ACCESSES : bool = false;
+
+    fn fetch(&mut self, registers: &Registers, op: Op) {
+        self.commit();
+        self.pending = Some((registers.pc, op));
+    }
+
+    fn fault(&mut self, _fault: Fault) { self.pending = None }
+
+    fn draw(&mut self, _x: u8, _y: u8, _width: usize, height: usize, collision: bool) {
+        self.draws += 1;
+        self.draw_rows += height as u64;
+        self.collisions += u64::from(collision);
+    }
+
+    fn timer_tick(&mut self, _delay: u8, _sound: u8) {
+        self.commit();
+        self.frames += 1;
+        self.max_frame_instructions = self.max_frame_instructions.max(self.frame_instructions);
+        self.frame_instructions = 0;
+        match self.waiting {
+            Some(Wait::Key)     => self.key_wait_frames += 1,
+            Some(Wait::VBlank)  => self.vblank_wait_frames += 1,
+            None                => {},
+        }
+    }
+
+    fn key_wait(&mut self, _v: V) {
+        self.pending = None;
+        self.waiting = Some(Wait::Key);
+    }
+
+    fn vblank_wait(&mut self) {
+        self.pending = None;
+        self.waiting = Some(Wait::VBlank);
+    }
+}
+
+
+
+/// The opcode pattern of `op`, as used by [`Decode`]'s docs: `"8XY4"`, `"DXYN"`, ...
+fn op_class(op: Op) -> &'static str { op.decode(&mut Class) }
+
+struct Class;
+impl Decode for Class {
+    type Result = &'static str;
+
+    fn invalid              (&mut self, _op: u16)                   -> &'static str { "invalid" }
+    fn call_mcs             (&mut self, _addr: Addr)                -> &'static str { "0NNN" }
+    fn display_clear        (&mut self)                             -> &'static str { "00E0" }
+    fn flow_return          (&mut self)                             -> &'static str { "00EE" }
+    fn flow_goto            (&mut self, _addr: Addr)                -> &'static str { "1NNN" }
+    fn flow_call            (&mut self, _addr: Addr)                -> &'static str { "2NNN" }
+    fn skip_if_v_eq_c       (&mut self, _v: V, _c: u8)              -> &'static str { "3XNN" }
+    fn skip_if_v_ne_c       (&mut self, _v: V, _c: u8)              -> &'static str { "4XNN" }
+    fn skip_if_v_eq_v       (&mut self, _vx: V, _vy: V)             -> &'static str { "5XY0" }
+    fn set_v_c              (&mut self, _vx: V, _c: u8)             -> &'static str { "6XNN" }
+    fn add_v_c              (&mut self, _vx: V, _c: u8)             -> &'static str { "7XNN" }
+    fn set_v_v              (&mut self, _vx: V, _vy: V)             -> &'static str { "8XY0" }
+    fn bitor_v_v            (&mut self, _vx: V, _vy: V)             -> &'static str { "8XY1" }
+    fn bitand_v_v           (&mut self, _vx: V, _vy: V)             -> &'static str { "8XY2" }
+    fn bitxor_v_v           (&mut self, _vx: V, _vy: V)             -> &'static str { "8XY3" }
+    fn add_v_v              (&mut self, _vx: V, _vy: V)             -> &'static str { "8XY4" }
+    fn sub_v_v              (&mut self, _vx: V, _vy: V)             -> &'static str { "8XY5" }
+    fn shr1_v               (&mut self, _vx: V, _vy: V)             -> &'static str { "8XY6" }
+    fn sub_v_v_alt          (&mut self, _vx: V, _vy: V)             -> &'static str { "8XY7" }
+    fn shl1_v               (&mut self, _vx: V, _vy: V)             -> &'static str { "8XYE" }
+    fn skip_if_v_ne_v       (&mut self, _vx: V, _vy: V)             -> &'static str { "9XY0" }
+    fn set_i_c              (&mut self, _c: Addr)                   -> &'static str { "ANNN" }
+    fn set_pc_v0_plus_c     (&mut self, _v0: (), _c: Addr)          -> &'static str { "BNNN" }
+    fn set_v_rand_mask      (&mut self, _v: V, _mask: u8)           -> &'static str { "CXNN" }
+    fn draw_x_y_h           (&mut self, _vx: V, _vy: V, _h: Nibble) -> &'static str { "DXYN" }
+    fn skip_if_pressed      (&mut self, _key: V)                    -> &'static str { "EX9E" }
+    fn skip_unless_pressed  (&mut self, _key: V)                    -> &'static str { "EXA1" }
+    fn get_delay_timer      (&mut self, _v: V)                      -> &'static str { "FX07" }
+    fn await_key            (&mut self, _v: V)                      -> &'static str { "FX0A" }
+    fn set_delay_timer      (&mut self, _v: V)                      -> &'static str { "FX15" }
+    fn set_sound_timer      (&mut self, _v: V)                      -> &'static str { "FX18" }
+    fn add_i_v              (&mut self, _v: V)                      -> &'static str { "FX1E" }
+    fn set_i_sprite         (&mut self, _v: V)                      -> &'static str { "FX29" }
+    fn set_i_bcd            (&mut self, _v: V)                      -> &'static str { "FX33" }
+    fn reg_dump             (&mut self, _v: V)                      -> &'static str { "FX55" }
+    fn reg_load             (&mut self, _v: V)                      -> &'static str { "FX65" }
+
+    fn scroll_down          (&mut self, _n: Nibble)                 -> &'static str { "00CN" }
+    fn scroll_right         (&mut self)                             -> &'static str { "00FB" }
+    fn scroll_left          (&mut self)                             -> &'static str { "00FC" }
+    fn exit                 (&mut self)                             -> &'static str { "00FD" }
+    fn lores                (&mut self)                             -> &'static str { "00FE" }
+    fn hires                (&mut self)                             -> &'static str { "00FF" }
+    fn draw_x_y_16x16       (&mut self, _vx: V, _vy: V)             -> &'static str { "DXY0" }
+    fn set_i_sprite_large   (&mut self, _v: V)                      -> &'static str { "FX30" }
+    fn rpl_dump             (&mut self, _v: V)                      -> &'static str { "FX75" }
+    fn rpl_load             (&mut self, _v: V)                      -> &'static str { "FX85" }
+
+    fn scroll_up            (&mut self, _n: Nibble)                 -> &'static str { "00DN" }
+    fn reg_dump_range       (&mut self, _vx: V, _vy: V)             -> &'static str { "5XY2" }
+    fn reg_load_range       (&mut self, _vx: V, _vy: V)             -> &'static str { "5XY3" }
+    fn set_i_long           (&mut self)                             -> &'static str { "F000" }
+    fn select_planes        (&mut self, _n: Nibble)                 -> &'static str { "FN01" }
+    fn audio_pattern        (&mut self)                             -> &'static str { "F002" }
+    fn set_pitch            (&mut self, _v: V)                      -> &'static str { "FX3A" }
+}
+
+#[test] fn test_profile() {
+    let program = [
+        0x60, 0x03,             // 200: V0 <- 3
+        0x22, 0x0C,             // 202: call 20C
+        0x70, 0xFF,             // 204: V0 += 0xFF
+        0x30, 0x00,             // 206: skip if V0 == 0
+        0x12, 0x02,             // 208: goto 202
+        0xF1, 0x0A,             // 20A: V1 <- key
+        0xD0, 0x01,             // 20C: draw(V0, V0, 1)
+        0x00, 0xEE,             // 20E: return
+    ];
+    let mut ctx = Context::<(), Memory4K, Profiler>::default();
+    ctx.memory.copy_from_slice(Addr(0x200), &program).unwrap();
+    ctx.registers.pc = Addr(0x200);
+    assert_eq!(ctx.try_step_many(100), Ok(1 + 3 * 6 - 1));
+    for _ in 0 .. 3 {
+        ctx.step_clocks();
+        assert_eq!(ctx.try_step_many(100), Ok(0));
+    }
+    ctx.step_clocks();
+
+    let p = &ctx.observer;
+    assert_eq!((p.instructions, p.frames, p.max_frame_instructions), (18, 4, 18));
+    assert_eq!((p.draws, p.draw_rows, p.key_wait_frames), (3, 3, 4));
+    assert_eq!(p.executed[&Addr(0x202)], Instruction { op: Op(0x220C), count: 3 });
+    assert!(!p.executed.contains_key(&Addr(0x20A)), "blocked instructions aren't executed");
+    assert_eq!((p.classes["2NNN"], p.classes["DXYN"], p.classes["6XNN"]), (3, 3, 1));
+    assert_eq!(p.loops, BTreeMap::from([((Addr(0x202), Addr(0x208)), 2)]));
+    assert_eq!(p.subroutines[&Addr(0x20C)], Subroutine { calls: 3, self_instructions: 6, total_instructions: 6 });
+
+    let symbols = asm::read_symbols("# symbols\n0200 main\n020C draw\n".as_bytes()).unwrap();
+    let mut report = Vec::new();
+    p.write_report(&mut report, &symbols).unwrap();
+    let report = String::from_utf8(report).unwrap();
+    assert!(report.contains("0202-0208  main+2\n"), "{report}");
+    assert!(report.lines().any(|l| l.ends_with("020C  draw") && l.trim_start().starts_with("3 ")), "{report}");
+
+    let mut ctx = Context::<(), Memory4K, Profiler>::default();
+    ctx.memory.copy_from_slice(Addr(0x200), &[0x22, 0x00]).unwrap(); // 200: call 200
+    ctx.registers.pc = Addr(0x200);
+    for _ in 0 .. 100 { let _ = ctx.try_step_many(100); ctx.step_clocks(); }
+    let p = &ctx.observer;
+    assert_eq!(p.instructions, p.executed[&Addr(0x200)].count, "faulting instructions aren't executed");
+    assert_eq!(p.stack.len() as u64, p.instructions);
+    assert!(p.instructions < 100);
+}
@@ -0,0 +1,148 @@
+/// Machine dependent instruction behaviors.  Pick a preset like [`Quirks::COSMAC_VIP`], [`Quirks::CHIP_48`], or [`Quirks::SUPER_CHIP`].
+///
+/// ### References
+/// *   <https://tobiasvl.github.io/blog/write-a-chip-8-emulator/#instructions>
+/// *   <https://github.com/Timendus/chip8-test-suite#quirks-test>
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)] pub struct Quirks {
+    /// Which instruction set extensions are executed.  Extended instructions are treated as the original CHIP-8 instructions they alias (typically `0NNN`) otherwise.
+    pub instruction_set: InstructionSet,
+
+    /// `8XY6`/`8XYE` shift `VY` into `VX` (`true`, COSMAC VIP) instead of shifting `VX` in place (`false`, CHIP-48 / SUPER-CHIP).
+    pub shift_vy:       bool,
+
+    /// `BNNN` jumps to `VX + XNN` (`true`, CHIP-48 / SUPER-CHIP "`BXNN`") instead of `V0 + NNN` (`false`, COSMAC VIP).
+    pub jump_vx:        bool,
+
+    /// `FX55`/`FX65` leave `I` incremented past the last register (`true`, COSMAC VIP) instead of unchanged (`false`, CHIP-48 / SUPER-CHIP).
+    pub increment_i:    bool,
+
+    /// `DXYN` clips sprites at the edges of the screen (`true`) instead of wrapping them around to the other side (`false`).
+    ///
+    /// The *starting* coordinates of a sprite always wrap.
+    pub clip_sprites:   bool,
+
+    /// `8XY1`/`8XY2`/`8XY3` reset `VF` to 0 (`true`, COSMAC VIP) as a side effect of the VIP's ALU routine.
+    pub vf_reset:       bool,
+
+    /// `DXYN` waits for the next vertical blank (`true`, COSMAC VIP), limiting programs to one sprite draw per frame.
+    /// A new [`Context`](crate::Context) hasn't had a vertical blank yet, so with this set, its first `DXYN` waits for the first
+    /// [`step_clocks`](crate::Context::step_clocks) too.
+    pub display_wait:   bool,
+
+    /// Maximum number of nested `2NNN` calls before [`FaultKind::StackOverflow`](crate::FaultKind::StackOverflow).
+    /// 12 on the COSMAC VIP, 16 on SUPER-CHIP.  Capped at 16 in registers, or 48 in memory.
+    pub stack_depth:    u8,
+
+    /// Keep return addresses in emulated memory (`true`, COSMAC VIP) instead of in [`Registers`](crate::Registers) (`false`).
+    /// Entries are big endian, starting at [`Addr::SYSTEM_STACK_ETC_START`](crate::Addr::SYSTEM_STACK_ETC_START) and growing upward.
+    pub stack_in_memory: bool,
+
+    /// `0NNN` runs CDP1802 machine code at `NNN` (`true`, COSMAC VIP) instead of [faulting](crate::FaultKind::MachineCodeCall) (`false`).
+    /// Off in every preset, since most `0NNN`s executed by ROMs are bugs.  See [`vip`](crate::vip) for the calling convention.
+    pub machine_code:   bool,
+}
+
+/// This crate's behavior before quirks were configurable: [`COSMAC_VIP`](Self::COSMAC_VIP)'s shifts, jumps, and `I` increments,
+/// without [`vf_reset`](Self::vf_reset), [`display_wait`](Self::display_wait), or an in-memory stack.  Opt into presets explicitly.
+impl Default for Quirks {
+    fn default() -> Self { Quirks { vf_reset: false, display_wait: false, stack_depth: 16, stack_in_memory: false, ..Self::COSMAC_VIP } }
+}
+
+impl Quirks {
+    /// The original interpreter for the [COSMAC VIP](https://en.wikipedia.org/wiki/COSMAC_VIP).
+    pub const COSMAC_VIP : Quirks = Quirks {
+        instruction_set: InstructionSet::Chip8,
+        shift_vy:       true,
+        jump_vx:        false,
+        increment_i:    true,
+        clip_sprites:   true,
+        vf_reset:       true,
+        display_wait:   true,
+        stack_depth:    12,
+        stack_in_memory: true,
+        machine_code:   false,
+    };
+
+    /// CHIP-48 for the HP-48 graphing calculators.
+    pub const CHIP_48 : Quirks = Quirks {
+        instruction_set: InstructionSet::Chip8,
+        shift_vy:       false,
+        jump_vx:        true,
+        increment_i:    false,
+        clip_sprites:   true,
+        vf_reset:       false,
+        display_wait:   false,
+        stack_depth:    16,
+        stack_in_memory: false,
+        machine_code:   false,
+    };
+
+    /// SUPER-CHIP 1.1 for the HP-48 graphing calculators.
+    pub const SUPER_CHIP : Quirks = Quirks {
+        instruction_set: InstructionSet::SuperChip,
+        shift_vy:       false,
+        jump_vx:        true,
+        increment_i:    false,
+        clip_sprites:   true,
+        vf_reset:       false,
+        display_wait:   false,
+        stack_depth:    16,
+        stack_in_memory: false,
+        machine_code:   false,
+    };
+
+    /// [Octo](https://github.com/JohnEarnest/Octo)'s XO-CHIP extensions.  Use with a [`Memory64K`](crate::Memory64K).
+    pub const XO_CHIP : Quirks = Quirks {
+        instruction_set: InstructionSet::XoChip,
+        shift_vy:       true,
+        jump_vx:        false,
+        increment_i:    true,
+        clip_sprites:   false,
+        vf_reset:       false,
+        display_wait:   false,
+        stack_depth:    16,
+        stack_in_memory: false,
+        machine_code:   false,
+    };
+}
+
+/// Parse a preset by name: `vip` (or `chip8`), `chip48`, `schip` (or `superchip`), or `xochip`.
+impl core::str::FromStr for Quirks {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, String> {
+        match name {
+            "vip" | "chip8"         => Ok(Quirks::COSMAC_VIP),
+            "chip48"                => Ok(Quirks::CHIP_48),
+            "schip" | "superchip"   => Ok(Quirks::SUPER_CHIP),
+            "xochip"                => Ok(Quirks::XO_CHIP),
+            other                   => Err(format!("unknown quirks {other:?} (expected vip, chip48, schip, or xochip)")),
+        }
+    }
+}
+
+/// Instruction set extensions, in increasing order of support.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)] pub enum InstructionSet {
+    /// The original 35 instructions of the COSMAC VIP interpreter.
+    #[default] Chip8,
+
+    /// SUPER-CHIP 1.1: 128 x 64 high resolution mode, scrolling, 16 x 16 sprites, large fonts, and RPL flags.
+    SuperChip,
+
+    /// XO-CHIP: SUPER-CHIP plus 64 KiB addressing, 2 bitplanes, register range save/load, and audio patterns.
+    XoChip,
+}
+
+/// Parse an instruction set by name: `chip8`, `schip` (or `superchip`), or `xochip`.
+impl core::str::FromStr for InstructionSet {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, String> {
+        match name {
+            "chip8"                 => Ok(InstructionSet::Chip8),
+            "schip" | "superchip"   => Ok(InstructionSet::SuperChip),
+            "xochip"                => Ok(InstructionSet::XoChip),
+            other                   => Err(format!("unknown instruction set {other:?} (expected chip8, schip, or xochip)")),
+        }
+    }
+}
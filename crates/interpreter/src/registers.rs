@@ -2,7 +2,7 @@ use crate::*;
 
 
 
-/// V0 ..= VF, I, PC, and Stack
+/// V0 ..= VF, I, PC, and Stack (unless the stack lives in memory - see [`Quirks::stack_in_memory`])
 #[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Registers { // https://en.wikipedia.org/wiki/CHIP-8#Registers
     /// General purpouse registers
@@ -14,7 +14,8 @@ pub struct Registers { // https://en.wikipedia.org/wiki/CHIP-8#Registers
     /// Program Counter
     pub pc:     Addr,
 
-    pub(crate) stack:       Vec<Addr>, // could also live somewhere in memory[0xEA0 ..= 0xEFF]
+    pub(crate) sp:          u8, // number of return addresses on the stack
+    pub(crate) stack:       [Addr; 16], // unused if Quirks::stack_in_memory
     pub(crate) delay_timer: u8,
     pub(crate) sound_timer: u8,
     #[doc(hidden)] pub sound_playing: bool, // XXX
@@ -28,3 +29,14 @@ impl core::ops::Index<V> for Registers {
 impl core::ops::IndexMut<V> for Registers {
     fn index_mut(&mut self, index: V) -> &mut Self::Output { index.0.array_mut(&mut self.v) }
 }
+
+impl Registers {
+    /// Number of return addresses on the call stack (see [`Context::call_stack`].)
+    pub fn call_depth(&self) -> u8 { self.sp }
+
+    /// `FX07`/`FX15`: decremented at 60 Hz by [`Context::step_clocks`].
+    pub fn delay_timer(&self) -> u8 { self.delay_timer }
+
+    /// `FX18`: decremented at 60 Hz by [`Context::step_clocks`], with sound playing while it's 2 or more.
+    pub fn sound_timer(&self) -> u8 { self.sound_timer }
+}
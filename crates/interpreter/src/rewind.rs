@@ -0,0 +1,199 @@
+//! Rewind: a ring buffer of [save states](crate::savestate) taken every N frames.
+//!
+//! Only the newest snapshot is kept whole.  Every older snapshot is stored as a backward delta against the snapshot
+//! after it: the XOR of the two states, with runs of unchanged (zero) bytes run length encoded.  Since most of memory
+//! doesn't change from frame to frame, a typical delta is a few dozen bytes instead of the ~6 KiB of a full
+//! [`Memory4K`] state.
+//!
+//! # Delta encoding
+//!
+//! A sequence of `(zeros, literals)` pairs until the end of the state, where each count is a
+//! [LEB128](https://en.wikipedia.org/wiki/LEB128) varint, `zeros` is the number of unchanged bytes to skip, and
+//! `literals` is followed by that many XOR bytes.
+
+use crate::*;
+use std::collections::VecDeque;
+use std::io;
+
+
+
+/// A ring buffer of compact [`Context`] snapshots for frame-granular time travel.
+///
+/// Call [`frame`](Self::frame) once per [`Context::step_clocks`] and [`rewind`](Self::rewind) to go back in time.
+#[derive(Clone, Debug)] pub struct Rewind {
+    interval:   u32,
+    capacity:   usize,
+    frame:      u64,
+    newest:     Option<(u64, Vec<u8>)>,
+    older:      VecDeque<(u64, Vec<u8>)>, // (frame, delta against the next newer snapshot), oldest first
+}
+
+impl Rewind {
+    /// Snapshot every `interval` frames (min 1), keeping at most `capacity` snapshots (min 1.)
+    pub fn new(interval: u32, capacity: usize) -> Self {
+        Self { interval: interval.max(1), capacity: capacity.max(1), frame: 0, newest: None, older: VecDeque::new() }
+    }
+
+    /// Snapshot every `interval` frames, keeping enough snapshots to rewind at least `frames` frames.
+    pub fn for_frames(interval: u32, frames: u64) -> Self {
+        let interval = interval.max(1);
+        Self::new(interval, usize::try_from(frames.div_ceil(interval.into()) + 1).unwrap_or(usize::MAX))
+    }
+
+    /// The number of frames [`frame`](Self::frame) has counted (rewinding moves this backwards.)
+    pub fn current_frame(&self) -> u64 { self.frame }
+
+    /// The number of snapshots currently held.
+    pub fn len(&self) -> usize { self.older.len() + usize::from(self.newest.is_some()) }
+
+    pub fn is_empty(&self) -> bool { self.newest.is_none() }
+
+    /// The furthest back, in frames, that [`rewind`](Self::rewind) can currently go.
+    pub fn available_frames(&self) -> u64 {
+        let oldest = self.older.front().or(self.newest.as_ref()).map_or(self.frame, |(frame, _)| *frame);
+        self.frame - oldest
+    }
+
+    /// Total bytes of snapshot data held (excluding bookkeeping.)
+    pub fn size_in_bytes(&self) -> usize {
+        self.newest.iter().chain(self.older.iter()).map(|(_, state)| state.len()).sum()
+    }
+
+    /// Forget all snapshots.
+    pub fn clear(&mut self) {
+        self.newest = None;
+        self.older.clear();
+    }
+
+    /// Advance the frame counter, taking a [`snapshot`](Self::snapshot) of `ctx` first if this frame is a multiple of the interval.
+    pub fn frame<S: Syscalls, M: Memory, O: Observer>(&mut self, ctx: &Context<S, M, O>) {
+        if self.frame.is_multiple_of(u64::from(self.interval)) { self.snapshot(ctx) }
+        self.frame += 1;
+    }
+
+    /// Take a snapshot of `ctx` for the current frame, regardless of the interval.
+    pub fn snapshot<S: Syscalls, M: Memory, O: Observer>(&mut self, ctx: &Context<S, M, O>) {
+        let mut state = Vec::new();
+        ctx.save_state(&mut state).expect("save_state into a Vec shouldn't fail");
+
+        if let Some((frame, prev)) = self.newest.take() {
+            if frame == self.frame {
+                // re-snapshotting the same frame: replace rather than delta against ourselves
+            } else if prev.len() == state.len() {
+                self.older.push_back((frame, encode_delta(&prev, &state)));
+            } else {
+                self.older.clear(); // different memory size: older deltas can't be applied to the new state
+            }
+        }
+        self.newest = Some((self.frame, state));
+        while self.len() > self.capacity { self.older.pop_front(); }
+    }
+
+    /// Restore `ctx` to the newest snapshot at least `frames` frames ago, or the oldest snapshot if there isn't one that old.
+    ///
+    /// Snapshots newer than the restored one are discarded.  Returns how many frames were actually rewound.
+    pub fn rewind<S: Syscalls, M: Memory, O: Observer>(&mut self, ctx: &mut Context<S, M, O>, frames: u64) -> io::Result<u64> {
+        let target = self.frame.saturating_sub(frames);
+        let Some((mut frame, mut state)) = self.newest.take() else { return Ok(0) };
+
+        while frame > target {
+            let Some((older_frame, delta)) = self.older.pop_back() else { break };
+            if let Err(err) = apply_delta(&mut state, &delta) {
+                self.older.clear(); // the delta chain is broken: keep what we can still restore
+                self.newest = Some((frame, state));
+                return Err(err);
+            }
+            frame = older_frame;
+        }
+
+        let result = ctx.load_state(&state[..]);
+        let rewound = self.frame - frame;
+        if result.is_ok() { self.frame = frame; }
+        self.newest = Some((frame, state));
+        result.map(|()| rewound)
+    }
+}
+
+fn encode_delta(older: &[u8], newer: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut xor = older.iter().zip(newer).map(|(a, b)| a ^ b).peekable();
+    while xor.peek().is_some() {
+        let mut zeros = 0;
+        while xor.next_if_eq(&0).is_some() { zeros += 1; }
+        let literals = core::iter::from_fn(|| xor.next_if(|&b| b != 0)).collect::<Vec<u8>>();
+        write_varint(&mut out, zeros);
+        write_varint(&mut out, literals.len());
+        out.extend_from_slice(&literals);
+    }
+    out
+}
+
+fn apply_delta(state: &mut [u8], mut delta: &[u8]) -> io::Result<()> {
+    let mut pos = 0usize;
+    while !delta.is_empty() {
+        pos = pos.checked_add(read_varint(&mut delta)?).ok_or_else(corrupt)?;
+        let n = read_varint(&mut delta)?;
+        if n > delta.len() { return Err(corrupt()) }
+        let (literals, rest) = delta.split_at(n);
+        let dst = state.get_mut(pos..).and_then(|s| s.get_mut(..n)).ok_or_else(corrupt)?;
+        dst.iter_mut().zip(literals).for_each(|(d, l)| *d ^= l);
+        pos += n;
+        delta = rest;
+    }
+    Ok(())
+}
+
+fn write_varint(out: &mut Vec<u8>, mut n: usize) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn read_varint(src: &mut &[u8]) -> io::Result<usize> {
+    let mut n = 0usize;
+    for shift in (0 .. usize::BITS).step_by(7) {
+        let (&b, rest) = src.split_first().ok_or_else(corrupt)?;
+        *src = rest;
+        n |= usize::from(b & 0x7F) << shift;
+        if b & 0x80 == 0 { return Ok(n) }
+    }
+    Err(corrupt())
+}
+
+fn corrupt() -> io::Error { io::Error::new(io::ErrorKind::InvalidData, "corrupt rewind delta") }
+
+#[test] fn test_rewind() {
+    let mut ctx = Context::<()> { quirks: Quirks::CHIP_48, ..Context::default() };
+    ctx.memory.copy_from_slice(Addr(0x200), &[0x70, 0x01, 0xA3, 0x00, 0xF0, 0x33, 0x12, 0x00]).unwrap(); // V0 += 1, I = 0x300, BCD V0, loop
+    ctx.registers.pc = Addr(0x200);
+
+    let mut rewind = Rewind::new(2, 4);
+    for _ in 0 .. 10 {
+        rewind.frame(&ctx);
+        ctx.try_step_many(4).unwrap();
+        ctx.step_clocks();
+    }
+    assert_eq!(rewind.len(), 4); // frames 2, 4, 6, 8
+    assert_eq!(rewind.available_frames(), 8);
+    assert!(rewind.size_in_bytes() < 2 * 6 * 1024, "deltas should be compact, got {} bytes", rewind.size_in_bytes());
+
+    assert_eq!(rewind.rewind(&mut ctx, 3).unwrap(), 4); // frame 10 -> 6
+    assert_eq!(ctx.registers.v[0], 6);
+    assert_eq!(&ctx.memory.as_bytes_ref()[0x300..0x303], &[0, 0, 6]);
+    assert_eq!(rewind.len(), 3);
+
+    assert_eq!(rewind.rewind(&mut ctx, 100).unwrap(), 4); // frame 6 -> 2, the oldest
+    assert_eq!(ctx.registers.v[0], 2);
+    assert_eq!(rewind.current_frame(), 2);
+    assert_eq!(rewind.len(), 1);
+
+    let mut delta = encode_delta(&[1, 2, 3, 0, 0, 0, 0, 9], &[1, 2, 4, 0, 0, 0, 0, 8]);
+    assert_eq!(delta, [2, 1, 7, 4, 1, 1]);
+    let mut state = [1, 2, 4, 0, 0, 0, 0, 8];
+    apply_delta(&mut state, &delta).unwrap();
+    assert_eq!(state, [1, 2, 3, 0, 0, 0, 0, 9]);
+    delta[0] = 0xFF;
+    assert!(apply_delta(&mut state, &delta).is_err());
+}
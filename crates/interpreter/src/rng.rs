@@ -0,0 +1,70 @@
+use crate::*;
+
+
+
+/// Where `CXNN` gets its random numbers from.  Owned by a [`Context`] so runs can be seeded and reproduced.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)] pub enum Rng {
+    /// Ask <code>[Syscalls]::rand</code> (the default - typically thread-global and unseeded.)
+    #[default] Syscalls,
+
+    /// [xorshift64*](https://en.wikipedia.org/wiki/Xorshift#xorshift*) with this (non-zero) state.  See [`Rng::seeded`].
+    Xorshift64(u64),
+
+    /// Modeled on the COSMAC VIP interpreter's own `CXNN` routine, which mixed a 16-bit seed with bytes of the
+    /// interpreter's code: the low byte is incremented and used to index page `0x1xx` of memory, and the byte found
+    /// there is added into the high byte, which is the result.
+    ///
+    /// This reads *emulated* memory, so it's only as random as whatever lives at `0x100 ..= 0x1FF` - load the VIP
+    /// interpreter there for the original sequence.
+    CosmacVip { seed: u16 },
+}
+
+impl Rng {
+    /// A [`Rng::Xorshift64`] deterministically derived from any `seed` (including 0.)
+    pub fn seeded(seed: u64) -> Self {
+        // https://prng.di.unimi.it/splitmix64.c - spreads seeds and never yields the all-zero xorshift state for small seeds
+        let mut z = seed.wrapping_add(0x9E3779B97F4A7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        Rng::Xorshift64((z ^ (z >> 31)).max(1))
+    }
+
+    /// Generate the next random byte.
+    pub fn next(&mut self, memory: &impl Memory, syscalls: &impl Syscalls) -> u8 {
+        match self {
+            Rng::Syscalls => syscalls.rand(),
+            Rng::Xorshift64(x) => {
+                if *x == 0 { *x = 1 } // xorshift gets stuck at 0
+                *x ^= *x >> 12;
+                *x ^= *x << 25;
+                *x ^= *x >> 27;
+                (x.wrapping_mul(0x2545F4914F6CDD1D) >> 56) as u8
+            },
+            Rng::CosmacVip { seed } => {
+                let [hi, lo] = seed.to_be_bytes();
+                let lo = lo.wrapping_add(1);
+                let hi = hi.wrapping_add(memory.read(Addr(0x100 | u16::from(lo))));
+                *seed = u16::from_be_bytes([hi, lo]);
+                hi
+            },
+        }
+    }
+}
+
+#[test] fn test_rng() {
+    let memory = Memory4K::new();
+    let mut a = Rng::seeded(42);
+    let mut b = Rng::seeded(42);
+    let a = (0 .. 64).map(|_| a.next(&memory, &())).collect::<Vec<_>>();
+    assert_eq!(a, (0 .. 64).map(|_| b.next(&memory, &())).collect::<Vec<_>>());
+    assert!(a.iter().any(|&x| x != a[0]));
+    assert_ne!(Rng::seeded(0), Rng::Xorshift64(0));
+
+    let mut memory = Memory4K::new();
+    memory.write(Addr(0x101), 0x30);
+    memory.write(Addr(0x102), 0x05);
+    let mut vip = Rng::CosmacVip { seed: 0x1000 };
+    assert_eq!(vip.next(&memory, &()), 0x40);
+    assert_eq!(vip.next(&memory, &()), 0x45);
+    assert_eq!(vip, Rng::CosmacVip { seed: 0x4502 });
+}
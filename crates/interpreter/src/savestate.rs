@@ -0,0 +1,272 @@
+//! Save states: [`Context::save_state`] / [`Context::load_state`]
+//!
+//! # Format
+//!
+//! All integers are little endian unless otherwise noted.
+//!
+//! | Bytes     | Field                                                             |
+//! | --------- | ----------------------------------------------------------------- |
+//! | 8         | Magic: `b"CHIP8SAV"`                                              |
+//! | 2         | Version ([`VERSION`] when written by this crate)                  |
+//! | 4         | Payload length in bytes                                           |
+//! | *length*  | Payload                                                           |
+//! | 4         | CRC-32 (IEEE) of the payload                                      |
+//!
+//! ### Payload (version 2)
+//!
+//! | Bytes     | Field                                                             |
+//! | --------- | ----------------------------------------------------------------- |
+//! | 1         | [`Quirks::instruction_set`] (0 = CHIP-8, 1 = SUPER-CHIP, 2 = XO-CHIP) |
+//! | 1         | [`Quirks`] flags: bit 0 `shift_vy`, 1 `jump_vx`, 2 `increment_i`, 3 `clip_sprites`, 4 `vf_reset`, 5 `display_wait`, 6 `stack_in_memory`, 7 `machine_code` |
+//! | 1         | [`Quirks::stack_depth`]                                           |
+//! | 16        | `V0` ..= `VF`                                                     |
+//! | 2         | `I`                                                               |
+//! | 2         | `PC`                                                              |
+//! | 1         | Stack pointer                                                     |
+//! | 16 * 2    | Register stack                                                    |
+//! | 1         | Delay timer                                                       |
+//! | 1         | Sound timer                                                       |
+//! | 1         | Context flags: bit 0 sound playing, 1 vblank, 2 high resolution   |
+//! | 1         | XO-CHIP plane mask                                                |
+//! | 1         | XO-CHIP pitch                                                     |
+//! | 16        | SUPER-CHIP RPL flags                                              |
+//! | 16        | XO-CHIP audio pattern                                             |
+//! | 2 * 1024  | SUPER-CHIP / XO-CHIP planes: 64 rows of 128 pixels, each row big endian with the leftmost pixel in the most significant bit |
+//! | 4         | Memory size in bytes (must match the loading [`Context`]'s [`Memory::SIZE`]) |
+//! | *size*    | Memory                                                            |
+//! | 1         | [`Rng`] kind (0 = `Syscalls`, 1 = `Xorshift64`, 2 = `CosmacVip`)  |
+//! | 8         | [`Rng`] state (0 for `Syscalls`)                                  |
+//!
+//! Version 1 payloads end after memory.  Loading one leaves [`Context::rng`] unchanged.
+
+use crate::*;
+use std::io::{self, Read, Write};
+
+
+
+/// Magic bytes at the start of every save state.
+pub const MAGIC : [u8; 8] = *b"CHIP8SAV";
+
+/// The version written by [`Context::save_state`].  [`Context::load_state`] accepts this or any older version.
+pub const VERSION : u16 = 2;
+
+impl<S: Syscalls, M: Memory, O: Observer> Context<S, M, O> {
+    /// Write a [versioned save state](self) of everything but [`syscalls`](Self::syscalls).
+    pub fn save_state(&self, mut w: impl Write) -> io::Result<()> {
+        let mut p = Vec::with_capacity(2200 + M::SIZE);
+
+        p.extend_from_slice(&quirks_to_bytes(&self.quirks));
+
+        let r = &self.registers;
+        p.extend_from_slice(&r.v);
+        p.extend_from_slice(&r.i.0.to_le_bytes());
+        p.extend_from_slice(&r.pc.0.to_le_bytes());
+        p.push(r.sp);
+        for addr in r.stack.iter() { p.extend_from_slice(&addr.0.to_le_bytes()) }
+        p.push(r.delay_timer);
+        p.push(r.sound_timer);
+
+        p.push(bits(&[r.sound_playing, self.vblank, self.hires]));
+        p.push(self.planes);
+        p.push(self.pitch);
+        p.extend_from_slice(&self.rpl);
+        p.extend_from_slice(&self.audio_pattern);
+        for plane in self.screen_planes.0.iter() {
+            for row in bytemuck::cast_ref::<_, [u128; 64]>(plane) { p.extend_from_slice(&row.to_be_bytes()) }
+        }
+
+        p.extend_from_slice(&u32::try_from(M::SIZE).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "memory too large for save state"))?.to_le_bytes());
+        p.extend_from_slice(self.memory.as_slice_ref());
+
+        p.extend_from_slice(&rng_to_bytes(&self.rng));
+
+        w.write_all(&MAGIC)?;
+        w.write_all(&VERSION.to_le_bytes())?;
+        w.write_all(&(p.len() as u32).to_le_bytes())?;
+        w.write_all(&p)?;
+        w.write_all(&crc32(&p).to_le_bytes())?;
+        Ok(())
+    }
+
+    /// Read a save state written by [`save_state`](Self::save_state) (of this or any older [`VERSION`]), replacing everything but [`syscalls`](Self::syscalls).
+    ///
+    /// Corrupt, truncated, or incompatible states are rejected with [`io::ErrorKind::InvalidData`] and leave `self` unmodified.
+    pub fn load_state(&mut self, mut r: impl Read) -> io::Result<()> {
+        let mut header = [0u8; 14];
+        r.read_exact(&mut header).map_err(truncated)?;
+        if header[..8] != MAGIC { return Err(invalid("not a CHIP-8 save state (bad magic)")) }
+        let version = u16::from_le_bytes([header[8], header[9]]);
+        if version == 0 || version > VERSION { return Err(invalid(format!("unsupported save state version {version} (expected 1 ..= {VERSION})"))) }
+        let len = u32::from_le_bytes([header[10], header[11], header[12], header[13]]) as usize;
+
+        let mut payload = Vec::new();
+        r.by_ref().take(len as u64).read_to_end(&mut payload)?;
+        if payload.len() != len { return Err(truncated(io::ErrorKind::UnexpectedEof.into())) }
+        let mut crc = [0u8; 4];
+        r.read_exact(&mut crc).map_err(truncated)?;
+        if u32::from_le_bytes(crc) != crc32(&payload) { return Err(invalid("corrupt save state (checksum mismatch)")) }
+
+        let mut p = Payload(&payload[..]);
+
+        let quirks = quirks_from_bytes(p.array()?)?;
+        let stack_in_memory = quirks.stack_in_memory;
+
+        let mut registers = Registers { v: p.array()?, i: Addr(p.u16()?), pc: Addr(p.u16()?), sp: p.u8()?, ..Registers::default() };
+        for addr in registers.stack.iter_mut() { *addr = Addr(p.u16()?) }
+        registers.delay_timer = p.u8()?;
+        registers.sound_timer = p.u8()?;
+        if registers.sp > quirks.stack_depth || (!stack_in_memory && usize::from(registers.sp) > registers.stack.len()) { return Err(invalid("corrupt save state (stack pointer out of range)")) }
+
+        let [sound_playing, vblank, hires, ..] = unbits(p.u8()?);
+        registers.sound_playing = sound_playing;
+        let planes = p.u8()?;
+        if planes & !0b11 != 0 { return Err(invalid("corrupt save state (plane mask out of range)")) }
+        let pitch = p.u8()?;
+        let rpl = p.array()?;
+        let audio_pattern = p.array()?;
+        let mut screen_planes = ScreenBitplanes128x64::new();
+        for plane in screen_planes.0.iter_mut() {
+            for row in bytemuck::cast_mut::<_, [u128; 64]>(plane) { *row = u128::from_be_bytes(p.array()?) }
+        }
+
+        let size = p.u32()? as usize;
+        if size != M::SIZE { return Err(invalid(format!("save state has {size} bytes of memory, but this context has {}", M::SIZE))) }
+        let mut memory = M::default();
+        memory.as_slice_mut().copy_from_slice(p.bytes(size)?);
+
+        let rng = if version < 2 { self.rng } else { rng_from_bytes(p.array()?)? };
+
+        if !p.0.is_empty() { return Err(invalid("corrupt save state (trailing payload bytes)")) }
+
+        self.quirks         = quirks;
+        self.registers      = registers;
+        self.memory         = memory;
+        self.vblank         = vblank;
+        self.hires          = hires;
+        self.screen_planes  = screen_planes;
+        self.planes         = planes;
+        self.rpl            = rpl;
+        self.audio_pattern  = audio_pattern;
+        self.pitch          = pitch;
+        self.rng            = rng;
+        Ok(())
+    }
+}
+
+/// The 3 byte [`Quirks`] encoding shared by save states and [movies](crate::movie).
+pub(crate) fn quirks_to_bytes(q: &Quirks) -> [u8; 3] {
+    [q.instruction_set as u8, bits(&[q.shift_vy, q.jump_vx, q.increment_i, q.clip_sprites, q.vf_reset, q.display_wait, q.stack_in_memory, q.machine_code]), q.stack_depth]
+}
+
+pub(crate) fn quirks_from_bytes([instruction_set, flags, stack_depth]: [u8; 3]) -> io::Result<Quirks> {
+    let instruction_set = match instruction_set {
+        0 => InstructionSet::Chip8,
+        1 => InstructionSet::SuperChip,
+        2 => InstructionSet::XoChip,
+        n => return Err(invalid(format!("unknown instruction set {n}"))),
+    };
+    let [shift_vy, jump_vx, increment_i, clip_sprites, vf_reset, display_wait, stack_in_memory, machine_code] = unbits(flags);
+    Ok(Quirks { instruction_set, shift_vy, jump_vx, increment_i, clip_sprites, vf_reset, display_wait, stack_depth, stack_in_memory, machine_code })
+}
+
+/// The 9 byte [`Rng`] encoding shared by save states and [movies](crate::movie).
+pub(crate) fn rng_to_bytes(rng: &Rng) -> [u8; 9] {
+    let (kind, state) = match *rng {
+        Rng::Syscalls               => (0, 0),
+        Rng::Xorshift64(x)          => (1, x),
+        Rng::CosmacVip { seed }     => (2, seed.into()),
+    };
+    let mut b = [kind; 9];
+    b[1..].copy_from_slice(&u64::to_le_bytes(state));
+    b
+}
+
+pub(crate) fn rng_from_bytes(b: [u8; 9]) -> io::Result<Rng> {
+    let state = u64::from_le_bytes(b[1..].try_into().unwrap());
+    match b[0] {
+        0 => Ok(Rng::Syscalls),
+        1 => Ok(Rng::Xorshift64(state)),
+        2 => Ok(Rng::CosmacVip { seed: u16::try_from(state).map_err(|_| invalid("COSMAC VIP rng seed out of range"))? }),
+        n => Err(invalid(format!("unknown rng kind {n}"))),
+    }
+}
+
+struct Payload<'a>(&'a [u8]);
+impl<'a> Payload<'a> {
+    fn bytes(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        if n > self.0.len() { return Err(invalid("corrupt save state (payload too short)")) }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> io::Result<[u8; N]> { Ok(self.bytes(N)?.try_into().unwrap()) }
+    fn u8 (&mut self) -> io::Result<u8 > { Ok(self.array::<1>()?[0]) }
+    fn u16(&mut self) -> io::Result<u16> { Ok(u16::from_le_bytes(self.array()?)) }
+    fn u32(&mut self) -> io::Result<u32> { Ok(u32::from_le_bytes(self.array()?)) }
+}
+
+fn bits(flags: &[bool]) -> u8 { flags.iter().enumerate().fold(0, |b, (i, &f)| b | u8::from(f) << i) }
+fn unbits(b: u8) -> [bool; 8] { core::array::from_fn(|i| b & (1 << i) != 0) }
+
+fn invalid(msg: impl Into<String>) -> io::Error { io::Error::new(io::ErrorKind::InvalidData, msg.into()) }
+fn truncated(err: io::Error) -> io::Error {
+    if err.kind() == io::ErrorKind::UnexpectedEof { invalid("truncated save state") } else { err }
+}
+
+/// CRC-32 (IEEE 802.3), as used by zip, png, etc.
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &b| (0 .. 8).fold(crc ^ u32::from(b), |crc, _| (crc >> 1) ^ (0xEDB88320 & (crc & 1).wrapping_neg())))
+}
+
+#[test] fn test_round_trip() {
+    let mut a = Context::<()> { quirks: Quirks::SUPER_CHIP, rng: Rng::seeded(1), ..Context::default() };
+    a.memory.copy_from_slice(Addr(0x200), &[0x00, 0xFF, 0x22, 0x06, 0x12, 0x04, 0xD0, 0x10]).unwrap();
+    a.registers.pc = Addr(0x200);
+    a.registers.i = Addr(0x200);
+    assert_eq!(a.try_step_many(3), Ok(3));
+
+    let mut state = Vec::new();
+    a.save_state(&mut state).unwrap();
+    let mut b = Context::<()>::default();
+    b.load_state(&state[..]).unwrap();
+    assert_eq!(a.registers, b.registers);
+    assert_eq!(a.quirks, b.quirks);
+    assert_eq!(a.rng, b.rng);
+    assert!(b.is_hires());
+    assert_eq!(a.screen_128x64().get_pixel(0, 0), b.screen_128x64().get_pixel(0, 0));
+    assert_eq!(a.memory.as_bytes_ref()[..], b.memory.as_bytes_ref()[..]);
+
+    assert_eq!(crc32(b"123456789"), 0xCBF43926);
+
+    assert_eq!(Context::<(), Memory64K>::default().load_state(&state[..]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    assert_eq!(b.load_state(&state[..50]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+    let payload = &state[14 .. state.len() - 4 - 9]; // version 1: no rng
+    let mut v1 = [&MAGIC[..], &1u16.to_le_bytes(), &(payload.len() as u32).to_le_bytes(), payload, &crc32(payload).to_le_bytes()].concat();
+    let mut c = Context::<()> { rng: Rng::CosmacVip { seed: 7 }, ..Context::default() };
+    c.load_state(&v1[..]).unwrap();
+    assert_eq!(c.registers, a.registers);
+    assert_eq!(c.rng, Rng::CosmacVip { seed: 7 });
+    v1[8] = 3;
+    assert_eq!(c.load_state(&v1[..]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+    state[100] ^= 0x01;
+    assert_eq!(b.load_state(&state[..]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+}
+
+#[test] fn test_corrupt_planes() {
+    let mut state = Vec::new();
+    Context::<()>::default().save_state(&mut state).unwrap();
+    let mut payload = state[14 .. state.len() - 4].to_vec();
+    let planes = 3 + 16 + 2 + 2 + 1 + 32 + 1 + 1 + 1; // quirks, v, i, pc, sp, stack, dt, st, flags
+    assert_eq!(payload[planes], 1);
+    payload[planes] = 0xFF;
+    let state = [&state[..14], &payload[..], &crc32(&payload).to_le_bytes()].concat();
+
+    let mut ctx = Context::<()>::default();
+    let err = ctx.load_state(&state[..]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(err.to_string(), "corrupt save state (plane mask out of range)");
+    assert_eq!(ctx.planes, 1, "a rejected state shouldn't be partially loaded");
+}
@@ -47,8 +47,99 @@ impl ScreenMonochrome64x32 {
                 let x = x + ox;
                 let y = y + oy;
                 if let Some(original) = self.try_get_pixel(x, y) {
-                    overlap |= original;
                     if row & (0x80 >> ox) != 0 { // left to right
+                        overlap |= original;
+                        self.set_pixel(x, y, !original); // XOR behavior
+                    }
+                }
+            }
+        }
+        overlap
+    }
+
+    /// Like [`draw_sprite`](Self::draw_sprite), but pixels past the right/bottom edges wrap around to the left/top edges instead of being clipped.
+    pub fn draw_sprite_wrapping(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+        let mut overlap = false;
+        for (oy, row) in sprite.iter().copied().enumerate() {
+            for ox in 0 .. 8 {
+                let x = (x + ox) % Self::WIDTH;
+                let y = (y + oy) % Self::HEIGHT;
+                let original = self.get_pixel(x, y);
+                if row & (0x80 >> ox) != 0 { // left to right
+                    overlap |= original;
+                    self.set_pixel(x, y, !original); // XOR behavior
+                }
+            }
+        }
+        overlap
+    }
+}
+
+
+
+/// 128 x 64 x 1 bit per pixel = 1024 bytes = 64 owords
+#[derive(Clone, Copy, Zeroable, Pod)] #[repr(transparent)] pub struct ScreenMonochrome128x64([u128; 64]);
+impl Default for ScreenMonochrome128x64 { fn default() -> Self { Self::new() } }
+
+impl ScreenMonochrome128x64 {
+    pub const fn new() -> Self { Self([0; 64]) }
+    pub const WIDTH     : usize = 128;
+    pub const HEIGHT    : usize = 64;
+
+    pub fn clear(&mut self) {
+        self.0.fill(0)
+    }
+
+    pub fn try_get_pixel(&self, x: usize, y: usize) -> Option<bool> {
+        let row = *self.0.get(y)?;
+        let mask = (1u128 << 127).checked_shr(x.try_into().ok()?)?;
+        Some(row & mask != 0)
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> bool {
+        let pixel = self.try_get_pixel(x, y);
+        debug_assert!(pixel.is_some(), "get_pixel({x}, {y}) out of bounds");
+        pixel.unwrap_or(false)
+    }
+
+    pub fn try_set_pixel(&mut self, x: usize, y: usize, value: bool) -> Result<(), ()> {
+        let mask = (1u128 << 127).checked_shr(x.try_into().map_err(|_| ())?).ok_or(())?;
+        let row = self.0.get_mut(y).ok_or(())?;
+        if value {
+            *row |= mask;
+        } else {
+            *row &=!mask;
+        }
+        Ok(())
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, value: bool) {
+        let _ = self.try_set_pixel(x, y, value);
+    }
+
+    /// Draw an 8 pixel wide sprite, clipping at the edges of the screen.
+    pub fn draw_sprite(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+        self.draw_sprite_scaled(x, y, 8, sprite.iter().map(|&row| u16::from(row) << 8), 1, false)
+    }
+
+    /// XOR a `width` (≤ 16) pixel wide sprite onto the screen, with each sprite pixel covering `scale` x `scale` screen pixels.
+    ///
+    /// `rows` are left aligned: the most significant bit is the leftmost pixel.
+    /// If `wrap`, pixels past the right/bottom edges wrap around to the left/top edges instead of being clipped.
+    /// Returns `true` if any pixels were turned off.
+    pub fn draw_sprite_scaled(&mut self, x: usize, y: usize, width: usize, rows: impl IntoIterator<Item = u16>, scale: usize, wrap: bool) -> bool {
+        let mut overlap = false;
+        for (oy, row) in rows.into_iter().enumerate() {
+            for ox in 0 .. width.min(16) {
+                if row & (0x8000 >> ox) == 0 { continue } // left to right
+                for (sx, sy) in (0 .. scale*scale).map(|s| (s % scale, s / scale)) {
+                    let (mut x, mut y) = (x + ox*scale + sx, y + oy*scale + sy);
+                    if wrap {
+                        x %= Self::WIDTH;
+                        y %= Self::HEIGHT;
+                    }
+                    if let Some(original) = self.try_get_pixel(x, y) {
+                        overlap |= original;
                         self.set_pixel(x, y, !original); // XOR behavior
                     }
                 }
@@ -56,4 +147,78 @@ impl ScreenMonochrome64x32 {
         }
         overlap
     }
+
+    /// Scroll the screen contents down `n` pixels, clearing the rows scrolled in from the top.
+    pub fn scroll_down(&mut self, n: usize) {
+        let n = n.min(Self::HEIGHT);
+        self.0.copy_within(..Self::HEIGHT-n, n);
+        self.0[..n].fill(0);
+    }
+
+    /// Scroll the screen contents up `n` pixels, clearing the rows scrolled in from the bottom.
+    pub fn scroll_up(&mut self, n: usize) {
+        let n = n.min(Self::HEIGHT);
+        self.0.copy_within(n.., 0);
+        self.0[Self::HEIGHT-n..].fill(0);
+    }
+
+    /// Scroll the screen contents left `n` pixels, clearing the columns scrolled in from the right.
+    pub fn scroll_left(&mut self, n: usize) {
+        let n = u32::try_from(n).unwrap_or(u32::MAX);
+        for row in self.0.iter_mut() { *row = row.checked_shl(n).unwrap_or(0) }
+    }
+
+    /// Scroll the screen contents right `n` pixels, clearing the columns scrolled in from the left.
+    pub fn scroll_right(&mut self, n: usize) {
+        let n = u32::try_from(n).unwrap_or(u32::MAX);
+        for row in self.0.iter_mut() { *row = row.checked_shr(n).unwrap_or(0) }
+    }
+
+    /// Downsample to 64 x 32, lighting a pixel if any pixel of the corresponding 2 x 2 block is lit.
+    pub fn to_64x32(&self) -> ScreenMonochrome64x32 {
+        let mut screen = ScreenMonochrome64x32::new();
+        for y in 0 .. ScreenMonochrome64x32::HEIGHT {
+            for x in 0 .. ScreenMonochrome64x32::WIDTH {
+                let lit = (0 .. 4).any(|s| self.get_pixel(2*x + s%2, 2*y + s/2));
+                screen.set_pixel(x, y, lit);
+            }
+        }
+        screen
+    }
+}
+
+/// 128 x 64 x 2 bitplanes = 4 colors = 2048 bytes (XO-CHIP)
+#[derive(Clone, Copy, Default, Zeroable, Pod)] #[repr(transparent)] pub struct ScreenBitplanes128x64(pub [ScreenMonochrome128x64; 2]);
+
+impl ScreenBitplanes128x64 {
+    pub const fn new() -> Self { Self([ScreenMonochrome128x64::new(); 2]) }
+    pub const WIDTH     : usize = 128;
+    pub const HEIGHT    : usize = 64;
+
+    /// Iterate the planes selected by `mask` (bit 0 = plane 0, bit 1 = plane 1.)
+    pub fn planes_mut(&mut self, mask: u8) -> impl Iterator<Item = &mut ScreenMonochrome128x64> {
+        self.0.iter_mut().enumerate().filter(move |(i, _)| mask & (1 << i) != 0).map(|(_, plane)| plane)
+    }
+
+    /// The color index (0 ..= 3) of a pixel: bit 0 from plane 0, bit 1 from plane 1.
+    pub fn get_color(&self, x: usize, y: usize) -> u8 {
+        u8::from(self.0[0].get_pixel(x, y)) | u8::from(self.0[1].get_pixel(x, y)) << 1
+    }
+
+    /// Combine all planes into a single monochrome screen, lighting any pixel that isn't color 0.
+    pub fn to_monochrome(&self) -> ScreenMonochrome128x64 {
+        let mut screen = self.0[0];
+        for (dst, src) in screen.0.iter_mut().zip(self.0[1].0.iter()) { *dst |= *src }
+        screen
+    }
+}
+
+#[test] fn test_scroll_128x64() {
+    let mut screen = ScreenMonochrome128x64::new();
+    screen.set_pixel(0, 0, true);
+    screen.scroll_right(4);
+    screen.scroll_down(2);
+    assert!(screen.get_pixel(4, 2));
+    screen.scroll_left(5);
+    assert!(!(0 .. 128).any(|x| screen.get_pixel(x, 2)));
 }
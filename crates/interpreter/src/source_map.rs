@@ -0,0 +1,90 @@
+use crate::*;
+use std::io::{self, BufRead, Write};
+
+
+
+/// Maps instruction addresses to assembler source lines, for source level breakpoints and stack traces.
+///
+/// ### Text format
+/// One entry per line: `ADDR LINE FILE`, where `ADDR` is hexadecimal, `LINE` is 1-based decimal, and `FILE` is the rest
+/// of the line (so it may contain spaces.)  Blank lines and lines starting with `#` are ignored.
+/// ```text
+/// # chip8 source map
+/// 0200 3 game.8o
+/// 0202 4 game.8o
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)] pub struct SourceMap {
+    entries: Vec<SourceLocation>, // sorted by addr
+}
+
+/// An address and the source line it was assembled from.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)] pub struct SourceLocation {
+    pub addr: Addr,
+    pub file: String,
+    pub line: u32,
+}
+
+impl SourceMap {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn push(&mut self, addr: Addr, file: impl Into<String>, line: u32) {
+        let loc = SourceLocation { addr, file: file.into(), line };
+        let idx = self.entries.partition_point(|e| e.addr <= addr);
+        self.entries.insert(idx, loc);
+    }
+
+    pub fn entries(&self) -> &[SourceLocation] { &self.entries }
+
+    /// The source line `addr` was assembled from, if known.
+    pub fn location(&self, addr: Addr) -> Option<&SourceLocation> {
+        let idx = self.entries.partition_point(|e| e.addr < addr);
+        self.entries.get(idx).filter(|e| e.addr == addr)
+    }
+
+    /// Addresses assembled from `line` of any file whose path ends with `file` (so relative and absolute paths match.)
+    pub fn addrs(&self, file: &str, line: u32) -> impl Iterator<Item = Addr> + '_ {
+        let file = normalize(file);
+        self.entries.iter().filter(move |e| e.line == line && paths_match(&normalize(&e.file), &file)).map(|e| e.addr)
+    }
+
+    pub fn read(r: impl BufRead) -> io::Result<Self> {
+        let mut map = Self::new();
+        for (n, line) in r.lines().enumerate() {
+            let line = line?;
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() || line.starts_with('#') { continue }
+            let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("line {}: expected `ADDR LINE FILE`, got {line:?}", n+1));
+            let mut parts = line.splitn(3, ' ');
+            let addr = parts.next().and_then(|a| u16::from_str_radix(a, 16).ok()).ok_or_else(invalid)?;
+            let src  = parts.next().and_then(|l| l.parse().ok()).ok_or_else(invalid)?;
+            let file = parts.next().filter(|f| !f.is_empty()).ok_or_else(invalid)?;
+            map.push(Addr(addr), file, src);
+        }
+        Ok(map)
+    }
+
+    pub fn write(&self, mut w: impl Write) -> io::Result<()> {
+        for e in self.entries.iter() { writeln!(w, "{:04X} {} {}", e.addr.0, e.line, e.file)? }
+        Ok(())
+    }
+}
+
+fn normalize(path: &str) -> String { path.replace('\\', "/") }
+fn paths_match(a: &str, b: &str) -> bool {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    long == short || long.ends_with(&format!("/{short}"))
+}
+
+#[test] fn test_source_map() {
+    let text = "# comment\n0202 4 src/my game.8o\n0200 3 src/my game.8o\n\n0300 1 lib.8o\n";
+    let map = SourceMap::read(text.as_bytes()).unwrap();
+    assert_eq!(map.location(Addr(0x200)).map(|l| l.line), Some(3));
+    assert_eq!(map.location(Addr(0x201)), None);
+    assert_eq!(map.addrs("C:\\project\\src\\my game.8o", 4).collect::<Vec<_>>(), [Addr(0x202)]);
+    assert_eq!(map.addrs("game.8o", 4).count(), 0);
+
+    let mut out = Vec::new();
+    map.write(&mut out).unwrap();
+    assert_eq!(SourceMap::read(&out[..]).unwrap(), map);
+    assert!(SourceMap::read("0200 x.8o".as_bytes()).is_err());
+}
@@ -12,6 +12,16 @@ pub trait Syscalls {
     fn sound_play(&self);
     fn sound_stop(&self);
     fn render(&self, screen: &ScreenMonochrome64x32);
+
+    /// Render a SUPER-CHIP screen.  Defaults to downsampling to 64 x 32 for [`render`](Self::render).
+    fn render_128x64(&self, screen: &ScreenMonochrome128x64) { self.render(&screen.to_64x32()) }
+
+    /// Render an XO-CHIP screen.  Defaults to merging all planes for [`render_128x64`](Self::render_128x64).
+    fn render_bitplanes(&self, screen: &ScreenBitplanes128x64) { self.render_128x64(&screen.to_monochrome()) }
+
+    /// XO-CHIP's 1-bit audio `pattern` (`F002`) or `pitch` (`FX3A`) changed.
+    /// The pattern should be played, most significant bit first, at <code>4000 * 2<sup>(pitch - 64) / 48</sup></code> bits per second while sound is playing.
+    fn sound_pattern(&self, _pattern: &[u8; 16], _pitch: u8) {}
 }
 
 impl Syscalls for () {
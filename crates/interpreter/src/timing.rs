@@ -0,0 +1,186 @@
+use crate::*;
+
+
+
+/// [COSMAC VIP](https://en.wikipedia.org/wiki/COSMAC_VIP) timing, in 1802 machine cycles (8 clocks, ≈ 4.5 µs.)
+///
+/// Each frame, the CDP1861 interrupts the CPU, then steals a machine cycle per displayed byte for DMA, leaving the rest to
+/// the CHIP-8 interpreter.  Instruction costs are estimated from the VIP interpreter's routines (typically 2 machine
+/// cycles per 1802 instruction), and depend on state: skips cost more when taken, `DXYN` by sprite height and
+/// alignment, `FX33` by digit values, `FX55`/`FX65` by register count.  See Laurence Scotford's
+/// [CHIP-8 on the COSMAC VIP](https://laurencescotford.com/chip-8-on-the-cosmac-vip-index/) series for the routines.
+///
+/// Use with <code>[Quirks]::[COSMAC_VIP](Quirks::COSMAC_VIP)</code> and [`Context::try_step_frame_vip`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)] pub struct VipTiming {
+    cycles: u32, // spent this frame, possibly past CPU_CYCLES_PER_FRAME if the last instruction ran over
+}
+
+impl VipTiming {
+    /// 1.76064 MHz / 8 clocks per machine cycle.
+    pub const MACHINE_CYCLES_PER_SECOND : u32 = 1_760_640 / 8;
+
+    /// 262 lines of 14 machine cycles each (≈ 60 Hz.)
+    pub const CYCLES_PER_FRAME          : u32 = 262 * 14;
+
+    /// 32 rows of 8 bytes, each displayed on 4 consecutive lines.
+    pub const DMA_CYCLES_PER_FRAME      : u32 = 128 * 8;
+
+    /// The interpreter's interrupt routine: display setup, and decrementing timers.
+    pub const INTERRUPT_CYCLES          : u32 = 46;
+
+    /// Machine cycles left to run CHIP-8 instructions each frame.
+    pub const CPU_CYCLES_PER_FRAME      : u32 = Self::CYCLES_PER_FRAME - Self::DMA_CYCLES_PER_FRAME - Self::INTERRUPT_CYCLES;
+
+    /// Fetching, decoding, and dispatching an instruction through the interpreter's main loop.
+    pub const FETCH_CYCLES              : u32 = 40;
+
+    pub const fn new() -> Self { Self { cycles: 0 } }
+
+    /// Machine cycles spent on instructions so far this frame.
+    pub fn cycles(&self) -> u32 { self.cycles }
+
+    /// Machine cycles `op` would take to execute, given `ctx`'s current state (including [`FETCH_CYCLES`](Self::FETCH_CYCLES).)
+    pub fn cost<S: Syscalls, M: Memory, O: Observer>(ctx: &Context<S, M, O>, op: Op) -> u32 {
+        Self::FETCH_CYCLES + op.decode(&mut Cost(ctx))
+    }
+}
+
+/// How [`Context::try_step_frame`] times a frame's instructions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)] pub enum FrameTiming {
+    /// A flat number of instructions per 60 Hz frame, regardless of opcode.  The default is 500 Hz (`Steps(500/60)`.)
+    Steps(usize),
+
+    /// Per-opcode COSMAC VIP cycle costs, as [`Context::try_step_frame_vip`].
+    Vip(VipTiming),
+}
+
+impl Default for FrameTiming {
+    fn default() -> Self { FrameTiming::Steps(500/60) }
+}
+
+impl<S: Syscalls, M: Memory, O: Observer> Context<S, M, O> {
+    /// Run one frame of instructions timed by `timing`, then the 60 Hz [`step_clocks`](Self::step_clocks).  Returns the number of instructions executed.
+    ///
+    /// Blocking ends the frame's instructions early.  A [`Fault`] does too, and is returned after the clocks step.
+    pub fn try_step_frame(&mut self, timing: &mut FrameTiming) -> Result<usize, Fault> {
+        match timing {
+            FrameTiming::Steps(steps) => {
+                let result = self.try_step_many(*steps);
+                self.step_clocks();
+                result
+            },
+            FrameTiming::Vip(timing) => self.try_step_frame_vip(timing),
+        }
+    }
+
+    /// Run one frame of [`VipTiming`]: instructions until the frame's CPU cycles are spent, then the 60 Hz interrupt
+    /// ([`step_clocks`](Self::step_clocks).)  Returns the number of instructions executed.
+    ///
+    /// An instruction that starts before the interrupt finishes, borrowing cycles from the next frame.
+    /// Blocking (`DXYN` waiting for the interrupt, `FX0A` waiting for a key) idles until the interrupt.
+    /// A [`Fault`] also idles until the interrupt, then is returned.
+    pub fn try_step_frame_vip(&mut self, timing: &mut VipTiming) -> Result<usize, Fault> {
+        let mut steps = 0;
+        let mut result = Ok(());
+        while timing.cycles < VipTiming::CPU_CYCLES_PER_FRAME {
+            let cost = VipTiming::cost(self, Op(self.memory.read16(self.registers.pc)));
+            match self.try_step_single() {
+                Ok(StepOutcome::Stepped) => { timing.cycles += cost; steps += 1 },
+                Ok(_)       => break,
+                Err(fault)  => { result = Err(fault); break },
+            }
+        }
+        timing.cycles = timing.cycles.saturating_sub(VipTiming::CPU_CYCLES_PER_FRAME);
+        self.step_clocks();
+        result.map(|()| steps)
+    }
+}
+
+
+
+/// Execution cost of an [`Op`], excluding [`VipTiming::FETCH_CYCLES`].
+struct Cost<'a, S: Syscalls, M: Memory, O: Observer>(&'a Context<S, M, O>);
+impl<S: Syscalls, M: Memory, O: Observer> Cost<'_, S, M, O> {
+    fn skip(&self, cycles: u32, skip: bool) -> u32 { if skip { cycles + 4 } else { cycles } }
+}
+impl<S: Syscalls, M: Memory, O: Observer> Decode for Cost<'_, S, M, O> {
+    type Result = u32;
+
+    fn invalid              (&mut self, _op: u16)                   -> u32 { 0 }
+    fn call_mcs             (&mut self, _addr: Addr)                -> u32 { 0 }
+    fn display_clear        (&mut self)                             -> u32 { 24 + 256 * 6 } // 3 1802 instructions per display byte
+    fn flow_return          (&mut self)                             -> u32 { 10 }
+    fn flow_goto            (&mut self, _addr: Addr)                -> u32 { 12 }
+    fn flow_call            (&mut self, _addr: Addr)                -> u32 { 26 }
+    fn skip_if_v_eq_c       (&mut self, v: V, c: u8)                -> u32 { self.skip(10, self.0.registers[v] == c) }
+    fn skip_if_v_ne_c       (&mut self, v: V, c: u8)                -> u32 { self.skip(10, self.0.registers[v] != c) }
+    fn skip_if_v_eq_v       (&mut self, vx: V, vy: V)               -> u32 { self.skip(14, self.0.registers[vx] == self.0.registers[vy]) }
+    fn set_v_c              (&mut self, _vx: V, _c: u8)             -> u32 { 6 }
+    fn add_v_c              (&mut self, _vx: V, _c: u8)             -> u32 { 10 }
+    fn set_v_v              (&mut self, _vx: V, _vy: V)             -> u32 { 44 } // 8XYN all run through a self modifying ALU stub
+    fn bitor_v_v            (&mut self, _vx: V, _vy: V)             -> u32 { 44 }
+    fn bitand_v_v           (&mut self, _vx: V, _vy: V)             -> u32 { 44 }
+    fn bitxor_v_v           (&mut self, _vx: V, _vy: V)             -> u32 { 44 }
+    fn add_v_v              (&mut self, _vx: V, _vy: V)             -> u32 { 44 }
+    fn sub_v_v              (&mut self, _vx: V, _vy: V)             -> u32 { 44 }
+    fn shr1_v               (&mut self, _vx: V, _vy: V)             -> u32 { 44 }
+    fn sub_v_v_alt          (&mut self, _vx: V, _vy: V)             -> u32 { 44 }
+    fn shl1_v               (&mut self, _vx: V, _vy: V)             -> u32 { 44 }
+    fn skip_if_v_ne_v       (&mut self, vx: V, vy: V)               -> u32 { self.skip(14, self.0.registers[vx] != self.0.registers[vy]) }
+    fn set_i_c              (&mut self, _c: Addr)                   -> u32 { 12 }
+    fn set_pc_v0_plus_c     (&mut self, _v0: (), _c: Addr)          -> u32 { 22 }
+    fn set_v_rand_mask      (&mut self, _v: V, _mask: u8)           -> u32 { 36 }
+    fn skip_if_pressed      (&mut self, key: V)                     -> u32 { self.skip(14, self.0.syscalls.is_pressed(self.0.registers[key])) }
+    fn skip_unless_pressed  (&mut self, key: V)                     -> u32 { self.skip(14, !self.0.syscalls.is_pressed(self.0.registers[key])) }
+    fn get_delay_timer      (&mut self, _v: V)                      -> u32 { 10 }
+    fn await_key            (&mut self, _v: V)                      -> u32 { 20 }
+    fn set_delay_timer      (&mut self, _v: V)                      -> u32 { 10 }
+    fn set_sound_timer      (&mut self, _v: V)                      -> u32 { 10 }
+    fn add_i_v              (&mut self, _v: V)                      -> u32 { 16 }
+    fn set_i_sprite         (&mut self, _v: V)                      -> u32 { 16 }
+    fn reg_dump             (&mut self, v: V)                       -> u32 { 14 + 14 * (u32::from(v.0.to_u8()) + 1) }
+    fn reg_load             (&mut self, v: V)                       -> u32 { 14 + 14 * (u32::from(v.0.to_u8()) + 1) }
+
+    fn set_i_bcd(&mut self, v: V) -> u32 {
+        let [hundreds, tens, ones] = bcd(self.0.registers[v]);
+        84 + 16 * u32::from(hundreds + tens + ones) // digits are found by repeated subtraction
+    }
+
+    fn draw_x_y_h(&mut self, vx: V, _vy: V, h: Nibble) -> u32 {
+        // each row is shifted into place a bit at a time, and unaligned rows span two display bytes
+        let shift = u32::from(self.0.registers[vx] % 8);
+        let row = if shift == 0 { 24 } else { 36 + 8 * shift };
+        26 + u32::from(h.to_u8()) * row
+    }
+}
+
+#[test] fn test_vip_timing() {
+    let program = [
+        0x60, 0x00,             // 200: V0 <- 0
+        0x70, 0x01,             // 202: V0 += 1
+        0x12, 0x02,             // 204: goto 202
+    ];
+    let mut ctx = Context::<()> { quirks: Quirks::COSMAC_VIP, ..Context::default() };
+    ctx.memory.copy_from_slice(Addr(0x200), &program).unwrap();
+    ctx.registers.pc = Addr(0x200);
+    ctx.registers.delay_timer = 10;
+
+    let mut timing = VipTiming::new();
+    let steps = ctx.try_step_frame_vip(&mut timing).unwrap();
+    let (set, add, goto) = (40 + 6, 40 + 10, 40 + 12);
+    assert_eq!(steps, 1 + (VipTiming::CPU_CYCLES_PER_FRAME - set).div_ceil((add + goto) / 2) as usize);
+    assert!(timing.cycles() < add.max(goto), "only the overrun carries over");
+    assert_eq!(ctx.registers.delay_timer, 9);
+
+    // sprite draws wait for the interrupt (Quirks::display_wait), so one per frame
+    let program = [
+        0xD0, 0x05,             // 200: draw(V0, V0, 5)
+        0x12, 0x00,             // 202: goto 200
+    ];
+    ctx.memory.copy_from_slice(Addr(0x200), &program).unwrap();
+    ctx.registers.pc = Addr(0x200);
+    ctx.registers.v[0] = 3;
+    ctx.try_step_frame_vip(&mut timing).unwrap();
+    assert_eq!(ctx.try_step_frame_vip(&mut timing), Ok(2));
+    assert_eq!(VipTiming::cost(&ctx, Op(0xD005)), 40 + 26 + 5 * (36 + 8 * 3));
+}
@@ -13,7 +13,7 @@ use std::marker::PhantomData;
 
 const IDS_BEFORE_REUSE  : usize     = 1000; // Allocate this many IDs before reusing a previously allocated ID (better detection of UAF bugs)
 const MAX_STEP          : Duration  = Duration::from_secs(1);
-const INSTRUCTION_HZ    : u16       = 500; // instructions per second
+const INSTRUCTION_HZ    : u16       = 500; // instructions per second, without VipTiming
 const CLOCK_HZ          : u16       = 60; // clock steps per second
 
 
@@ -29,19 +29,20 @@ pub fn set_syscalls_static(syscalls: &'static dyn Syscalls) {
 
 /// Create a new [`Context`] and return an opaque identifier for it.
 pub fn create_context(program: impl Read) -> ContextId {
-    let mut ctx = Context::default();
+    let mut ctx = Context::<TlsSyscalls>::default();
     ctx.registers.pc = Addr::PROGRAM_START_TYPICAL;
     ctx.memory.copy_from_io(ctx.registers.pc, program).expect("failed to copy ROM into memory");
     ctx.memory.copy_from_slice(Addr::TYPICAL_FONTS_START, bytemuck::cast_slice(font::DEFAULT)).expect("failed to copy font into memory"); // ≈ pointless?
+    ctx.memory.copy_from_slice(Addr::TYPICAL_LARGE_FONTS_START, bytemuck::cast_slice(font::LARGE)).expect("failed to copy large font into memory");
 
     ContextId::new(TLS.with(|tls| {
         let mut tls = tls.borrow_mut();
         if let Some(ContextId(idx, _)) = (tls.contexts_free_list.len() > IDS_BEFORE_REUSE).then(|| tls.contexts_free_list.pop_front()).flatten() {
-            tls.contexts[idx].replace(ctx);
+            tls.contexts[idx].replace(Slot { ctx, rewind: None, timing: None, fault: None });
             idx
         } else {
             let idx = tls.contexts.len();
-            tls.contexts.push(Some(ctx));
+            tls.contexts.push(Some(Slot { ctx, rewind: None, timing: None, fault: None }));
             idx
         }
     }))
@@ -57,6 +58,51 @@ pub fn destroy_context(id: ContextId) {
     });
 }
 
+/// Start keeping [`Rewind`](rewind::Rewind) snapshots of a [`Context`] every `interval` frames, enough to [`rewind`] at least `history`.
+/// Replaces (and forgets) any previous rewind history.  Panics if the context doesn't exist.
+pub fn enable_rewind(id: ContextId, interval: u32, history: Duration) {
+    with_slot(id, |slot| slot.rewind = Some(rewind::Rewind::for_frames(interval, frames(history))));
+}
+
+/// Stop keeping rewind snapshots of a [`Context`], freeing any history.  Panics if the context doesn't exist.
+pub fn disable_rewind(id: ContextId) {
+    with_slot(id, |slot| slot.rewind = None);
+}
+
+/// Rewind a [`Context`] by at least `duration` of gameplay (or as far back as its history goes.)
+/// Returns how far it was actually rewound, which is zero if [`enable_rewind`] wasn't called.  Panics if the context doesn't exist.
+///
+/// If the history turns out to be corrupt, it's discarded (as if by [`disable_rewind`]) and zero is returned.
+pub fn rewind(id: ContextId, duration: Duration) -> Duration {
+    with_slot(id, |slot| {
+        let Some(rewind) = slot.rewind.as_mut() else { return Duration::ZERO };
+        let Ok(frames) = rewind.rewind(&mut slot.ctx, frames(duration)) else {
+            slot.rewind = None;
+            return Duration::ZERO;
+        };
+        slot.fault = None;
+        Duration::from_millis(frames * 1000 / u64::from(CLOCK_HZ))
+    })
+}
+
+/// The [`Fault`] that halted a [`Context`] during the last [`update`], if any.  Panics if the context doesn't exist.
+///
+/// A faulted context retries the faulting instruction every frame, so this stays set until something (e.g. [`rewind`]) moves it past.
+pub fn fault(id: ContextId) -> Option<Fault> {
+    with_slot(id, |slot| slot.fault)
+}
+
+/// Time a [`Context`]'s instructions with [`VipTiming`], charging per-opcode cycle costs against each 60 Hz frame.
+/// Panics if the context doesn't exist.
+pub fn enable_vip_timing(id: ContextId) {
+    with_slot(id, |slot| slot.timing = Some(VipTiming::new()));
+}
+
+/// Run a flat 500 instructions per second on a [`Context`], regardless of opcode (the default.)  Panics if the context doesn't exist.
+pub fn disable_vip_timing(id: ContextId) {
+    with_slot(id, |slot| slot.timing = None);
+}
+
 /// Update all [`tls`]-owned [`Context`]s for this thread.
 pub fn update() {
     let now = Instant::now();
@@ -68,9 +114,17 @@ pub fn update() {
 
         // Step logic
         while now >= tls.next_step {
-            for ctx in tls.contexts.iter_mut().flatten() {
-                ctx.try_step_many((INSTRUCTION_HZ/CLOCK_HZ).into());
-                ctx.step_clocks();
+            for Slot { ctx, rewind, timing, fault } in tls.contexts.iter_mut().flatten() {
+                if let Some(rewind) = rewind { rewind.frame(ctx) }
+                // a Fault leaves pc on the faulting instruction, halting the context
+                let result = if let Some(timing) = timing {
+                    ctx.try_step_frame_vip(timing).map(|_| ())
+                } else {
+                    let result = ctx.try_step_many((INSTRUCTION_HZ/CLOCK_HZ).into()).map(|_| ());
+                    ctx.step_clocks();
+                    result
+                };
+                *fault = result.err();
             }
             tls.next_step += Duration::from_millis((1000/CLOCK_HZ).into());
         }
@@ -85,11 +139,24 @@ thread_local! {
 }
 
 struct Tls {
-    contexts:           Vec<Option<Context<TlsSyscalls>>>,
+    contexts:           Vec<Option<Slot>>,
     contexts_free_list: VecDeque<ContextId>,
     next_step:          Instant,
 }
 
+struct Slot {
+    ctx:    Context<TlsSyscalls>,
+    rewind: Option<rewind::Rewind>,
+    timing: Option<VipTiming>,
+    fault:  Option<Fault>, // from the last update
+}
+
+fn with_slot<R>(id: ContextId, f: impl FnOnce(&mut Slot) -> R) -> R {
+    TLS.with(|tls| f(tls.borrow_mut().contexts.get_mut(id.0).and_then(|c| c.as_mut()).expect("context doesn't exist or already destroyed")))
+}
+
+fn frames(duration: Duration) -> u64 { (duration.as_millis() * u128::from(CLOCK_HZ)).div_ceil(1000).try_into().unwrap_or(u64::MAX) }
+
 impl Default for Tls {
     fn default() -> Self {
         Self {
@@ -110,6 +177,7 @@ impl Syscalls for PanicSyscalls {
     fn sound_play(&self)                            { panic() }
     fn sound_stop(&self)                            { panic() }
     fn render(&self, _: &ScreenMonochrome64x32)     { panic() }
+    fn render_128x64(&self, _: &ScreenMonochrome128x64) { panic() }
 }
 
 #[derive(Default)] struct TlsSyscalls;
@@ -120,4 +188,15 @@ impl Syscalls for TlsSyscalls {
     fn sound_play(&self)                                { SYSCALLS.with(|sc| sc.borrow().sound_play()) }
     fn sound_stop(&self)                                { SYSCALLS.with(|sc| sc.borrow().sound_stop()) }
     fn render(&self, screen: &ScreenMonochrome64x32)    { SYSCALLS.with(|sc| sc.borrow().render(screen)) }
+    fn render_128x64(&self, screen: &ScreenMonochrome128x64) { SYSCALLS.with(|sc| sc.borrow().render_128x64(screen)) }
+}
+
+#[test] fn test_fault() {
+    set_syscalls_static(&());
+    let id = create_context(&[0x00, 0xEE][..]); // return (underflow)
+    assert_eq!(fault(id), None);
+    assert!(with_slot(id, |slot| slot.timing.is_none()), "flat 500 Hz by default");
+    update();
+    assert_eq!(fault(id).map(|f| (f.kind, f.pc)), Some((FaultKind::StackUnderflow, Addr::PROGRAM_START_TYPICAL)));
+    destroy_context(id);
 }
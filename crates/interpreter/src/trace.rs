@@ -0,0 +1,178 @@
+//! Instruction traces: one line of state per executed instruction, for diffing against other emulators.
+//!
+//! # Format
+//!
+//! Each line is the state *before* an instruction executes, in fixed columns (à la [nestest](https://www.qmtpro.com/~nes/misc/nestest.log)):
+//!
+//! ```text
+//! 0200  6A02  VA <- 0x02                                      V0:00 V1:00 V2:00 V3:00 V4:00 V5:00 V6:00 V7:00 V8:00 V9:00 VA:00 VB:00 VC:00 VD:00 VE:00 VF:00 I:0000 DT:00 ST:00 SP:0
+//! ```
+//!
+//! | Column    | Field                                                                 |
+//! | --------- | --------------------------------------------------------------------- |
+//! | 0         | PC (4 hex digits)                                                     |
+//! | 6         | Opcode (4 hex digits)                                                 |
+//! | 12        | [`Op`] disassembly, padded to [`DISASSEMBLY_WIDTH`] (informational)   |
+//! | 60        | `NAME:HEX` state fields: `V0` ..= `VF`, `I`, `DT` (delay timer), `ST` (sound timer), `SP` (stack depth) |
+//!
+//! Blocked instructions (waiting on a key or vblank) and [`Fault`]s aren't executed, and so aren't traced.
+//! [`diff`] compares the PC, opcode, and whichever state fields both traces have, so traces from other emulators don't
+//! need disassembly or every field to be compared.
+
+use crate::*;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, BufRead, Write};
+
+
+
+/// Width of the disassembly column.
+pub const DISASSEMBLY_WIDTH : usize = 46;
+
+/// Write the trace line for the instruction at `ctx`'s program counter, including the trailing newline.
+pub fn write_line<S: Syscalls, M: Memory, O: Observer>(w: impl Write, ctx: &Context<S, M, O>) -> io::Result<()> {
+    write_registers_line(w, &ctx.registers, Op(ctx.memory.read16(ctx.registers.pc)))
+}
+
+fn write_registers_line(mut w: impl Write, r: &Registers, op: Op) -> io::Result<()> {
+    write!(w, "{:04X}  {:04X}  {:<DISASSEMBLY_WIDTH$} ", r.pc.0, op.0, format!("{op:?}"))?;
+    for (i, v) in r.v.iter().enumerate() { write!(w, " V{i:X}:{v:02X}")? }
+    writeln!(w, " I:{:04X} DT:{:02X} ST:{:02X} SP:{:X}", r.i.0, r.delay_timer, r.sound_timer, r.sp)
+}
+
+/// An [`Observer`] tracing instructions as they're executed, so trace a ROM by running it in a <code>[Context]&lt;S, M, [Tracer]&lt;W&gt;&gt;</code>.
+///
+/// Each line is written once the instruction is known not to have blocked or faulted: when the next instruction is
+/// fetched, when the timers tick, or on [`finish`](Self::finish).  Write errors don't interrupt execution: the first is
+/// kept and returned by [`finish`](Self::finish).
+pub struct Tracer<W: Write> {
+    w:          W,
+    result:     io::Result<()>,
+    pending:    Vec<u8>, // fetched, but not known to have executed yet
+}
+
+impl<W: Write> Tracer<W> {
+    pub fn new(w: W) -> Self { Self { w, result: Ok(()), pending: Vec::with_capacity(192) } }
+
+    /// Write the pending line, flush, and return the writer or the first write error.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.commit();
+        self.result?;
+        self.w.flush()?;
+        Ok(self.w)
+    }
+
+    fn commit(&mut self) {
+        if !self.pending.is_empty() && self.result.is_ok() { self.result = self.w.write_all(&self.pending) }
+        self.pending.clear();
+    }
+}
+
+impl<W: Write> Observer for Tracer<W> {
+    const ACCESSES : bool = false;
+
+    fn fetch(&mut self, registers: &Registers, op: Op) {
+        self.commit();
+        let _ = write_registers_line(&mut self.pending, registers, op); // infallible
+    }
+
+    fn timer_tick(&mut self, _delay: u8, _sound: u8) { self.commit() }
+    fn fault(&mut self, _fault: Fault)  { self.pending.clear() }
+    fn key_wait(&mut self, _v: V)       { self.pending.clear() }
+    fn vblank_wait(&mut self)           { self.pending.clear() }
+}
+
+
+
+/// Where two traces first disagree.  Returned by [`diff`].
+#[derive(Clone, Debug, PartialEq, Eq)] pub struct Divergence {
+    /// The 1-based line number of the first mismatch.
+    pub line:       usize,
+    /// The mismatched fields (`"PC"`, `"OP"`, `"V0"`, `"I"`, ...), or `"EOF"` if one trace ended early.
+    pub fields:     Vec<String>,
+    /// The line before the mismatch (from `a`), whose instruction probably caused it.
+    pub previous:   Option<String>,
+    pub a:          Option<String>,
+    pub b:          Option<String>,
+}
+
+impl Display for Divergence {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        writeln!(fmt, "traces diverge at line {}: {}", self.line, self.fields.join(", "))?;
+        if let Some(previous) = self.previous.as_ref() { writeln!(fmt, "   {previous}")? }
+        writeln!(fmt, "a: {}", self.a.as_deref().unwrap_or("<end of trace>"))?;
+        write!  (fmt, "b: {}", self.b.as_deref().unwrap_or("<end of trace>"))
+    }
+}
+
+/// Find the first line where traces `a` and `b` disagree, or `None` if they're identical (ignoring disassembly.)
+pub fn diff(a: impl BufRead, b: impl BufRead) -> io::Result<Option<Divergence>> {
+    let (mut a, mut b) = (a.lines(), b.lines());
+    let mut previous = None;
+    for line in 1 .. {
+        let (la, lb) = (a.next().transpose()?, b.next().transpose()?);
+        let fields = match (la.as_deref(), lb.as_deref()) {
+            (None, None)            => return Ok(None),
+            (Some(la), Some(lb))    => mismatched_fields(la, lb),
+            _                       => vec!["EOF".into()],
+        };
+        if !fields.is_empty() { return Ok(Some(Divergence { line, fields, previous, a: la, b: lb })) }
+        previous = la;
+    }
+    unreachable!()
+}
+
+fn mismatched_fields(a: &str, b: &str) -> Vec<String> {
+    let (a, b) = (parse(a), parse(b));
+    let mut fields = Vec::new();
+    for (name, va) in a.iter() {
+        if let Some((_, vb)) = b.iter().find(|(n, _)| n == name) {
+            if va != vb { fields.push(name.to_string()) }
+        }
+    }
+    fields
+}
+
+/// `PC`, `OP`, then the trailing `NAME:HEX` fields, as `(name, value)`.  Values are parsed to ignore leading zeros.
+fn parse(line: &str) -> Vec<(&str, Option<u32>)> {
+    let hex = |v: &str| u32::from_str_radix(v, 16).ok();
+    let mut tokens = line.split_whitespace();
+    let mut fields = vec![("PC", tokens.next().and_then(hex)), ("OP", tokens.next().and_then(hex))];
+    let state = line.split_whitespace().rev().map_while(|t| t.split_once(':')).collect::<Vec<_>>();
+    fields.extend(state.into_iter().rev().map(|(name, v)| (name, hex(v))));
+    fields
+}
+
+#[test] fn test_trace() {
+    let program = [
+        0x6A, 0x02,             // 200: VA <- 2
+        0xA3, 0x00,             // 202: I <- 0x300
+        0x7A, 0xFF,             // 204: VA += 0xFF
+        0x12, 0x04,             // 206: goto 204
+    ];
+    let run = |tweak: bool| {
+        let mut ctx = Context::<(), Memory4K, _>::with_observer((), Tracer::new(Vec::new()));
+        ctx.memory.copy_from_slice(Addr(0x200), &program).unwrap();
+        ctx.registers.pc = Addr(0x200);
+        assert_eq!(ctx.try_step_many(3), Ok(3));
+        if tweak { ctx.registers.v[0xA] ^= 1 }
+        assert_eq!(ctx.try_step_many(3), Ok(3));
+        ctx.memory.copy_from_slice(ctx.registers.pc, &[0x00, 0xEE]).unwrap(); // return underflows, so isn't traced
+        assert!(ctx.try_step_single().is_err());
+        String::from_utf8(ctx.observer.finish().unwrap()).unwrap()
+    };
+
+    let a = run(false);
+    let lines = a.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 6);
+    assert_eq!(lines[0], format!("0200  6A02  {:<DISASSEMBLY_WIDTH$}  V0:00 V1:00 V2:00 V3:00 V4:00 V5:00 V6:00 V7:00 V8:00 V9:00 VA:00 VB:00 VC:00 VD:00 VE:00 VF:00 I:0000 DT:00 ST:00 SP:0", "VA <- 0x02"));
+    assert!(lines[3].starts_with("0206  1204  pc <- 0x204 ") && lines[3].contains(" VA:01 ") && lines[3].contains(" I:0300 "), "{}", lines[3]);
+    assert_eq!(diff(a.as_bytes(), a.as_bytes()).unwrap(), None);
+
+    let b = run(true);
+    let d = diff(a.as_bytes(), b.as_bytes()).unwrap().unwrap();
+    assert_eq!((d.line, d.fields.as_slice(), d.previous.as_deref()), (4, &["VA".to_string()][..], Some(lines[2])));
+
+    // other emulators' traces needn't have disassembly or every field
+    let other = "0200 6a02 V0:0 VA:0\n0202 A300 VA:2 I:0\n0204 7AFF VA:2 I:300\n";
+    assert_eq!(diff(a.as_bytes(), other.as_bytes()).unwrap().map(|d| (d.line, d.fields)), Some((4, vec!["EOF".into()])));
+}
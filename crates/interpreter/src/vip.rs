@@ -0,0 +1,318 @@
+//! [COSMAC VIP](https://en.wikipedia.org/wiki/COSMAC_VIP) machine emulation: a [CDP1802](cdp1802) CPU, RAM, the monitor
+//! ROM, the CDP1861 video chip, and the hex keypad.
+//!
+//! [`Vip`] runs the *original* CHIP-8 interpreter as 1802 machine code, instead of emulating its behavior like
+//! [`Context`] does.  Load the interpreter into [`Vip::ram`] at `0000`, a program at `0200`, the monitor into
+//! [`Vip::monitor`], then [`reset`](Vip::reset) — the monitor sizes memory and jumps to `0000` unless `C` is held.
+//! Neither the monitor nor the interpreter are included with this crate: bring your own dumps.
+//!
+//! | Hardware      | [`Syscalls`]                                                                  |
+//! | ------------- | ----------------------------------------------------------------------------- |
+//! | CDP1861       | [`render`](Syscalls::render) once per frame, every 4th displayed line         |
+//! | Hex keypad    | [`is_pressed`](Syscalls::is_pressed) for the key latched by `OUT 2`, as `EF3` |
+//! | `Q` tone      | [`sound_play`](Syscalls::sound_play) / [`sound_stop`](Syscalls::sound_stop)   |
+//!
+//! # Machine code calls
+//!
+//! With <code>[Quirks]::machine_code</code>, a [`Context`] runs `0NNN` as a call to 1802 code at `NNN`, setting up the
+//! registers the VIP interpreter's routines would have:
+//!
+//! | Register  | Value                                                                     |
+//! | --------- | ------------------------------------------------------------------------- |
+//! | `P`, `X`  | 3, 2                                                                      |
+//! | `R2`      | `0ECF`: a small stack, growing down                                       |
+//! | `R3`      | `NNN`                                                                     |
+//! | `R5`      | The next CHIP-8 instruction                                               |
+//! | `R6`, `R7`| `VX` and `VY` of the `0XYN` instruction, with `V0` ..= `VF` at `0EF0` ..= `0EFF` |
+//! | `R8`      | Delay timer (high byte), sound timer (low byte)                           |
+//! | `RA`      | `I`                                                                       |
+//! | `RB`      | `0F00`: the display                                                       |
+//!
+//! The code returns with `D4` (`SEP R4`), after which `V0` ..= `VF` are read back from memory, and `I`, the timers, and
+//! the program counter from `RA`, `R8`, and `R5`.  Code that hasn't returned within a second of machine cycles, or that
+//! idles (`IDL`) waiting on interrupts, [faults](FaultKind::MachineCodeCall).
+//!
+//! With <code>[Quirks]::stack_in_memory</code>, the call stack would overlap the machine code stack (`0EC0` ..= `0ECF`)
+//! if it could grow past 16 entries, so calls with a larger <code>[Quirks]::stack_depth</code> fault too.
+
+use crate::*;
+use crate::cdp1802::{Bus, Cpu};
+
+
+
+/// A COSMAC VIP with 4 KiB of RAM.  See the [module](self) docs.
+pub struct Vip<S: Syscalls> {
+    pub cpu:        Cpu,
+    /// `0000` ..= `7FFF` (mirrored.)
+    pub ram:        Memory4K,
+    /// `8000` ..= `FFFF` (mirrored), and `0000` after a [`reset`](Self::reset) until the first access above `8000`.
+    pub monitor:    [u8; 512],
+    pub syscalls:   S,
+    io:             Io,
+    budget:         i32, // machine cycles owed to the CPU, negative if the last instruction ran over
+}
+
+#[derive(Clone, Copy, Default)] struct Io {
+    rom_at_zero:    bool,
+    display:        bool, // CDP1861 enabled by `INP 1`, disabled by `OUT 1`
+    ef1:            bool, // CDP1861 display status
+    keypad:         u8,
+    playing:        bool,
+}
+
+impl<S: Syscalls + Default> Default for Vip<S> {
+    fn default() -> Self { Self::with_syscalls(S::default()) }
+}
+
+impl<S: Syscalls> Vip<S> {
+    pub fn new() -> Self where S : Default { Self::default() }
+
+    /// Create a VIP around `syscalls`, with empty RAM and monitor.  Load them, then [`reset`](Self::reset).
+    pub fn with_syscalls(syscalls: S) -> Self {
+        Self { cpu: Cpu::new(), ram: Memory4K::new(), monitor: [0; 512], syscalls, io: Io::default(), budget: 0 }
+    }
+
+    /// Press the reset switch: reset the CPU, map the monitor at `0000`, and turn off the display.
+    pub fn reset(&mut self) {
+        let Self { cpu, ram, monitor, syscalls, io, .. } = self;
+        cpu.reset(&mut VipBus { ram, monitor, syscalls, io });
+        self.io = Io { rom_at_zero: true, ..Io::default() };
+        self.budget = 0;
+    }
+
+    /// Run a single 60 Hz frame of 262 lines, then [`render`](Syscalls::render) what was displayed.
+    ///
+    /// Each line is 14 machine cycles.  While the display is enabled, lines 80 ..= 207 each lose 8 cycles to DMA, an
+    /// interrupt is requested during the 2 lines before them, and `EF1` is asserted for the 4 lines at either end.
+    pub fn step_frame(&mut self) {
+        let mut lines = [[0u8; 8]; DISPLAY.end - DISPLAY.start];
+        let Self { cpu, ram, monitor, syscalls, io, budget } = self;
+        let mut bus = VipBus { ram, monitor, syscalls, io };
+        for line in 0 .. LINES {
+            let display = bus.io.display;
+            let dma = display && DISPLAY.contains(&line);
+            let interrupt = display && (DISPLAY.start - 2 .. DISPLAY.start).contains(&line);
+            bus.io.ef1 = display && ((DISPLAY.start - 4 .. DISPLAY.start).contains(&line) || (DISPLAY.end - 4 .. DISPLAY.end).contains(&line));
+
+            *budget += (CYCLES_PER_LINE - if dma { 8 } else { 0 }) as i32;
+            while *budget > 0 {
+                if interrupt { *budget -= cpu.interrupt() as i32 }
+                *budget -= cpu.step(&mut bus) as i32;
+            }
+
+            if dma {
+                for b in lines[line - DISPLAY.start].iter_mut() { *b = cpu.dma_out(&mut bus) }
+            }
+        }
+
+        let mut screen = ScreenMonochrome64x32::new();
+        for (y, line) in lines.iter().step_by(4).enumerate() {
+            for x in 0 .. ScreenMonochrome64x32::WIDTH {
+                screen.set_pixel(x, y, line[x / 8] & (0x80 >> (x % 8)) != 0);
+            }
+        }
+        syscalls.render(&screen);
+    }
+}
+
+const LINES             : usize = 262;
+const CYCLES_PER_LINE   : u32   = 14;
+const DISPLAY           : core::ops::Range<usize> = 80 .. 208;
+
+struct VipBus<'a, S: Syscalls> {
+    ram:        &'a mut Memory4K,
+    monitor:    &'a [u8; 512],
+    syscalls:   &'a S,
+    io:         &'a mut Io,
+}
+
+impl<S: Syscalls> Bus for VipBus<'_, S> {
+    fn read(&mut self, addr: u16) -> u8 {
+        let rom = addr & 0x8000 != 0;
+        if rom { self.io.rom_at_zero = false }
+        if rom || self.io.rom_at_zero { self.monitor[usize::from(addr) % 512] } else { self.ram.read(Addr(addr)) }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        if addr & 0x8000 == 0 { self.ram.write(Addr(addr), value) }
+    }
+
+    fn output(&mut self, port: u8, value: u8) {
+        match port {
+            1 => self.io.display = false,
+            2 => self.io.keypad = value & 0xF,
+            _ => {},
+        }
+    }
+
+    fn input(&mut self, port: u8) -> u8 {
+        if port == 1 { self.io.display = true }
+        0
+    }
+
+    fn ef(&mut self, flag: u8) -> bool {
+        match flag {
+            1 => self.io.ef1,
+            3 => self.syscalls.is_pressed(self.io.keypad),
+            _ => false,
+        }
+    }
+
+    fn q(&mut self, q: bool) {
+        match (self.io.playing, q) {
+            (false, true)   => self.syscalls.sound_play(),
+            (true, false)   => self.syscalls.sound_stop(),
+            _               => {},
+        }
+        self.io.playing = q;
+    }
+}
+
+
+
+/// `V0` ..= `VF` during a machine code call.
+const V_START : u16 = 0x0EF0;
+
+/// In-memory call stack entries below the machine code stack at `0EC0` ..= `0ECF`.
+const MAX_STACK_IN_MEMORY : u8 = ((0x0EC0 - Addr::SYSTEM_STACK_ETC_START.0) / 2) as u8;
+
+impl<S: Syscalls, M: Memory, O: Observer> Context<S, M, O> {
+    /// `0NNN` with [`Quirks::machine_code`]: call the 1802 code at `addr` (see the [module](self) docs.)
+    pub(crate) fn call_machine_code(&mut self, addr: Addr) -> Result<StepOutcome, Fault> {
+        if self.quirks.stack_in_memory && self.stack_capacity() > MAX_STACK_IN_MEMORY { return Err(self.fault(FaultKind::MachineCodeCall)) }
+        let op = self.memory.read16(self.registers.pc);
+        let v = self.registers.v;
+        let _ = self.memory.copy_from_slice(Addr(V_START), &v); // any Memory has at least 4 KiB
+
+        let mut cpu = Cpu::new();
+        cpu.p = 3;
+        cpu.x = 2;
+        cpu.r[0x2] = 0x0ECF;
+        cpu.r[0x3] = addr.0;
+        cpu.r[0x5] = self.registers.pc.0.wrapping_add(2);
+        cpu.r[0x6] = V_START | (op >> 8 & 0xF);
+        cpu.r[0x7] = V_START | (op >> 4 & 0xF);
+        cpu.r[0x8] = u16::from_be_bytes([self.registers.delay_timer, self.registers.sound_timer]);
+        cpu.r[0xA] = self.registers.i.0;
+        cpu.r[0xB] = Addr::SYSTEM_DISPLAY_START.0;
+
+        let mut bus = ContextBus { memory: &mut self.memory, syscalls: &self.syscalls, keypad: 0 };
+        let mut cycles = 0;
+        while cpu.p != 4 {
+            if cpu.idle || cycles >= VipTiming::MACHINE_CYCLES_PER_SECOND { return Err(self.fault(FaultKind::MachineCodeCall)) }
+            cycles += cpu.step(&mut bus);
+        }
+
+        for (i, v) in (V_START ..).zip(self.registers.v.iter_mut()) { *v = self.memory.read(Addr(i)) }
+        [self.registers.delay_timer, self.registers.sound_timer] = cpu.r[0x8].to_be_bytes();
+        self.registers.i = Addr(cpu.r[0xA]);
+        self.registers.pc = Addr(cpu.r[0x5]);
+        Ok(StepOutcome::Stepped)
+    }
+}
+
+/// A [`Context`]'s memory and keypad, for machine code calls.  `Q` is ignored in favor of the sound timer.
+struct ContextBus<'a, S: Syscalls, M: Memory> {
+    memory:     &'a mut M,
+    syscalls:   &'a S,
+    keypad:     u8,
+}
+
+impl<S: Syscalls, M: Memory> Bus for ContextBus<'_, S, M> {
+    fn read(&mut self, addr: u16) -> u8 { self.memory.read(Addr(addr)) }
+    fn write(&mut self, addr: u16, value: u8) { self.memory.write(Addr(addr), value) }
+    fn output(&mut self, port: u8, value: u8) { if port == 2 { self.keypad = value & 0xF } }
+    fn ef(&mut self, flag: u8) -> bool { flag == 3 && self.syscalls.is_pressed(self.keypad) }
+}
+
+
+
+#[cfg(test)] #[derive(Default)] struct TestSyscalls {
+    pressed:    core::cell::Cell<Option<u8>>,
+    playing:    core::cell::Cell<bool>,
+    screen:     core::cell::Cell<ScreenMonochrome64x32>,
+}
+
+#[cfg(test)] impl Syscalls for TestSyscalls {
+    fn rand(&self) -> u8 { 0 }
+    fn get_key(&self) -> Option<u8> { self.pressed.get() }
+    fn is_pressed(&self, key: u8) -> bool { self.pressed.get() == Some(key) }
+    fn sound_play(&self) { self.playing.set(true) }
+    fn sound_stop(&self) { self.playing.set(false) }
+    fn render(&self, screen: &ScreenMonochrome64x32) { self.screen.set(*screen) }
+}
+
+#[test] fn test_vip() {
+    let mut vip = Vip::<TestSyscalls>::new();
+    vip.monitor[..0x30].copy_from_slice(&[
+        0xC0, 0x80, 0x03,       // 000: R0 <- 8003, unmapping the monitor from 0000  LBR
+        0xF8, 0x80, 0xB3,       // 003: R3.1 <- 0x80                            LDI, PHI
+        0xF8, 0x0A, 0xA3,       // 006: R3.0 <- 0x0A                            LDI, PLO
+        0xD3,                   // 009: P <- 3, leaving R0 for DMA              SEP R3
+        0xF8, 0x80, 0xB1,       // 00A: R1.1 <- 0x80                            LDI, PHI
+        0xF8, 0x24, 0xA1,       // 00D: R1.0 <- 0x24 (interrupt routine)        LDI, PLO
+        0xF8, 0x0E, 0xB2,       // 010: R2.1 <- 0x0E                            LDI, PHI
+        0xF8, 0xFF, 0xA2,       // 013: R2.0 <- 0xFF (stack)                    LDI, PLO
+        0xE2,                   // 016: X <- 2                                  SEX R2
+        0x69,                   // 017: display on                              INP 1
+        0xF8, 0x05, 0x52,       // 018: M(R2) <- 5                              LDI, STR R2
+        0x62,                   // 01B: keypad latch <- M(R2++)                 OUT 2
+        0x22,                   // 01C: R2--                                    DEC R2
+        0x3E, 0x1D,             // 01D: loop until key 5 is pressed             BN3
+        0x7B,                   // 01F: beep                                    SEQ
+        0x30, 0x20,             // 020: loop                                    BR
+        0x72,                   // 022: restore D                               LDXA
+        0x70,                   // 023: restore X, P, and enable interrupts     RET
+        0x22, 0x78, 0x22, 0x52, // 024: save T, D                               DEC R2, SAV, DEC R2, STR R2
+        0xF8, 0x0F, 0xB0,       // 028: R0.1 <- 0x0F                            LDI, PHI
+        0xF8, 0x00, 0xA0,       // 02B: R0.0 <- 0x00 (display)                  LDI, PLO
+        0x30, 0x22,             // 02E: return                                  BR
+    ]);
+    vip.ram.copy_from_slice(Addr(0xF00), &[0xC0, 0, 0, 0, 0, 0, 0, 0x01]).unwrap();
+    vip.reset();
+
+    vip.step_frame(); // display enabled mid frame
+    vip.step_frame();
+    let screen = vip.syscalls.screen.get();
+    assert!(screen.get_pixel(0, 0) && screen.get_pixel(1, 0) && !screen.get_pixel(2, 0) && !screen.get_pixel(62, 0) && screen.get_pixel(63, 0));
+    assert!(!vip.syscalls.playing.get());
+
+    vip.syscalls.pressed.set(Some(5));
+    vip.step_frame();
+    assert!(vip.syscalls.playing.get());
+    assert_eq!(vip.cpu.p, 3, "interrupts should return to the main loop");
+}
+
+#[test] fn test_machine_code() {
+    let mut ctx = Context::<()>::default();
+    ctx.memory.copy_from_slice(Addr(0x200), &[0x03, 0x00, 0x00, 0x00, 0x03, 0x10]).unwrap(); // call 300, (skipped), call 310
+    ctx.memory.copy_from_slice(Addr(0x300), &[
+        0xF8, 0x2A,             // 300: D <- 0x2A                               LDI
+        0x56,                   // 302: VX <- D (V3)                            STR R6
+        0x8A, 0xFC, 0x01, 0xAA, // 303: I.0 += 1                                GLO RA, ADI, PLO RA
+        0x15, 0x15,             // 307: skip the next CHIP-8 instruction        INC R5, INC R5
+        0xD4,                   // 309: return                                  SEP R4
+    ]).unwrap();
+    ctx.memory.copy_from_slice(Addr(0x310), &[0x30, 0x10]).unwrap(); // loop forever
+    ctx.registers.pc = Addr(0x200);
+    ctx.registers.i = Addr(0x123);
+
+    assert_eq!(ctx.try_step_single().map_err(|f| f.kind), Err(FaultKind::MachineCodeCall));
+    ctx.quirks.machine_code = true;
+    assert_eq!(ctx.try_step_single(), Ok(StepOutcome::Stepped));
+    assert_eq!((ctx.registers[V(N3)], ctx.registers.i, ctx.registers.pc), (0x2A, Addr(0x124), Addr(0x204)));
+    assert_eq!(ctx.try_step_single().map_err(|f| (f.kind, f.pc)), Err((FaultKind::MachineCodeCall, Addr(0x204))));
+
+    ctx.quirks.stack_in_memory = true;
+    ctx.quirks.stack_depth = 17; // would overlap the machine code stack at 0xEC0
+    ctx.registers.pc = Addr(0x200);
+    assert_eq!(ctx.try_step_single().map_err(|f| (f.kind, f.pc)), Err((FaultKind::MachineCodeCall, Addr(0x200))));
+    ctx.quirks.stack_depth = 16;
+    assert_eq!(ctx.try_step_single(), Ok(StepOutcome::Stepped));
+
+    let mut ctx = Context::<(), Memory64K>::default();
+    ctx.quirks.machine_code = true;
+    ctx.memory.copy_from_slice(Addr(0x200), &[0x03, 0x00]).unwrap();
+    ctx.registers.pc = Addr(0x200);
+    assert!(ctx.accesses().memory_write.is_some_and(|w| w.contains(Addr(0xFFFF))), "machine code could write anywhere");
+}